@@ -59,6 +59,12 @@ export function blob_from_str(data) {
     });
 }
 
+export function blob_from_bytes(data) {
+    return new Blob([data], {
+        type: "application/zip",
+    });
+}
+
 export function event_to_file(event) {
     return event.target.files[0];
 }
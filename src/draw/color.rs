@@ -61,6 +61,36 @@ impl Color {
     pub fn to_string(self) -> String {
         format!("#{:02x}{:02x}{:02x}", self.r(), self.g(), self.b())
     }
+
+    // Color of a blackbody at the temperature (in Kelvin, 1000 to 40000), the brightest channel
+    // is 1. The approximation gives display colors, squared back to the linear ones renders
+    // are gamma corrected from
+    pub fn blackbody(kelvin: f32) -> Color {
+        let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+
+        let r = if t <= 66.0 {
+            255.0
+        } else {
+            329.69873 * (t - 60.0).powf(-0.13320476)
+        };
+        let g = if t <= 66.0 {
+            99.4708 * t.ln() - 161.11957
+        } else {
+            288.12216 * (t - 60.0).powf(-0.07551485)
+        };
+        let b = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.51773 * (t - 10.0).ln() - 305.0448
+        };
+
+        let linear = |c: f32| (c.clamp(0.0, 255.0) / 255.0).powi(2);
+        let (r, g, b) = (linear(r), linear(g), linear(b));
+        let max = r.max(g).max(b);
+        Color(Vec3D::new(r / max, g / max, b / max))
+    }
 }
 
 impl Default for Color {
@@ -68,3 +98,47 @@ impl Default for Color {
         Color::new(255, 255, 255)
     }
 }
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn daylight_is_near_white() {
+        let color = Color::blackbody(6500.0).0;
+
+        for channel in [color.x, color.y, color.z] {
+            assert!(channel > 0.9, "{color:?}");
+        }
+    }
+
+    #[test]
+    fn cold_blackbodies_are_red_and_hot_ones_blue() {
+        let color = Color::blackbody(1000.0).0;
+        assert_eq!(color.x, 1.0);
+        assert!(color.y < 0.1 && color.z == 0.0, "{color:?}");
+
+        let color = Color::blackbody(40000.0).0;
+        assert_eq!(color.z, 1.0);
+        assert!(color.x < color.y && color.y < color.z, "{color:?}");
+
+        // Bluer the hotter it gets
+        let temperatures = [
+            1000.0, 2000.0, 3500.0, 5000.0, 6500.0, 10000.0, 20000.0, 40000.0,
+        ];
+        for pair in temperatures.windows(2) {
+            let (cold, hot) = (Color::blackbody(pair[0]).0, Color::blackbody(pair[1]).0);
+            assert!(hot.z / hot.x > cold.z / cold.x, "{pair:?}");
+        }
+    }
+
+    #[test]
+    fn temperatures_are_clamped() {
+        assert_eq!(Color::blackbody(0.0), Color::blackbody(1000.0));
+        assert_eq!(Color::blackbody(-500.0), Color::blackbody(1000.0));
+        assert_eq!(Color::blackbody(100000.0), Color::blackbody(40000.0));
+        assert_eq!(Color::blackbody(f32::INFINITY), Color::blackbody(40000.0));
+    }
+}
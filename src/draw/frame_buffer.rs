@@ -1,5 +1,7 @@
 use super::color::Color;
 use crate::vector::Vec2D;
+use image::codecs::png::PngEncoder;
+use image::{ColorType, ImageEncoder};
 
 // --------------------------------------------------
 
@@ -69,6 +71,19 @@ impl FrameBuffer {
         (out, self.size)
     }
 
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut out = vec![];
+        PngEncoder::new(&mut out)
+            .write_image(
+                &self.to_vec_u8(false).0,
+                self.size.x,
+                self.size.y,
+                ColorType::Rgb8,
+            )
+            .expect("Encoding a frame buffer as png should not fail");
+        out
+    }
+
     pub fn to_vec_u32(&self) -> Vec<u32> {
         self.buffer.iter().map(|x| x.to_u32()).collect()
     }
@@ -1,6 +1,7 @@
 use std::sync::{Arc, Mutex};
 
-use crate::graphics::{self, CameraInput, Object3D, SceneList};
+use crate::graphics::{self, CameraInput, Material, Object3D, SceneList, Texture};
+use crate::image::{image_names, register_image, ImageData, ImageSource};
 use crate::misc::{
     canvas_html, document, flip_vec_u8_to_canvas, update_window_size, window, RenderState,
 };
@@ -132,6 +133,7 @@ pub fn render_frame(
                     settings_1.samples_per_pixel_full(),
                     settings_1.ray_bounce_recursion_limit(),
                     true,
+                    settings_1.stereo(),
                 );
                 *render_state_1 = RenderState::RenderingFreeze
             }
@@ -143,6 +145,17 @@ pub fn render_frame(
                     settings_1.ray_bounce_recursion_limit(),
                     true,
                     // false,
+                    settings_1.stereo(),
+                );
+                *render_state_1 = RenderState::RenderingFreeze
+            }
+            RenderState::SequenceRender => {
+                document().exit_pointer_lock();
+
+                scene.render_sequence(
+                    settings_1.samples_per_pixel_full(),
+                    settings_1.ray_bounce_recursion_limit(),
+                    settings_1.stereo(),
                 );
                 *render_state_1 = RenderState::RenderingFreeze
             }
@@ -168,6 +181,12 @@ fn register_draw_timer(
     settings: Arc<Mutex<Settings>>,
 ) {
     let closure = Closure::<dyn Fn()>::new(move || {
+        if let Some(frames) = crate::graphics::SEQUENCE.lock().unwrap().take() {
+            let download_name = format!("sequence_{:?}", thread_rng().gen::<u32>());
+            log::info!("Downloaded as {download_name:?}");
+            crate::misc::download_zip(&frames, download_name);
+        }
+
         let mut render = crate::graphics::RENDER.lock().unwrap();
         if render.0 {
             let mut render_state_1 = render_state.lock().unwrap();
@@ -364,6 +383,8 @@ fn register_keyboard_input(
                             left: pressed_key == 37 || pressed_key == 65,
                             up: pressed_key == 75,
                             down: pressed_key == 74,
+                            roll_left: pressed_key == 78,
+                            roll_right: pressed_key == 77,
                             reset: pressed_key == 81,
                             mouse_move: (*camera_input_1).mouse_move,
                         };
@@ -404,7 +425,7 @@ fn register_keyboard_input(
                                 .object_list
                                 .objects
                                 .push(Object3D::Sphere {
-                                    pos: current_scene.camera.pos + current_scene.camera.rotation,
+                                    pos: current_scene.camera.pos + current_scene.camera.direction(),
                                     radius: 0.4,
                                     material: Arc::new(graphics::Material::default()),
                                 });
@@ -418,6 +439,7 @@ fn register_keyboard_input(
                         match pressed_key {
                             82 => *render_state_1 = RenderState::PreviewRender,
                             70 => *render_state_1 = RenderState::FullRender,
+                            71 => *render_state_1 = RenderState::SequenceRender,
                             _ => {}
                         }
 
@@ -581,6 +603,8 @@ fn register_settings_input_fields(
             RenderState::RenderingFreeze => {}
             _ => {
                 scenes_1.change_vfov(settings_1.vfov());
+                scenes_1.change_projection(settings_1.projection());
+                scenes_1.change_animation_time(settings_1.animation_time());
                 update_window_size(&*settings_1, &mut *view_size_1);
 
                 render_frame(
@@ -599,6 +623,15 @@ fn register_settings_input_fields(
             .add_event_listener_with_callback("change", closure.as_ref().unchecked_ref())
             .unwrap();
     }
+    // Preview follows the time slider while it is being dragged
+    settings.lock().unwrap().input_elements["animation_time"]
+        .add_event_listener_with_callback("input", closure.as_ref().unchecked_ref())
+        .unwrap();
+    for elem in &settings.lock().unwrap().select_elements {
+        elem.1
+            .add_event_listener_with_callback("change", closure.as_ref().unchecked_ref())
+            .unwrap();
+    }
 
     closure.forget();
 }
@@ -719,63 +752,66 @@ fn register_upload_scene_from_file(
 ) {
     let settings_2 = Arc::clone(&settings);
 
-    let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
-        let camera_input_1 = Arc::clone(&camera_input);
-        let render_state_1 = Arc::clone(&render_state);
-        let scenes_1 = Arc::clone(&scenes);
-        let settings_1 = Arc::clone(&settings_2);
-        let view_size_1 = Arc::clone(&view_size);
+    let closure = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+        if matches!(*render_state.lock().unwrap(), RenderState::RenderingFreeze) {
+            return;
+        }
 
-        let file_reader = Arc::new(web_sys::FileReader::new().unwrap());
-        file_reader
-            .read_as_text(&crate::misc::event_to_file(event))
-            .unwrap();
+        let files = match settings_2.lock().unwrap().file_input_element.files() {
+            Some(files) => files,
+            None => return,
+        };
+        let file_count = files.length() as usize;
+        let read_files = Arc::new(Mutex::new(Vec::<Option<(String, Vec<u8>)>>::new()));
+
+        // Files are read one by one, the upload is handled once the last one has been read or
+        // failed - failed ones are left out
+        for file in (0..files.length()).filter_map(|i| files.get(i)) {
+            let camera_input_1 = Arc::clone(&camera_input);
+            let render_state_1 = Arc::clone(&render_state);
+            let scenes_1 = Arc::clone(&scenes);
+            let settings_1 = Arc::clone(&settings_2);
+            let view_size_1 = Arc::clone(&view_size);
+            let read_files_1 = Arc::clone(&read_files);
+
+            let file_reader = Arc::new(web_sys::FileReader::new().unwrap());
+            file_reader.read_as_array_buffer(&file).unwrap();
+
+            let file_reader_2 = Arc::clone(&file_reader);
+            let file_name = file.name();
+            let file_onloadend_closure = Closure::<dyn Fn()>::new(move || {
+                let mut read_files = read_files_1.lock().unwrap();
+                match file_reader_2.result() {
+                    Ok(v) if !v.is_null() => read_files.push(Some((
+                        file_name.clone(),
+                        js_sys::Uint8Array::new(&v).to_vec(),
+                    ))),
+                    Ok(_) => {
+                        log::error!("Failed reading file {file_name:}");
+                        read_files.push(None)
+                    }
+                    Err(e) => {
+                        log::error!("Failed reading file {file_name:}: {e:?}");
+                        read_files.push(None)
+                    }
+                }
 
-        {
-            let camera_input_1 = Arc::clone(&camera_input_1);
-            let render_state_1 = Arc::clone(&render_state_1);
-            let scenes_1 = Arc::clone(&scenes_1);
-            let settings_1 = Arc::clone(&settings_1);
-            let view_size_1 = Arc::clone(&view_size_1);
-
-            if !matches!(*render_state.lock().unwrap(), RenderState::RenderingFreeze) {
-                let file_reader_2 = Arc::clone(&file_reader);
-
-                let file_onload_closure =
-                    Closure::<dyn Fn()>::new(move || match file_reader_2.result() {
-                        Ok(v) => {
-                            if let Some(text) = v.as_string() {
-                                match serde_yaml::from_str::<crate::graphics::Scene>(&text) {
-                                    Ok(v) => {
-                                        let mut scenes = scenes_1.lock().unwrap();
-                                        *scenes.current() = v;
-                                        scenes.camera_reset();
-
-                                        render_frame(
-                                            Arc::clone(&camera_input_1),
-                                            Arc::clone(&render_state_1),
-                                            Arc::clone(&scenes_1),
-                                            Arc::clone(&settings_1),
-                                            Arc::clone(&view_size_1),
-                                        )
-                                    }
-                                    Err(e) => {
-                                        log::error!("Failed parsing string as object: {e:?}")
-                                    }
-                                }
-                            } else {
-                                log::error!("Failed parsing read file as string")
-                            }
-                        }
-                        Err(e) => {
-                            log::error!("Failed reading text from file: {e:?}")
-                        }
-                    });
+                if read_files.len() == file_count {
+                    let files: Vec<_> = read_files.drain(..).flatten().collect();
+                    load_uploaded_files(
+                        &files,
+                        Arc::clone(&camera_input_1),
+                        Arc::clone(&render_state_1),
+                        Arc::clone(&scenes_1),
+                        Arc::clone(&settings_1),
+                        Arc::clone(&view_size_1),
+                    )
+                }
+            });
 
-                file_reader.set_onload(Some(file_onload_closure.as_ref().unchecked_ref()));
+            file_reader.set_onloadend(Some(file_onloadend_closure.as_ref().unchecked_ref()));
 
-                file_onload_closure.forget();
-            }
+            file_onloadend_closure.forget();
         }
     }) as Box<dyn FnMut(_)>);
 
@@ -788,3 +824,152 @@ fn register_upload_scene_from_file(
 
     closure.forget();
 }
+
+// An .obj file (with its .mtl files) or a .ply / .stl file is added to the current scene, a .gltf /
+// .glb file (with the buffers and images it refers to) or a .yaml / .yml scene file replaces it.
+// Other images are added as textures
+fn load_uploaded_files(
+    files: &[(String, Vec<u8>)],
+    camera_input: Arc<Mutex<CameraInput>>,
+    render_state: Arc<Mutex<RenderState>>,
+    scenes: Arc<Mutex<SceneList>>,
+    settings: Arc<Mutex<Settings>>,
+    view_size: Arc<Mutex<Vec2D<u32>>>,
+) {
+    let with_extension = |extension: &'static str| {
+        files
+            .iter()
+            .filter(move |(name, _)| name.to_lowercase().ends_with(extension))
+    };
+    let text = |data: &[u8]| String::from_utf8_lossy(data).to_string();
+
+    // Images become textures named like their file, glTF files load their own ones
+    let images: Vec<_> = with_extension(".png")
+        .chain(with_extension(".jpg"))
+        .chain(with_extension(".jpeg"))
+        .collect();
+    if with_extension(".gltf").chain(with_extension(".glb")).next().is_none() {
+        for (name, data) in &images {
+            match ImageData::decode(ImageSource::Named(name.clone()), data) {
+                Ok(image) => {
+                    register_image(image);
+                    EditObject::add_image_option(name);
+                }
+                Err(e) => log::error!("{e:}"),
+            }
+        }
+    }
+
+    if let Some((_, obj)) = with_extension(".obj").next() {
+        let mtls: Vec<String> = with_extension(".mtl").map(|(_, mtl)| text(mtl)).collect();
+
+        match crate::import::import_obj(
+            &text(obj),
+            &mtls.iter().map(String::as_str).collect::<Vec<_>>(),
+        ) {
+            Ok(objects) => {
+                scenes
+                    .lock()
+                    .unwrap()
+                    .current()
+                    .object_list
+                    .objects
+                    .extend(objects.objects);
+            }
+            Err(e) => {
+                log::error!("Failed importing .obj file: {e:}");
+                return;
+            }
+        }
+    } else if let Some((name, data)) = with_extension(".ply").chain(with_extension(".stl")).next() {
+        // Vertex colors are shown if there are any, the material can be changed in the edit panel
+        let material = Material::Lambertian {
+            texture: Texture::VertexColor,
+        };
+        let object = if name.to_lowercase().ends_with(".ply") {
+            crate::import::import_ply(data, material)
+        } else {
+            crate::import::import_stl(data, material)
+        };
+
+        match object {
+            Ok(object) => scenes
+                .lock()
+                .unwrap()
+                .current()
+                .object_list
+                .objects
+                .push(object),
+            Err(e) => {
+                log::error!("Failed importing {name:}: {e:}");
+                return;
+            }
+        }
+    } else if let Some((name, gltf)) = with_extension(".gltf")
+        .chain(with_extension(".glb"))
+        .next()
+    {
+        // Uploaded files are looked up by the file name at the end of the uri
+        let resource = |uri: &str| {
+            let file_name = uri.rsplit('/').next().unwrap_or(uri);
+            files
+                .iter()
+                .find(|(name, _)| name == file_name)
+                .map(|(_, data)| data.clone())
+        };
+        let name = name.rsplit_once('.').map_or(name.as_str(), |(name, _)| name);
+
+        match crate::import::import_gltf(name, gltf, &resource) {
+            Ok(mut v) => {
+                let mut scenes = scenes.lock().unwrap();
+                v.change_size(scenes.current().size);
+                *scenes.current() = v;
+            }
+            Err(e) => {
+                log::error!("Failed importing glTF file: {e:}");
+                return;
+            }
+        }
+    } else if let Some((_, yaml)) = with_extension(".yaml")
+        .chain(with_extension(".yml"))
+        .next()
+        .or_else(|| {
+            // Scene files used to be uploaded with any name, so other files are read as one too
+            files.iter().find(|(name, _)| {
+                ![".png", ".jpg", ".jpeg", ".raw", ".mtl", ".bin"]
+                    .iter()
+                    .any(|extension| name.to_lowercase().ends_with(extension))
+            })
+        })
+    {
+        // Volumes in the scene refer to the .raw files uploaded with it
+        for (name, data) in with_extension(".raw") {
+            graphics::register_volume_file(name, data.clone());
+        }
+
+        match serde_yaml::from_str::<crate::graphics::Scene>(&text(yaml)) {
+            Ok(v) => {
+                let mut scenes = scenes.lock().unwrap();
+                *scenes.current() = v;
+                scenes.camera_reset();
+
+                // Images embedded in the scene got registered while reading it
+                for name in image_names() {
+                    EditObject::add_image_option(&name);
+                }
+            }
+            Err(e) => {
+                log::error!("Failed parsing string as object: {e:?}");
+                return;
+            }
+        }
+    } else if !images.is_empty() {
+        log::info!("Added {:} textures, pick them in the edit panel", images.len());
+        return;
+    } else {
+        log::error!("Upload contains no scene, .obj, .ply, .stl, .gltf, .glb or image file");
+        return;
+    }
+
+    render_frame(camera_input, render_state, scenes, settings, view_size)
+}
@@ -0,0 +1,434 @@
+use std::ops::{Add, Mul, Sub};
+
+use super::camera::Orientation;
+use super::object::ObjectList;
+use crate::draw::Color;
+use crate::vector::Vec3D;
+use serde::{Deserialize, Serialize};
+
+// --------------------------------------------------
+
+const FPS_DEFAULT: f32 = 24.0;
+
+// --------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub pos: Vec3D<f32>,
+    pub orientation: Orientation,
+    pub vfov: f32,
+}
+
+// --------------------------------------------------
+
+// Shapes the transition from a keyframe to the next one
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    Step,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::Step => 0.0,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+// --------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Keyframe<T> {
+    pub time: f32,
+    pub value: T,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+impl<T: Copy> Keyframe<T> {
+    // The easing of a keyframe is used on the way to the following one, values are clamped to
+    // the first and last keyframe
+    fn sample(keyframes: &[Keyframe<T>], time: f32, lerp: impl Fn(T, T, f32) -> T) -> Option<T> {
+        let mut keyframes = keyframes.to_vec();
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let next = keyframes
+            .iter()
+            .position(|keyframe| keyframe.time > time)
+            .unwrap_or(keyframes.len());
+
+        match next {
+            0 => keyframes.first().map(|keyframe| keyframe.value),
+            _ if next == keyframes.len() => keyframes.last().map(|keyframe| keyframe.value),
+            _ => {
+                let (from, to) = (keyframes[next - 1], keyframes[next]);
+                let t = (time - from.time) / (to.time - from.time);
+
+                Some(lerp(from.value, to.value, from.easing.apply(t)))
+            }
+        }
+    }
+}
+
+// --------------------------------------------------
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum ObjectProperty {
+    Position(Vec<Keyframe<Vec3D<f32>>>),
+    Radius(Vec<Keyframe<f32>>),
+    Color(Vec<Keyframe<Color>>),
+    LightIntensity(Vec<Keyframe<f32>>),
+}
+
+// Animates a single property of the object at the given index of the scene's object list
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ObjectTrack {
+    pub object: usize,
+    pub property: ObjectProperty,
+}
+
+// --------------------------------------------------
+
+// Timeline of a scene, times are in seconds
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Animation {
+    pub fps: f32,
+    pub duration: f32,
+    #[serde(default)]
+    pub camera: Vec<CameraKeyframe>,
+    #[serde(default)]
+    pub objects: Vec<ObjectTrack>,
+}
+
+impl Animation {
+    pub fn frame_count(&self) -> u32 {
+        ((self.duration * self.fps).round() as u32).max(1)
+    }
+
+    pub fn frame_time(&self, frame: u32) -> f32 {
+        (frame as f32) / self.fps
+    }
+
+    // Catmull-Rom spline through the keyframes, clamped to the first and last one
+    pub fn camera_at(&self, time: f32) -> Option<CameraKeyframe> {
+        let mut keyframes = self.camera.clone();
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Animation::unwrap_angles(&mut keyframes);
+
+        let last = keyframes.len().checked_sub(1)?;
+        let next = keyframes
+            .iter()
+            .position(|keyframe| keyframe.time > time)
+            .unwrap_or(keyframes.len());
+
+        if next == 0 || next > last {
+            let mut out = keyframes[next.min(last)];
+            out.time = time;
+            return Some(out);
+        }
+
+        let (p0, p1, p2, p3) = (
+            keyframes[next.saturating_sub(2)],
+            keyframes[next - 1],
+            keyframes[next],
+            keyframes[(next + 1).min(last)],
+        );
+        let t = (time - p1.time) / (p2.time - p1.time);
+        let angles = |keyframe: CameraKeyframe| {
+            Vec3D::new(
+                keyframe.orientation.yaw,
+                keyframe.orientation.pitch,
+                keyframe.orientation.roll,
+            )
+        };
+        let orientation = catmull_rom(angles(p0), angles(p1), angles(p2), angles(p3), t);
+
+        Some(CameraKeyframe {
+            time,
+            pos: catmull_rom(p0.pos, p1.pos, p2.pos, p3.pos, t),
+            orientation: Orientation::new(orientation.x, orientation.y, orientation.z),
+            vfov: catmull_rom(p0.vfov, p1.vfov, p2.vfov, p3.vfov, t),
+        })
+    }
+
+    pub fn objects_at(&self, object_list: &ObjectList, time: f32) -> ObjectList {
+        let mut out = object_list.clone();
+
+        for track in &self.objects {
+            if let Some(object) = out.objects.get_mut(track.object) {
+                let lerp_f32 = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+                *object = match &track.property {
+                    ObjectProperty::Position(keyframes) => {
+                        match Keyframe::sample(keyframes, time, |a, b, t| a + (b - a) * t) {
+                            Some(pos) => object.translated(pos - object.position()),
+                            None => continue,
+                        }
+                    }
+                    ObjectProperty::Radius(keyframes) => {
+                        match Keyframe::sample(keyframes, time, lerp_f32) {
+                            Some(radius) => object.with_radius(radius),
+                            None => continue,
+                        }
+                    }
+                    ObjectProperty::Color(keyframes) => {
+                        match Keyframe::sample(keyframes, time, |a, b, t| {
+                            Color::from_vec3d(a.0 + (b.0 - a.0) * t)
+                        }) {
+                            Some(color) => object.with_material(object.material().with_color(color)),
+                            None => continue,
+                        }
+                    }
+                    ObjectProperty::LightIntensity(keyframes) => {
+                        match Keyframe::sample(keyframes, time, lerp_f32) {
+                            Some(intensity) => {
+                                object.with_material(object.material().with_intensity(intensity))
+                            }
+                            None => continue,
+                        }
+                    }
+                };
+            }
+        }
+
+        out
+    }
+
+    // Makes consecutive yaw and roll values differ by at most 180°, so the camera turns the
+    // short way around instead of spinning back over 0°/360°
+    fn unwrap_angles(keyframes: &mut [CameraKeyframe]) {
+        let unwrap = |previous: f32, current: f32| {
+            previous + (current - previous + 180.0).rem_euclid(360.0) - 180.0
+        };
+
+        for i in 1..keyframes.len() {
+            let previous = keyframes[i - 1].orientation;
+            let current = &mut keyframes[i].orientation;
+
+            current.yaw = unwrap(previous.yaw, current.yaw);
+            current.roll = unwrap(previous.roll, current.roll);
+        }
+    }
+}
+
+impl Default for Animation {
+    fn default() -> Animation {
+        Animation {
+            fps: FPS_DEFAULT,
+            duration: 0.0,
+            camera: vec![],
+            objects: vec![],
+        }
+    }
+}
+
+// --------------------------------------------------
+
+fn catmull_rom<T>(p0: T, p1: T, p2: T, p3: T, t: f32) -> T
+where
+    T: Copy + Add<T, Output = T> + Sub<T, Output = T> + Mul<f32, Output = T>,
+{
+    let (t2, t3) = (t * t, t * t * t);
+
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::graphics::{Material, Object3D};
+    use crate::vector::VectorOperation;
+
+    fn camera_keyframe(time: f32, pos: Vec3D<f32>, yaw: f32) -> CameraKeyframe {
+        CameraKeyframe {
+            time,
+            pos,
+            orientation: Orientation::new(yaw, 0.0, 0.0),
+            vfov: 90.0,
+        }
+    }
+
+    fn camera_animation(camera: Vec<CameraKeyframe>) -> Animation {
+        Animation {
+            duration: 4.0,
+            camera,
+            ..Animation::default()
+        }
+    }
+
+    #[test]
+    fn camera_passes_through_its_keyframes() {
+        let keyframes = vec![
+            camera_keyframe(0.0, Vec3D::new(0.0, 0.0, 0.0), 0.0),
+            camera_keyframe(1.0, Vec3D::new(1.0, 2.0, 0.0), 30.0),
+            camera_keyframe(3.0, Vec3D::new(-1.0, 0.5, 4.0), 90.0),
+            camera_keyframe(4.0, Vec3D::new(2.0, 0.0, 1.0), 45.0),
+        ];
+        // Keyframes don't have to be sorted in the file
+        let animation = camera_animation(keyframes.iter().rev().copied().collect());
+
+        for keyframe in &keyframes {
+            let at = animation.camera_at(keyframe.time).unwrap();
+            assert!((at.pos - keyframe.pos).length() < 1e-5);
+            assert!((at.orientation.yaw - keyframe.orientation.yaw).abs() < 1e-4);
+        }
+
+        // Before the first and after the last keyframe the camera stands still
+        assert_eq!(animation.camera_at(-1.0).unwrap().pos, keyframes[0].pos);
+        assert_eq!(animation.camera_at(10.0).unwrap().pos, keyframes[3].pos);
+        assert!(camera_animation(vec![]).camera_at(1.0).is_none());
+    }
+
+    #[test]
+    fn evenly_spaced_keyframes_on_a_line_are_followed_linearly() {
+        let animation = camera_animation(
+            (0..4)
+                .map(|i| camera_keyframe(i as f32, Vec3D::new(i as f32 * 2.0, 1.0, 0.0), 0.0))
+                .collect(),
+        );
+
+        // Between the inner keyframes - next to the first and last one the camera eases in and out
+        for time in [1.0, 1.25, 1.5, 1.9] {
+            let at = animation.camera_at(time).unwrap();
+            assert!((at.pos - Vec3D::new(time * 2.0, 1.0, 0.0)).length() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn yaw_turns_the_short_way_around() {
+        let animation = camera_animation(vec![
+            camera_keyframe(0.0, Vec3D::new(0.0, 0.0, 0.0), 350.0),
+            camera_keyframe(1.0, Vec3D::new(0.0, 0.0, 0.0), 10.0),
+        ]);
+
+        // Halfway between 350° and 10° is 0°, not 180°
+        let yaw = animation
+            .camera_at(0.5)
+            .unwrap()
+            .orientation
+            .yaw
+            .rem_euclid(360.0);
+        assert!(yaw.min(360.0 - yaw) < 1e-3);
+    }
+
+    #[test]
+    fn frames_cover_the_duration() {
+        let animation = Animation {
+            fps: 24.0,
+            duration: 2.5,
+            ..Animation::default()
+        };
+
+        assert_eq!(animation.frame_count(), 60);
+        assert!((animation.frame_time(12) - 0.5).abs() < 1e-6);
+        // Scenes without an animation still render a single frame
+        assert_eq!(Animation::default().frame_count(), 1);
+    }
+
+    fn keyframe<T>(time: f32, value: T, easing: Easing) -> Keyframe<T> {
+        Keyframe {
+            time,
+            value,
+            easing,
+        }
+    }
+
+    #[test]
+    fn easings_shape_the_way_to_the_next_keyframe() {
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let sample = |easing: Easing, time: f32| {
+            Keyframe::sample(
+                &[
+                    keyframe(0.0, 0.0, easing),
+                    keyframe(2.0, 10.0, Easing::Linear),
+                ],
+                time,
+                lerp,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(sample(Easing::Linear, 0.5), 2.5);
+        assert_eq!(sample(Easing::Step, 1.9), 0.0);
+        assert!(sample(Easing::EaseIn, 0.5) < 2.5);
+        assert!(sample(Easing::EaseOut, 0.5) > 2.5);
+        assert_eq!(sample(Easing::EaseInOut, 1.0), 5.0);
+        for easing in [
+            Easing::Linear,
+            Easing::Step,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            // Clamped to the first and last keyframe
+            assert_eq!(sample(easing, -1.0), 0.0);
+            assert_eq!(sample(easing, 2.0), 10.0);
+            assert_eq!(sample(easing, 5.0), 10.0);
+        }
+    }
+
+    #[test]
+    fn object_tracks_change_the_objects_they_point_to() {
+        let sphere = |material: Material| Object3D::Sphere {
+            pos: Vec3D::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: Arc::new(material),
+        };
+        let objects = ObjectList::new(vec![
+            sphere(Material::default()),
+            sphere(Material::light(Vec3D::new(2.0, 2.0, 2.0))),
+        ]);
+        let animation = Animation {
+            duration: 1.0,
+            objects: vec![
+                ObjectTrack {
+                    object: 0,
+                    property: ObjectProperty::Position(vec![
+                        keyframe(0.0, Vec3D::new(0.0, 0.0, 0.0), Easing::Linear),
+                        keyframe(1.0, Vec3D::new(4.0, 0.0, 2.0), Easing::Linear),
+                    ]),
+                },
+                ObjectTrack {
+                    object: 1,
+                    property: ObjectProperty::LightIntensity(vec![
+                        keyframe(0.0, 1.0, Easing::Linear),
+                        keyframe(1.0, 3.0, Easing::Linear),
+                    ]),
+                },
+                // Tracks of objects that don't exist are ignored
+                ObjectTrack {
+                    object: 5,
+                    property: ObjectProperty::Radius(vec![keyframe(0.0, 2.0, Easing::Linear)]),
+                },
+            ],
+            ..Animation::default()
+        };
+
+        let at = animation.objects_at(&objects, 0.5);
+        assert_eq!(at.objects[0].position(), Vec3D::new(2.0, 0.0, 1.0));
+        // Light intensities scale the strength, which isn't clamped to white
+        match &*at.objects[1].material().clone() {
+            Material::DiffuseLight { strength, .. } => assert!((strength - 4.0).abs() < 1e-5),
+            material => panic!("{material:?} is not a light"),
+        }
+        assert_eq!(at.objects.len(), 2);
+    }
+}
@@ -12,6 +12,8 @@ pub struct CameraInput {
     pub left: bool,
     pub up: bool,
     pub down: bool,
+    pub roll_left: bool,
+    pub roll_right: bool,
     pub reset: bool,
     pub mouse_move: Option<Vec2D<f32>>,
 }
@@ -24,14 +26,158 @@ impl CameraInput {
 
 // --------------------------------------------------
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    Orthographic {
+        view_height: f32,
+    },
+    Fisheye,
+    Equirectangular,
+}
+
+// --------------------------------------------------
+
+// Yaw / pitch / roll in degrees. Yaw turns around the world Y axis, pitch tilts the view up and
+// down and roll spins the image around the view direction. Unlike a single direction vector,
+// this keeps a well defined "up" even when looking straight up or down.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Orientation {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub roll: f32,
+}
+
+impl Orientation {
+    const PITCH_LIMIT: f32 = 90.0;
+
+    pub const fn new(yaw: f32, pitch: f32, roll: f32) -> Orientation {
+        Orientation { yaw, pitch, roll }
+    }
+
+    pub fn from_direction(direction: Vec3D<f32>) -> Orientation {
+        let direction = direction.unit_vec();
+
+        Orientation::new(
+            direction.x.atan2(direction.z).to_degrees(),
+            direction.y.clamp(-1.0, 1.0).asin().to_degrees(),
+            0.0,
+        )
+    }
+
+    pub fn look_at(from: Vec3D<f32>, target: Vec3D<f32>, up: Vec3D<f32>) -> Orientation {
+        let mut out = Orientation::from_direction(target - from);
+
+        let forward = out.forward();
+        let up_projected = up - forward * up.dot(&forward);
+        if up_projected.length_squared() > f32::EPSILON {
+            let up_projected = up_projected.unit_vec();
+            let up_unrolled = out.right_unrolled().cross(&forward);
+
+            out.roll = forward
+                .dot(&up_unrolled.cross(&up_projected))
+                .atan2(up_unrolled.dot(&up_projected))
+                .to_degrees();
+        }
+
+        out
+    }
+
+    pub fn forward(&self) -> Vec3D<f32> {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        Vec3D::new(pitch.cos() * yaw.sin(), pitch.sin(), pitch.cos() * yaw.cos())
+    }
+
+    pub fn right(&self) -> Vec3D<f32> {
+        self.right_unrolled()
+            .rotate_around(self.forward(), self.roll.to_radians())
+    }
+
+    pub fn up(&self) -> Vec3D<f32> {
+        self.right_unrolled()
+            .cross(&self.forward())
+            .rotate_around(self.forward(), self.roll.to_radians())
+    }
+
+    // Only depends on yaw, so it never degenerates when looking straight up or down
+    fn right_unrolled(&self) -> Vec3D<f32> {
+        let yaw = self.yaw.to_radians();
+        Vec3D::new(-yaw.cos(), 0.0, yaw.sin())
+    }
+
+    fn rotate(&mut self, yaw: f32, pitch: f32, roll: f32) {
+        self.yaw = (self.yaw + yaw).rem_euclid(360.0);
+        self.pitch = (self.pitch + pitch).clamp(-Orientation::PITCH_LIMIT, Orientation::PITCH_LIMIT);
+        self.roll = (self.roll + roll).rem_euclid(360.0);
+    }
+}
+
+// --------------------------------------------------
+
+#[derive(Deserialize)]
+struct LookAt {
+    target: Vec3D<f32>,
+    #[serde(default = "LookAt::default_up")]
+    up: Vec3D<f32>,
+}
+
+impl LookAt {
+    fn default_up() -> Vec3D<f32> {
+        Vec3D::new(0.0, 1.0, 0.0)
+    }
+}
+
+// Camera files used to store a single direction vector as "rotation" - those still load, with
+// the direction converted to yaw and pitch. A "look_at" target overrides both.
+#[derive(Deserialize)]
+struct CameraData {
+    pos: Vec3D<f32>,
+    #[serde(default)]
+    orientation: Option<Orientation>,
+    #[serde(default)]
+    rotation: Option<Vec3D<f32>>,
+    #[serde(default)]
+    look_at: Option<LookAt>,
+    vfov: f32,
+    #[serde(default)]
+    projection: Projection,
+}
+
+impl From<CameraData> for Camera {
+    fn from(item: CameraData) -> Camera {
+        let orientation = match (item.orientation, item.rotation) {
+            (Some(orientation), _) => orientation,
+            (None, Some(rotation)) => Orientation::from_direction(rotation),
+            (None, None) => Orientation::default(),
+        };
+
+        let mut out = Camera::new(item.pos, orientation, 1.0, item.vfov, item.projection);
+        if let Some(look_at) = item.look_at {
+            out.look_at(look_at.target, look_at.up);
+        }
+        out
+    }
+}
+
+// --------------------------------------------------
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "CameraData")]
 pub struct Camera {
     pub pos: Vec3D<f32>,
-    pub rotation: Vec3D<f32>,
+    pub orientation: Orientation,
     pub vfov: f32,
+    pub projection: Projection,
     #[serde(skip)]
     aspect_ratio: f32,
     #[serde(skip)]
+    u: Vec3D<f32>,
+    #[serde(skip)]
+    v: Vec3D<f32>,
+    #[serde(skip)]
+    w: Vec3D<f32>,
+    #[serde(skip)]
     horizontal: Vec3D<f32>,
     #[serde(skip)]
     vertical: Vec3D<f32>,
@@ -40,10 +186,16 @@ pub struct Camera {
 }
 
 impl Camera {
-    pub fn new(pos: Vec3D<f32>, rotation: Vec3D<f32>, aspect_ratio: f32, vfov: f32) -> Camera {
-        let w = (-rotation).unit_vec();
-        let u = Vec3D::new(0.0, 1.0, 0.0).cross(&w);
-        let v = w.cross(&u);
+    pub fn new(
+        pos: Vec3D<f32>,
+        orientation: Orientation,
+        aspect_ratio: f32,
+        vfov: f32,
+        projection: Projection,
+    ) -> Camera {
+        let w = -orientation.forward();
+        let u = orientation.right();
+        let v = orientation.up();
 
         let viewport_height = 2.0 * (vfov.to_radians() / 2.0).tan();
         let viewport_width = aspect_ratio * viewport_height;
@@ -52,82 +204,361 @@ impl Camera {
 
         Camera {
             pos,
-            rotation,
+            orientation,
             vfov,
+            projection,
             aspect_ratio,
+            u,
+            v,
+            w,
             horizontal,
             vertical,
             lower_left_corner: pos - horizontal / 2.0 - vertical / 2.0 - w,
         }
     }
 
+    pub fn direction(&self) -> Vec3D<f32> {
+        -self.w
+    }
+
+    pub fn look_at(&mut self, target: Vec3D<f32>, up: Vec3D<f32>) {
+        self.orientation = Orientation::look_at(self.pos, target, up);
+        self.update();
+    }
+
     pub fn change_vfov(&mut self, vfov: f32) {
-        *self = Camera::new(self.pos, self.rotation, self.aspect_ratio, vfov)
+        self.vfov = vfov;
+        self.update();
+    }
+
+    pub fn change_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+        self.update();
     }
 
     pub fn reset(&mut self) {
-        *self = Camera::new(
-            Vec3D::new(0.0, 0.0, 0.0),
-            Vec3D::new(0.0, 0.0, 1.0),
-            self.aspect_ratio,
-            self.vfov,
-        )
+        self.pos = Vec3D::new(0.0, 0.0, 0.0);
+        self.orientation = Orientation::default();
+        self.update();
     }
 
     pub fn resize(&mut self, aspect_ratio: f32) {
-        *self = Camera::new(self.pos, self.rotation, aspect_ratio, self.vfov)
+        self.aspect_ratio = aspect_ratio;
+        self.update();
     }
 
     fn update(&mut self) {
-        *self = Camera::new(self.pos, self.rotation, self.aspect_ratio, self.vfov)
+        *self = Camera::new(
+            self.pos,
+            self.orientation,
+            self.aspect_ratio,
+            self.vfov,
+            self.projection,
+        )
     }
 
     pub fn handle_input(&mut self, input: &CameraInput) {
         const ROTATION_SPEED_MULTIPLIER: f32 = 0.001;
         const MOVEMENT_SPEED_MULTIPLIER: f32 = 0.05;
+        const ROLL_SPEED: f32 = 2.0;
 
         if input.reset {
             return self.reset();
         }
 
+        let forward = self.orientation.forward();
+        let right = self.orientation.right();
+
         if input.forward {
-            self.pos += self.rotation * MOVEMENT_SPEED_MULTIPLIER
+            self.pos += forward * MOVEMENT_SPEED_MULTIPLIER
         }
         if input.back {
-            self.pos -= self.rotation * MOVEMENT_SPEED_MULTIPLIER
+            self.pos -= forward * MOVEMENT_SPEED_MULTIPLIER
         }
         if input.right {
-            self.pos -= self.rotation.rotate_around_y(std::f32::consts::PI / 2.0)
-                * MOVEMENT_SPEED_MULTIPLIER
+            self.pos += right * MOVEMENT_SPEED_MULTIPLIER
         }
         if input.left {
-            self.pos += self.rotation.rotate_around_y(std::f32::consts::PI / 2.0)
-                * MOVEMENT_SPEED_MULTIPLIER
+            self.pos -= right * MOVEMENT_SPEED_MULTIPLIER
         }
         if input.up {
-            self.pos.y += self.rotation.length() * MOVEMENT_SPEED_MULTIPLIER
+            self.pos.y += MOVEMENT_SPEED_MULTIPLIER
         }
         if input.down {
-            self.pos.y -= self.rotation.length() * MOVEMENT_SPEED_MULTIPLIER
+            self.pos.y -= MOVEMENT_SPEED_MULTIPLIER
         }
 
-        if let Some(mouse_move) = input.mouse_move {
-            let rotate = Vec2D::new(
-                -mouse_move.x,
-                mouse_move.y * if self.rotation.z > 0.0 { 1.0 } else { -1.0 },
-            ) * ROTATION_SPEED_MULTIPLIER;
+        if input.roll_left {
+            self.orientation.rotate(0.0, 0.0, -ROLL_SPEED)
+        }
+        if input.roll_right {
+            self.orientation.rotate(0.0, 0.0, ROLL_SPEED)
+        }
 
-            self.rotation = self.rotation.rotate_around_y(rotate.x);
-            self.rotation = self.rotation.rotate_around_x(rotate.y);
+        if let Some(mouse_move) = input.mouse_move {
+            let rotate = mouse_move * -ROTATION_SPEED_MULTIPLIER.to_degrees();
+            self.orientation.rotate(rotate.x, rotate.y, 0.0);
         }
 
         self.update();
     }
 
+    // Moves the camera sideways by eye_offset and skews the perspective viewport (off-axis),
+    // so that objects at convergence_distance end up with zero parallax
+    pub fn stereo_eye(&self, eye_offset: f32, convergence_distance: f32) -> Camera {
+        let mut out = self.clone();
+
+        out.pos += self.u * eye_offset;
+        out.lower_left_corner += self.u * (eye_offset - eye_offset / convergence_distance);
+
+        out
+    }
+
+    // The ray with the one through the next pixel to the right as its differential
+    pub fn get_pixel_ray(&self, x: f32, y: f32, pixel_width: f32) -> Ray {
+        self.get_ray(x, y)
+            .with_differential(self.get_ray(x + pixel_width, y))
+    }
+
     pub fn get_ray(&self, x: f32, y: f32) -> Ray {
-        Ray::new(
-            self.pos,
-            self.lower_left_corner + self.horizontal * x + self.vertical * y - self.pos,
+        match self.projection {
+            Projection::Perspective => Ray::new(
+                self.pos,
+                self.lower_left_corner + self.horizontal * x + self.vertical * y - self.pos,
+            ),
+            Projection::Orthographic { view_height } => Ray::new(
+                self.pos
+                    + self.u * ((x - 0.5) * view_height * self.aspect_ratio)
+                    + self.v * ((y - 0.5) * view_height),
+                -self.w,
+            ),
+            Projection::Fisheye => {
+                // Equidistant fisheye - the angle from the view axis grows linearly with the
+                // distance from the image center, vfov spans the image height
+                let offset = Vec2D::new((x - 0.5) * self.aspect_ratio, y - 0.5);
+                let radius = offset.length();
+                let theta = radius * self.vfov.to_radians();
+                let (sin_phi, cos_phi) = if radius > 0.0 {
+                    (offset.y / radius, offset.x / radius)
+                } else {
+                    (0.0, 0.0)
+                };
+
+                Ray::new(
+                    self.pos,
+                    self.u * (theta.sin() * cos_phi) + self.v * (theta.sin() * sin_phi)
+                        - self.w * theta.cos(),
+                )
+            }
+            Projection::Equirectangular => {
+                // Full 360° x 180° panorama, the view direction is in the center of the image
+                let longitude = (x - 0.5) * 2.0 * std::f32::consts::PI;
+                let latitude = (y - 0.5) * std::f32::consts::PI;
+
+                Ray::new(
+                    self.pos,
+                    self.u * (latitude.cos() * longitude.sin()) + self.v * latitude.sin()
+                        - self.w * (latitude.cos() * longitude.cos()),
+                )
+            }
+        }
+    }
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn assert_close(a: Vec3D<f32>, b: Vec3D<f32>) {
+        assert!((a - b).length() < 1e-4, "{a:?} != {b:?}");
+    }
+
+    fn random_vec(rng: &mut StdRng) -> Vec3D<f32> {
+        Vec3D::new(
+            rng.gen_range(-10.0..10.0),
+            rng.gen_range(-10.0..10.0),
+            rng.gen_range(-10.0..10.0),
         )
     }
+
+    #[test]
+    fn axes_are_orthonormal() {
+        let mut rng = StdRng::seed_from_u64(28);
+        for _ in 0..200 {
+            let orientation = Orientation::new(
+                rng.gen_range(0.0..360.0),
+                rng.gen_range(-90.0..90.0),
+                rng.gen_range(0.0..360.0),
+            );
+            let (forward, right, up) =
+                (orientation.forward(), orientation.right(), orientation.up());
+
+            for axis in [forward, right, up] {
+                assert!((axis.length() - 1.0).abs() < 1e-4);
+            }
+            assert!(forward.dot(&right).abs() < 1e-4);
+            assert!(forward.dot(&up).abs() < 1e-4);
+            assert!(right.dot(&up).abs() < 1e-4);
+            // Left handed like the rest of the renderer - right x up points backwards
+            assert_close(right.cross(&up), -forward);
+        }
+    }
+
+    #[test]
+    fn from_direction_round_trips() {
+        let mut rng = StdRng::seed_from_u64(29);
+        for _ in 0..200 {
+            let direction = random_vec(&mut rng).unit_vec();
+            assert_close(Orientation::from_direction(direction).forward(), direction);
+        }
+
+        assert_close(
+            Orientation::new(0.0, 0.0, 0.0).forward(),
+            Vec3D::new(0.0, 0.0, 1.0),
+        );
+        assert_close(
+            Orientation::new(90.0, 0.0, 0.0).forward(),
+            Vec3D::new(1.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn look_at_faces_the_target_with_up_kept() {
+        let mut rng = StdRng::seed_from_u64(30);
+        for _ in 0..200 {
+            let (from, target, up) = (
+                random_vec(&mut rng),
+                random_vec(&mut rng),
+                random_vec(&mut rng),
+            );
+            let orientation = Orientation::look_at(from, target, up);
+            let forward = (target - from).unit_vec();
+
+            assert_close(orientation.forward(), forward);
+            // The camera's up is the given one, minus the part along the view direction
+            let up_projected = (up - forward * up.dot(&forward)).unit_vec();
+            assert_close(orientation.up(), up_projected);
+
+            // Reading the orientation back gives the same one
+            let again = Orientation::look_at(from, from + orientation.forward(), orientation.up());
+            assert_close(again.forward(), orientation.forward());
+            assert_close(again.up(), orientation.up());
+        }
+    }
+
+    #[test]
+    fn look_at_straight_up_keeps_the_orientation_defined() {
+        let orientation = Orientation::look_at(
+            Vec3D::new(0.0, 0.0, 0.0),
+            Vec3D::new(0.0, 5.0, 0.0),
+            Vec3D::new(0.0, 0.0, 1.0),
+        );
+
+        assert_close(orientation.forward(), Vec3D::new(0.0, 1.0, 0.0));
+        assert_close(orientation.up(), Vec3D::new(0.0, 0.0, 1.0));
+        assert!(!orientation.roll.is_nan());
+    }
+
+    #[test]
+    fn look_at_in_scene_files_overrides_the_orientation() {
+        let camera: Camera = serde_yaml::from_str(
+            "pos: { x: 1.0, y: 2.0, z: 3.0 }
+orientation: { yaw: 45.0, pitch: 10.0, roll: 5.0 }
+look_at: { target: { x: 1.0, y: 2.0, z: 10.0 } }
+vfov: 90.0",
+        )
+        .unwrap();
+
+        assert_close(camera.direction(), Vec3D::new(0.0, 0.0, 1.0));
+        assert_close(camera.orientation.up(), Vec3D::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn projections_look_along_the_view_direction() {
+        let orientation = Orientation::new(20.0, 15.0, 0.0);
+        for projection in [
+            Projection::Perspective,
+            Projection::Orthographic { view_height: 2.0 },
+            Projection::Fisheye,
+            Projection::Equirectangular,
+        ] {
+            let camera = Camera::new(
+                Vec3D::new(1.0, 0.0, -1.0),
+                orientation,
+                2.0,
+                90.0,
+                projection,
+            );
+            let ray = camera.get_ray(0.5, 0.5);
+
+            assert_close(ray.from, camera.pos);
+            assert_close(ray.dir.unit_vec(), orientation.forward());
+        }
+    }
+
+    #[test]
+    fn fisheye_and_panorama_angles() {
+        let orientation = Orientation::default();
+        let angle = |camera: &Camera, x: f32, y: f32| {
+            camera
+                .get_ray(x, y)
+                .dir
+                .unit_vec()
+                .dot(&orientation.forward())
+                .acos()
+                .to_degrees()
+        };
+
+        // The angle from the view axis grows linearly, vfov spans the image height
+        let fisheye = Camera::new(
+            Vec3D::new(0.0, 0.0, 0.0),
+            orientation,
+            1.0,
+            180.0,
+            Projection::Fisheye,
+        );
+        assert!((angle(&fisheye, 0.5, 1.0) - 90.0).abs() < 1e-3);
+        assert!((angle(&fisheye, 0.5, 0.75) - 45.0).abs() < 1e-3);
+
+        // 360° across the image, 180° from the bottom to the top
+        let panorama = Camera::new(
+            Vec3D::new(0.0, 0.0, 0.0),
+            orientation,
+            2.0,
+            90.0,
+            Projection::Equirectangular,
+        );
+        assert!((angle(&panorama, 0.75, 0.5) - 90.0).abs() < 1e-3);
+        assert!((angle(&panorama, 0.0, 0.5) - 180.0).abs() < 1e-2);
+        assert_close(panorama.get_ray(0.3, 1.0).dir.unit_vec(), orientation.up());
+        assert_close(
+            panorama.get_ray(0.5, 0.75).dir.unit_vec(),
+            (orientation.forward() + orientation.up()).unit_vec(),
+        );
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let orientation = Orientation::new(-40.0, 0.0, 10.0);
+        let camera = Camera::new(
+            Vec3D::new(0.0, 0.0, 0.0),
+            orientation,
+            2.0,
+            90.0,
+            Projection::Orthographic { view_height: 3.0 },
+        );
+
+        let (corner, opposite) = (camera.get_ray(0.0, 0.0), camera.get_ray(1.0, 1.0));
+        assert_close(corner.dir.unit_vec(), orientation.forward());
+        assert_close(opposite.dir.unit_vec(), orientation.forward());
+        // The view is 3 high and 6 wide
+        assert_close(
+            opposite.from - corner.from,
+            orientation.right() * 6.0 + orientation.up() * 3.0,
+        );
+    }
 }
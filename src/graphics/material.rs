@@ -12,7 +12,31 @@ pub enum Material {
     Lambertian { texture: Texture },
     Metal { texture: Texture, fuzz: f32 },
     Dielectric { refraction_index: f32 },
-    DiffuseLight { texture: Texture },
+    // Emits the texture color times the strength, tinted by the color of a blackbody at the
+    // temperature (in Kelvin) if there is one
+    DiffuseLight {
+        texture: Texture,
+        #[serde(default = "strength_default")]
+        strength: f32,
+        #[serde(default)]
+        temperature: Option<f32>,
+    },
+    // Scatters evenly in all directions, for the inside of volumes
+    Isotropic { texture: Texture },
+    // The material with its normal bent by a surface map before scattering
+    Bumped { material: Box<Material>, map: SurfaceMap },
+    // The material with holes where the opacity is below the cutoff, rays go through them as if
+    // the surface wasn't there
+    Cutout {
+        material: Box<Material>,
+        opacity: Texture,
+        #[serde(default = "cutoff_default")]
+        cutoff: f32,
+    },
+}
+
+const fn cutoff_default() -> f32 {
+    0.5
 }
 
 impl Material {
@@ -23,7 +47,7 @@ impl Material {
                     record.pos,
                     record.normal + Vec3D::new_rand_in_unit_sphere().unit_vec(),
                 ),
-                texture.color_value(record.u, record.v, record.pos),
+                texture.color_value(record),
             )),
             Material::Metal { texture, fuzz } => {
                 let scattered = Ray::new(
@@ -35,7 +59,7 @@ impl Material {
                 if scattered.dir.dot(&record.normal) > 0.0 {
                     Some((
                         scattered,
-                        texture.color_value(record.u, record.v, record.pos),
+                        texture.color_value(record),
                     ))
                 } else {
                     None
@@ -63,11 +87,19 @@ impl Material {
 
                 Some((Ray::new(record.pos, direction), Color::default()))
             }
-            Material::DiffuseLight { texture: _ } => None,
+            Material::DiffuseLight { .. } => None,
+            Material::Isotropic { texture } => Some((
+                Ray::new(record.pos, Vec3D::new_rand_in_unit_sphere().unit_vec()),
+                texture.color_value(record),
+            )),
+            Material::Bumped { material, map } => {
+                material.scatter(&map.bent(record, ray_in), ray_in)
+            }
+            Material::Cutout { material, .. } => material.scatter(record, ray_in),
         }
     }
 
-    pub fn emmited(&self, u: f32, v: f32, point: Vec3D<f32>) -> Color {
+    pub fn emmited(&self, record: &RayHit) -> Color {
         match self {
             Material::Lambertian { texture: _ } => Color::new(0, 0, 0),
             Material::Metal {
@@ -77,21 +109,30 @@ impl Material {
             Material::Dielectric {
                 refraction_index: _,
             } => Color::new(0, 0, 0),
-            Material::DiffuseLight { texture } => texture.color_value(u, v, point),
+            // Not clamped, lights can be brighter than white
+            Material::DiffuseLight { .. } => Color(self.emission(record)),
+            Material::Isotropic { texture: _ } => Color::new(0, 0, 0),
+            Material::Bumped { material, .. } | Material::Cutout { material, .. } => {
+                material.emmited(record)
+            }
         }
     }
 
-    pub fn color(&self, u: f32, v: f32, point: Vec3D<f32>) -> Color {
+    pub fn color(&self, record: &RayHit) -> Color {
         match self {
-            Material::Lambertian { texture } => texture.color_value(u, v, point),
+            Material::Lambertian { texture } => texture.color_value(record),
             Material::Metal {
                 texture,
                 fuzz: _fuzz,
-            } => texture.color_value(u, v, point),
-            Material::DiffuseLight { texture } => texture.color_value(u, v, point),
+            } => texture.color_value(record),
+            Material::DiffuseLight { .. } => Color::from_vec3d(self.emission(record)),
+            Material::Isotropic { texture } => texture.color_value(record),
             Material::Dielectric {
                 refraction_index: _,
             } => Color::new(255, 255, 255),
+            Material::Bumped { material, .. } | Material::Cutout { material, .. } => {
+                material.color(record)
+            }
         }
     }
 
@@ -99,11 +140,147 @@ impl Material {
         match self {
             Material::Metal { texture, .. } => Some(texture),
             Material::Lambertian { texture } => Some(texture),
-            Material::DiffuseLight { texture } => Some(texture),
+            Material::DiffuseLight { texture, .. } => Some(texture),
+            Material::Isotropic { texture } => Some(texture),
+            Material::Bumped { material, .. } | Material::Cutout { material, .. } => {
+                material.texture()
+            }
             _ => None,
         }
     }
 
+    // A solid colored light, the part of the color above white goes into the strength
+    pub fn light(color: Vec3D<f32>) -> Material {
+        let strength = color.x.max(color.y).max(color.z).max(1.0);
+
+        Material::DiffuseLight {
+            texture: Texture::SolidColor {
+                color: Color::from_vec3d(color / strength),
+            },
+            strength,
+            temperature: None,
+        }
+    }
+
+    // Solid colors look the same everywhere, so their uvs aren't needed
+    pub fn uses_uv(&self) -> bool {
+        match self {
+            Material::Bumped { .. } | Material::Cutout { .. } => true,
+            _ => self
+                .texture()
+                .is_some_and(|texture| !matches!(texture, Texture::SolidColor { .. })),
+        }
+    }
+
+    // Replaces the texture with a solid color, dielectrics have no color and are unchanged
+    pub fn with_color(&self, color: Color) -> Material {
+        let texture = Texture::SolidColor { color };
+
+        match self {
+            Material::Lambertian { .. } => Material::Lambertian { texture },
+            Material::Metal { fuzz, .. } => Material::Metal {
+                texture,
+                fuzz: *fuzz,
+            },
+            Material::DiffuseLight {
+                strength,
+                temperature,
+                ..
+            } => Material::DiffuseLight {
+                texture,
+                strength: *strength,
+                temperature: *temperature,
+            },
+            Material::Isotropic { .. } => Material::Isotropic { texture },
+            Material::Dielectric { .. } => self.clone(),
+            Material::Bumped { .. } | Material::Cutout { .. } => {
+                self.rewrapped(self.base().with_color(color))
+            }
+        }
+    }
+
+    // Scales the strength of lights, which isn't clamped, anything else is unchanged
+    pub fn with_intensity(&self, intensity: f32) -> Material {
+        match self {
+            Material::DiffuseLight {
+                texture,
+                strength,
+                temperature,
+            } => Material::DiffuseLight {
+                texture: texture.clone(),
+                strength: strength * intensity.max(0.0),
+                temperature: *temperature,
+            },
+            Material::Bumped { .. } | Material::Cutout { .. } => {
+                self.rewrapped(self.base().with_intensity(intensity))
+            }
+            _ => self.clone(),
+        }
+    }
+
+    // Light given off by lights, black for everything else
+    fn emission(&self, record: &RayHit) -> Vec3D<f32> {
+        match self {
+            Material::DiffuseLight {
+                texture,
+                strength,
+                temperature,
+            } => {
+                let (color, tint) = (
+                    texture.color_value(record).0,
+                    temperature.map_or(Vec3D::new(1.0, 1.0, 1.0), |kelvin| {
+                        Color::blackbody(kelvin).0
+                    }),
+                );
+                Vec3D::new(color.x * tint.x, color.y * tint.y, color.z * tint.z) * *strength
+            }
+            _ => Vec3D::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    // Whether rays go through the surface at the hit
+    pub fn transparent_at(&self, record: &RayHit) -> bool {
+        match self {
+            Material::Cutout {
+                material,
+                opacity,
+                cutoff,
+            } => opacity.opacity(record) < *cutoff || material.transparent_at(record),
+            Material::Bumped { material, .. } => material.transparent_at(record),
+            _ => false,
+        }
+    }
+
+    // The material inside any surface maps and cutouts
+    pub fn base(&self) -> &Material {
+        match self {
+            Material::Bumped { material, .. } | Material::Cutout { material, .. } => {
+                material.base()
+            }
+            _ => self,
+        }
+    }
+
+    // Replaces the base material, keeping the surface maps and cutouts around it
+    pub fn rewrapped(&self, base: Material) -> Material {
+        match self {
+            Material::Bumped { material, map } => Material::Bumped {
+                material: Box::new(material.rewrapped(base)),
+                map: map.clone(),
+            },
+            Material::Cutout {
+                material,
+                opacity,
+                cutoff,
+            } => Material::Cutout {
+                material: Box::new(material.rewrapped(base)),
+                opacity: opacity.clone(),
+                cutoff: *cutoff,
+            },
+            _ => base,
+        }
+    }
+
     fn reflect(v: Vec3D<f32>, n: Vec3D<f32>) -> Vec3D<f32> {
         v - n * 2.0 * v.dot(&n)
     }
@@ -129,3 +306,276 @@ impl Default for Material {
         }
     }
 }
+
+// --------------------------------------------------
+
+// Relief read from a texture at the hit's uv, bent along the hit's tangents
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum SurfaceMap {
+    // Tangent space normals stored as colors like in most normal map images - red along u, green
+    // along v and blue out of the surface. The strength scales how far they tilt
+    Normal {
+        texture: Texture,
+        #[serde(default = "strength_default")]
+        strength: f32,
+    },
+    // Brightness is height, the normal tilts away from the higher parts. The strength is how high
+    // white is compared to the size of the texture
+    Height {
+        texture: Texture,
+        #[serde(default = "strength_default")]
+        strength: f32,
+    },
+}
+
+const fn strength_default() -> f32 {
+    1.0
+}
+
+impl SurfaceMap {
+    // The record with the normal bent by the map. A normal bent away from the ray is kept as it was
+    fn bent(&self, record: &RayHit, ray_in: &Ray) -> RayHit {
+        // Smallest distance in uv the height is compared over
+        const HEIGHT_STEP: f32 = 0.001;
+
+        let (tangent, bitangent) = record.tangent_frame();
+        let normal = match self {
+            SurfaceMap::Normal { texture, strength } => {
+                let color = texture.color_value(record).0;
+                tangent * ((color.x * 2.0 - 1.0) * strength)
+                    + bitangent * ((color.y * 2.0 - 1.0) * strength)
+                    + record.normal * (color.z * 2.0 - 1.0).max(0.0)
+            }
+            SurfaceMap::Height { texture, strength } => {
+                let step = record.uv_footprint.max(HEIGHT_STEP);
+                let height = |du: f32, dv: f32| {
+                    let color = texture
+                        .color_value(&RayHit {
+                            u: record.u + du,
+                            v: record.v + dv,
+                            ..record.clone()
+                        })
+                        .0;
+                    (color.x + color.y + color.z) / 3.0
+                };
+
+                let slope_u = (height(step, 0.0) - height(-step, 0.0)) / (2.0 * step);
+                let slope_v = (height(0.0, step) - height(0.0, -step)) / (2.0 * step);
+                record.normal - (tangent * slope_u + bitangent * slope_v) * *strength
+            }
+        };
+
+        if normal.length_squared() > 1e-12 && normal.dot(&ray_in.dir) < 0.0 {
+            RayHit {
+                normal: normal.unit_vec(),
+                ..record.clone()
+            }
+        } else {
+            record.clone()
+        }
+    }
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::texture::PatternSpace;
+    use crate::image::{ImageData, ImageSource};
+    use std::sync::Arc;
+
+    fn assert_close(a: Vec3D<f32>, b: Vec3D<f32>) {
+        assert!((a - b).length() < 1e-2, "{a:?} != {b:?}");
+    }
+
+    // Hit at uv (0.5, 0.5) on the xy plane, seen from above
+    fn record(tangents: (Vec3D<f32>, Vec3D<f32>)) -> RayHit {
+        RayHit::new(
+            Vec3D::new(0.0, 0.0, 0.0),
+            Vec3D::new(0.0, 0.0, 1.0),
+            true,
+            0.5,
+            0.5,
+            Arc::new(Material::default()),
+        )
+        .with_tangents(tangents)
+    }
+
+    fn normal_map(r: u8, g: u8, b: u8) -> SurfaceMap {
+        SurfaceMap::Normal {
+            texture: Texture::SolidColor {
+                color: Color::new(r, g, b),
+            },
+            strength: 1.0,
+        }
+    }
+
+    const DOWN: Ray = Ray::new(Vec3D::new(0.0, 0.0, 1.0), Vec3D::new(0.0, 0.0, -1.0));
+    const UV_TANGENTS: (Vec3D<f32>, Vec3D<f32>) =
+        (Vec3D::new(1.0, 0.0, 0.0), Vec3D::new(0.0, 1.0, 0.0));
+
+    #[test]
+    fn flat_maps_keep_the_normal() {
+        let record = record(UV_TANGENTS);
+        let height = SurfaceMap::Height {
+            texture: Texture::default(),
+            strength: 5.0,
+        };
+
+        for map in [normal_map(128, 128, 255), height] {
+            assert_close(map.bent(&record, &DOWN).normal, record.normal);
+        }
+    }
+
+    #[test]
+    fn normal_maps_tilt_along_the_tangents() {
+        let diagonal = (1.0_f32 / 2.0).sqrt();
+
+        // Red tilts along u and green along v, half way out of the surface
+        let bent = normal_map(218, 128, 218).bent(&record(UV_TANGENTS), &DOWN);
+        assert_close(bent.normal, Vec3D::new(diagonal, 0.0, diagonal));
+        let bent = normal_map(128, 218, 218).bent(&record(UV_TANGENTS), &DOWN);
+        assert_close(bent.normal, Vec3D::new(0.0, diagonal, diagonal));
+        // Turning the uvs turns the tilt with them
+        let turned = (Vec3D::new(0.0, 1.0, 0.0), Vec3D::new(-1.0, 0.0, 0.0));
+        let bent = normal_map(218, 128, 218).bent(&record(turned), &DOWN);
+        assert_close(bent.normal, Vec3D::new(0.0, diagonal, diagonal));
+        // Dark red tilts the other way
+        let bent = normal_map(38, 128, 218).bent(&record(UV_TANGENTS), &DOWN);
+        assert_close(bent.normal, Vec3D::new(-diagonal, 0.0, diagonal));
+    }
+
+    #[test]
+    fn height_maps_tilt_away_from_the_high_side() {
+        // Height grows from 0 to 1 over u
+        let map = SurfaceMap::Height {
+            texture: Texture::LinearGradient {
+                low_color: Color::new(0, 0, 0),
+                high_color: Color::new(255, 255, 255),
+                space: PatternSpace::Uv,
+                start: Vec3D::new(0.0, 0.0, 0.0),
+                end: Vec3D::new(1.0, 0.0, 0.0),
+            },
+            strength: 1.0,
+        };
+        let diagonal = (1.0_f32 / 2.0).sqrt();
+
+        let bent = map.bent(&record(UV_TANGENTS), &DOWN);
+        assert_close(bent.normal, Vec3D::new(-diagonal, 0.0, diagonal));
+    }
+
+    #[test]
+    fn bent_normals_are_unit_length() {
+        let record = record((Vec3D::new(3.0, 0.0, 0.0), Vec3D::new(0.0, 0.2, 0.0)));
+
+        for (r, g, b) in [(128, 128, 255), (255, 0, 0), (0, 255, 128), (200, 60, 10)] {
+            let bent = normal_map(r, g, b).bent(&record, &DOWN);
+            assert!((bent.normal.length() - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn normals_bent_away_from_the_ray_are_kept() {
+        let record = record(UV_TANGENTS);
+        let grazing = Ray::new(Vec3D::new(-1.0, 0.0, 0.1), Vec3D::new(1.0, 0.0, -0.1));
+
+        let bent = normal_map(255, 128, 128).bent(&record, &grazing);
+        assert_eq!(bent.normal, record.normal);
+    }
+
+    fn cutout(opacity: Texture) -> Material {
+        Material::Cutout {
+            material: Box::new(Material::default()),
+            opacity,
+            cutoff: 0.5,
+        }
+    }
+
+    fn gray(value: u8) -> Texture {
+        Texture::SolidColor {
+            color: Color::new(value, value, value),
+        }
+    }
+
+    #[test]
+    fn cutouts_are_transparent_below_the_cutoff() {
+        // Opaque on the left half of the image, transparent on the right
+        let image = ImageData::new(
+            ImageSource::Named("cutout_test.png".to_string()),
+            2,
+            vec![255; 6],
+        )
+        .with_alpha(vec![255, 0]);
+        let material = cutout(Texture::Image {
+            data: Arc::new(image),
+            uv: Default::default(),
+        });
+        let at_u = |u: f32| RayHit {
+            u,
+            ..record(UV_TANGENTS)
+        };
+
+        assert!(!material.transparent_at(&at_u(0.25)));
+        assert!(material.transparent_at(&at_u(0.75)));
+
+        // Without alpha the brightness is the opacity
+        let record = record(UV_TANGENTS);
+        assert!(cutout(gray(0)).transparent_at(&record));
+        assert!(cutout(gray(100)).transparent_at(&record));
+        assert!(!cutout(gray(200)).transparent_at(&record));
+        assert!(!Material::default().transparent_at(&record));
+        // Surface maps around cutouts keep the holes
+        let bumped = Material::Bumped {
+            material: Box::new(cutout(gray(0))),
+            map: normal_map(128, 128, 255),
+        };
+        assert!(bumped.transparent_at(&record));
+    }
+
+    #[test]
+    fn lights_emit_their_color_times_the_strength() {
+        let record = record(UV_TANGENTS);
+        let light = |strength: f32, temperature: Option<f32>| Material::DiffuseLight {
+            texture: Texture::SolidColor {
+                color: Color::new(255, 128, 0),
+            },
+            strength,
+            temperature,
+        };
+        let color = Color::new(255, 128, 0).0;
+
+        assert_close(light(1.0, None).emmited(&record).0, color);
+        assert_close(light(4.0, None).emmited(&record).0, color * 4.0);
+        assert_close(
+            light(0.0, None).emmited(&record).0,
+            Vec3D::new(0.0, 0.0, 0.0),
+        );
+        assert_close(
+            light(4.0, None).with_intensity(0.5).emmited(&record).0,
+            color * 2.0,
+        );
+        // The temperature tints the color before it's scaled
+        let tint = Color::blackbody(2000.0).0;
+        assert_close(
+            light(3.0, Some(2000.0)).emmited(&record).0,
+            Vec3D::new(color.x * tint.x, color.y * tint.y, color.z * tint.z) * 3.0,
+        );
+        // Other materials give off nothing
+        assert_close(
+            Material::default().emmited(&record).0,
+            Vec3D::new(0.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn bright_light_colors_go_into_the_strength() {
+        let light = Material::light(Vec3D::new(4.0, 2.0, 0.0));
+
+        assert!(matches!(light, Material::DiffuseLight { strength, .. } if strength == 4.0));
+        assert_close(
+            light.emmited(&record(UV_TANGENTS)).0,
+            Vec3D::new(4.0, 2.0, 0.0),
+        );
+    }
+}
@@ -0,0 +1,530 @@
+use super::ray::Ray;
+use crate::draw::Color;
+use crate::vector::{Vec2D, Vec3D, VectorOperation};
+use serde::{Deserialize, Serialize};
+
+// --------------------------------------------------
+
+const EPSILON: f32 = 1e-7;
+const TRIANGLES_PER_LEAF: usize = 4;
+
+// --------------------------------------------------
+
+// Möller–Trumbore, returns the distance and the barycentric coordinates of the second and third vertex
+pub fn intersect_triangle(
+    vertices: &[Vec3D<f32>; 3],
+    ray: &Ray,
+    dist_min: f32,
+    dist_max: f32,
+) -> Option<(f32, f32, f32)> {
+    let edge_1 = vertices[1] - vertices[0];
+    let edge_2 = vertices[2] - vertices[0];
+
+    let h = ray.dir.cross(&edge_2);
+    let a = edge_1.dot(&h);
+    if a.abs() < EPSILON {
+        return None;
+    }
+
+    let f = 1.0 / a;
+    let s = ray.from - vertices[0];
+    let b1 = f * s.dot(&h);
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q = s.cross(&edge_1);
+    let b2 = f * ray.dir.dot(&q);
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let dist = f * edge_2.dot(&q);
+    if dist > dist_min && dist < dist_max {
+        Some((dist, b1, b2))
+    } else {
+        None
+    }
+}
+
+// Geometric normal, shading normal and uv at the given barycentric coordinates. Without uvs the
+// barycentric coordinates are used instead
+pub fn triangle_shading(
+    vertices: &[Vec3D<f32>; 3],
+    normals: Option<&[Vec3D<f32>; 3]>,
+    uvs: Option<&[Vec2D<f32>; 3]>,
+    b1: f32,
+    b2: f32,
+) -> (Vec3D<f32>, Vec3D<f32>, Vec2D<f32>) {
+    let b0 = 1.0 - b1 - b2;
+
+    let geometric_normal = (vertices[1] - vertices[0])
+        .cross(&(vertices[2] - vertices[0]))
+        .unit_vec();
+    let shading_normal = match normals {
+        Some(normals) => {
+            let normal = (normals[0] * b0 + normals[1] * b1 + normals[2] * b2).unit_vec();
+            // Keep the interpolated normal on the same side as the surface
+            if normal.dot(&geometric_normal) < 0.0 {
+                -normal
+            } else {
+                normal
+            }
+        }
+        None => geometric_normal,
+    };
+    let uv = match uvs {
+        Some(uvs) => uvs[0] * b0 + uvs[1] * b1 + uvs[2] * b2,
+        None => Vec2D::new(b1, b2),
+    };
+
+    (geometric_normal, shading_normal, uv)
+}
+
+// Directions u and v grow in along the triangle, from the same uvs as `triangle_shading`
+pub fn triangle_tangents(
+    vertices: &[Vec3D<f32>; 3],
+    uvs: Option<&[Vec2D<f32>; 3]>,
+) -> (Vec3D<f32>, Vec3D<f32>) {
+    let (edge_1, edge_2) = (vertices[1] - vertices[0], vertices[2] - vertices[0]);
+    let Some(uvs) = uvs else {
+        return (edge_1, edge_2);
+    };
+
+    let (delta_1, delta_2) = (uvs[1] - uvs[0], uvs[2] - uvs[0]);
+    let determinant = delta_1.x * delta_2.y - delta_2.x * delta_1.y;
+    if determinant.abs() < 1e-12 {
+        return (edge_1, edge_2);
+    }
+
+    (
+        (edge_1 * delta_2.y - edge_2 * delta_1.y) / determinant,
+        (edge_2 * delta_1.x - edge_1 * delta_2.x) / determinant,
+    )
+}
+
+// --------------------------------------------------
+
+// Leaves cover a range of `Mesh::order`, inner nodes are followed by their first child and store
+// the index of their second one in `start`
+#[derive(Clone, Debug, PartialEq)]
+struct BvhNode {
+    min: Vec3D<f32>,
+    max: Vec3D<f32>,
+    start: u32,
+    count: u32,
+}
+
+impl BvhNode {
+    fn hit(&self, from: Vec3D<f32>, inv_dir: Vec3D<f32>, dist_min: f32, dist_max: f32) -> bool {
+        let (mut t_min, mut t_max) = (dist_min, dist_max);
+
+        for (min, max, from, inv_dir) in [
+            (self.min.x, self.max.x, from.x, inv_dir.x),
+            (self.min.y, self.max.y, from.y, inv_dir.y),
+            (self.min.z, self.max.z, from.z, inv_dir.z),
+        ] {
+            let (t0, t1) = ((min - from) * inv_dir, (max - from) * inv_dir);
+            let (t0, t1) = if inv_dir < 0.0 { (t1, t0) } else { (t0, t1) };
+
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// --------------------------------------------------
+
+#[derive(Clone, Debug, Deserialize)]
+struct MeshData {
+    positions: Vec<Vec3D<f32>>,
+    #[serde(default)]
+    normals: Vec<Vec3D<f32>>,
+    #[serde(default)]
+    uvs: Vec<Vec2D<f32>>,
+    #[serde(default)]
+    colors: Vec<Color>,
+    indices: Vec<[u32; 3]>,
+    #[serde(default = "smooth_default")]
+    smooth: bool,
+}
+
+const fn smooth_default() -> bool {
+    true
+}
+
+// Indexed triangle mesh, vertex positions are relative to the position of the object holding it
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(try_from = "MeshData")]
+pub struct Mesh {
+    pub positions: Vec<Vec3D<f32>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub normals: Vec<Vec3D<f32>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub uvs: Vec<Vec2D<f32>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub colors: Vec<Color>,
+    pub indices: Vec<[u32; 3]>,
+    pub smooth: bool,
+    #[serde(skip)]
+    bvh: Vec<BvhNode>,
+    #[serde(skip)]
+    order: Vec<u32>,
+}
+
+impl Mesh {
+    // Normals, uvs and colors are optional (empty), but if given there has to be one per position.
+    // Smooth meshes without normals get them averaged from the surrounding faces
+    pub fn new(
+        positions: Vec<Vec3D<f32>>,
+        normals: Vec<Vec3D<f32>>,
+        uvs: Vec<Vec2D<f32>>,
+        colors: Vec<Color>,
+        indices: Vec<[u32; 3]>,
+        smooth: bool,
+    ) -> Result<Mesh, String> {
+        if indices.is_empty() {
+            return Err("Mesh has no triangles".to_string());
+        }
+        if let Some(index) = indices
+            .iter()
+            .flatten()
+            .find(|index| **index as usize >= positions.len())
+        {
+            return Err(format!(
+                "Mesh index {index:} is out of range, there are only {:} positions",
+                positions.len()
+            ));
+        }
+        if !normals.is_empty() && normals.len() != positions.len() {
+            return Err(format!(
+                "Mesh has {:} normals for {:} positions",
+                normals.len(),
+                positions.len()
+            ));
+        }
+        if !uvs.is_empty() && uvs.len() != positions.len() {
+            return Err(format!(
+                "Mesh has {:} uvs for {:} positions",
+                uvs.len(),
+                positions.len()
+            ));
+        }
+        if !colors.is_empty() && colors.len() != positions.len() {
+            return Err(format!(
+                "Mesh has {:} colors for {:} positions",
+                colors.len(),
+                positions.len()
+            ));
+        }
+
+        let normals = if smooth && normals.is_empty() {
+            Mesh::vertex_normals(&positions, &indices)
+        } else {
+            normals
+        };
+
+        let mut out = Mesh {
+            positions,
+            normals,
+            uvs,
+            colors,
+            order: (0..indices.len() as u32).collect(),
+            indices,
+            smooth,
+            bvh: vec![],
+        };
+        out.build_bvh(0, out.indices.len());
+
+        Ok(out)
+    }
+
+    pub fn triangle(&self, index: usize) -> [Vec3D<f32>; 3] {
+        self.indices[index].map(|i| self.positions[i as usize])
+    }
+
+    // Bounding box of the whole mesh
+    pub fn bounds(&self) -> (Vec3D<f32>, Vec3D<f32>) {
+        (self.bvh[0].min, self.bvh[0].max)
+    }
+
+    // Closest hit, returns the distance, the index of the triangle and its barycentric coordinates
+    pub fn hit(&self, ray: &Ray, dist_min: f32, dist_max: f32) -> Option<(f32, usize, f32, f32)> {
+        let inv_dir = Vec3D::new(1.0 / ray.dir.x, 1.0 / ray.dir.y, 1.0 / ray.dir.z);
+
+        let mut closest: Option<(f32, usize, f32, f32)> = None;
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.bvh[node_index];
+            let dist_max = closest.map_or(dist_max, |closest| closest.0);
+
+            if !node.hit(ray.from, inv_dir, dist_min, dist_max) {
+                continue;
+            }
+
+            if node.count > 0 {
+                for triangle in &self.order[node.start as usize..(node.start + node.count) as usize] {
+                    let dist_max = closest.map_or(dist_max, |closest| closest.0);
+
+                    if let Some((dist, b1, b2)) =
+                        intersect_triangle(&self.triangle(*triangle as usize), ray, dist_min, dist_max)
+                    {
+                        closest = Some((dist, *triangle as usize, b1, b2));
+                    }
+                }
+            } else {
+                stack.push(node.start as usize);
+                stack.push(node_index + 1);
+            }
+        }
+
+        closest
+    }
+
+    // Geometric normal, shading normal and uv of a point on the given triangle
+    pub fn shading(&self, triangle: usize, b1: f32, b2: f32) -> (Vec3D<f32>, Vec3D<f32>, Vec2D<f32>) {
+        let indices = self.indices[triangle];
+
+        triangle_shading(
+            &self.triangle(triangle),
+            if self.smooth && !self.normals.is_empty() {
+                Some(indices.map(|i| self.normals[i as usize]))
+            } else {
+                None
+            }
+            .as_ref(),
+            if self.uvs.is_empty() {
+                None
+            } else {
+                Some(indices.map(|i| self.uvs[i as usize]))
+            }
+            .as_ref(),
+            b1,
+            b2,
+        )
+    }
+
+    pub fn tangents(&self, triangle: usize) -> (Vec3D<f32>, Vec3D<f32>) {
+        let indices = self.indices[triangle];
+
+        triangle_tangents(
+            &self.triangle(triangle),
+            if self.uvs.is_empty() {
+                None
+            } else {
+                Some(indices.map(|i| self.uvs[i as usize]))
+            }
+            .as_ref(),
+        )
+    }
+
+    // Interpolated vertex color, if the mesh has them
+    pub fn vertex_color(&self, triangle: usize, b1: f32, b2: f32) -> Option<Color> {
+        if self.colors.is_empty() {
+            return None;
+        }
+
+        let [c0, c1, c2] = self.indices[triangle].map(|i| self.colors[i as usize].0);
+        Some(Color::from_vec3d(c0 * (1.0 - b1 - b2) + c1 * b1 + c2 * b2))
+    }
+
+    fn vertex_normals(positions: &[Vec3D<f32>], indices: &[[u32; 3]]) -> Vec<Vec3D<f32>> {
+        let mut normals = vec![Vec3D::new(0.0, 0.0, 0.0); positions.len()];
+
+        for triangle in indices {
+            let [a, b, c] = triangle.map(|i| positions[i as usize]);
+            // Not normalized, so bigger faces weigh more
+            let face_normal = (b - a).cross(&(c - a));
+
+            for i in triangle {
+                normals[*i as usize] += face_normal;
+            }
+        }
+
+        normals
+            .into_iter()
+            .map(|normal| {
+                if normal.length_squared() > 0.0 {
+                    normal.unit_vec()
+                } else {
+                    normal
+                }
+            })
+            .collect()
+    }
+
+    // Splits the triangles in order[start..end] at the median of their centroids along the
+    // longest axis, returns the index of the created node
+    fn build_bvh(&mut self, start: usize, end: usize) -> usize {
+        let (mut min, mut max) = (
+            Vec3D::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            Vec3D::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        );
+        for triangle in &self.order[start..end] {
+            for vertex in self.triangle(*triangle as usize) {
+                min = Vec3D::new(min.x.min(vertex.x), min.y.min(vertex.y), min.z.min(vertex.z));
+                max = Vec3D::new(max.x.max(vertex.x), max.y.max(vertex.y), max.z.max(vertex.z));
+            }
+        }
+
+        let node_index = self.bvh.len();
+        self.bvh.push(BvhNode {
+            min,
+            max,
+            start: start as u32,
+            count: (end - start) as u32,
+        });
+
+        if end - start > TRIANGLES_PER_LEAF {
+            let extent = max - min;
+            let axis = if extent.x >= extent.y && extent.x >= extent.z {
+                0
+            } else if extent.y >= extent.z {
+                1
+            } else {
+                2
+            };
+            let centroid = |mesh: &Mesh, triangle: u32| {
+                let [a, b, c] = mesh.triangle(triangle as usize);
+                let centroid = a + b + c;
+                match axis {
+                    0 => centroid.x,
+                    1 => centroid.y,
+                    _ => centroid.z,
+                }
+            };
+
+            let mut order = self.order[start..end].to_vec();
+            order.sort_by(|a, b| centroid(self, *a).total_cmp(&centroid(self, *b)));
+            self.order[start..end].copy_from_slice(&order);
+
+            let middle = (start + end) / 2;
+            self.build_bvh(start, middle);
+            let second_child = self.build_bvh(middle, end);
+
+            self.bvh[node_index].start = second_child as u32;
+            self.bvh[node_index].count = 0;
+        }
+
+        node_index
+    }
+}
+
+impl TryFrom<MeshData> for Mesh {
+    type Error = String;
+
+    fn try_from(item: MeshData) -> Result<Mesh, String> {
+        Mesh::new(
+            item.positions,
+            item.normals,
+            item.uvs,
+            item.colors,
+            item.indices,
+            item.smooth,
+        )
+    }
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_point(rng: &mut StdRng, size: f32) -> Vec3D<f32> {
+        Vec3D::new(
+            rng.gen_range(-size..size),
+            rng.gen_range(-size..size),
+            rng.gen_range(-size..size),
+        )
+    }
+
+    // Enough small triangles scattered in a box for the bvh to have several levels
+    fn random_mesh(rng: &mut StdRng) -> Mesh {
+        let mut positions = vec![];
+        for _ in 0..300 {
+            let center = random_point(rng, 2.0);
+            positions.extend((0..3).map(|_| center + random_point(rng, 0.3)));
+        }
+        let indices = (0..300).map(|i| [i * 3, i * 3 + 1, i * 3 + 2]).collect();
+
+        Mesh::new(positions, vec![], vec![], vec![], indices, false).unwrap()
+    }
+
+    // Tests every triangle, the closest hit is what the bvh has to find
+    fn brute_force_hit(mesh: &Mesh, ray: &Ray) -> Option<(f32, usize, f32, f32)> {
+        (0..mesh.indices.len())
+            .filter_map(|triangle| {
+                intersect_triangle(&mesh.triangle(triangle), ray, 0.001, f32::INFINITY)
+                    .map(|(dist, b1, b2)| (dist, triangle, b1, b2))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    #[test]
+    fn triangle_hit_gives_distance_and_barycentrics() {
+        let vertices = [
+            Vec3D::new(0.0, 0.0, 2.0),
+            Vec3D::new(1.0, 0.0, 2.0),
+            Vec3D::new(0.0, 1.0, 2.0),
+        ];
+        let ray = Ray::new(Vec3D::new(0.25, 0.5, 0.0), Vec3D::new(0.0, 0.0, 1.0));
+
+        let (dist, b1, b2) = intersect_triangle(&vertices, &ray, 0.001, f32::INFINITY).unwrap();
+        assert!((dist - 2.0).abs() < 1e-5);
+        assert!((b1 - 0.25).abs() < 1e-5 && (b2 - 0.5).abs() < 1e-5);
+
+        // Outside the triangle, behind the ray and past dist_max
+        let miss = Ray::new(Vec3D::new(0.75, 0.75, 0.0), Vec3D::new(0.0, 0.0, 1.0));
+        assert!(intersect_triangle(&vertices, &miss, 0.001, f32::INFINITY).is_none());
+        let behind = Ray::new(Vec3D::new(0.25, 0.5, 3.0), Vec3D::new(0.0, 0.0, 1.0));
+        assert!(intersect_triangle(&vertices, &behind, 0.001, f32::INFINITY).is_none());
+        assert!(intersect_triangle(&vertices, &ray, 0.001, 1.5).is_none());
+    }
+
+    #[test]
+    fn bvh_hit_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(31);
+        let mesh = random_mesh(&mut rng);
+        assert!(mesh.bvh.len() > 1);
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let from = random_point(&mut rng, 4.0);
+            let ray = Ray::new(from, random_point(&mut rng, 1.0) - from);
+
+            let (bvh, brute_force) = (
+                mesh.hit(&ray, 0.001, f32::INFINITY),
+                brute_force_hit(&mesh, &ray),
+            );
+            match (bvh, brute_force) {
+                (Some(bvh), Some(brute_force)) => {
+                    hits += 1;
+                    assert!((bvh.0 - brute_force.0).abs() < 1e-5);
+                    assert_eq!(bvh.1, brute_force.1);
+                }
+                (None, None) => {}
+                (bvh, brute_force) => panic!("bvh hit {bvh:?}, brute force hit {brute_force:?}"),
+            }
+        }
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn bounds_contain_every_position() {
+        let mesh = random_mesh(&mut StdRng::seed_from_u64(32));
+        let (min, max) = mesh.bounds();
+
+        assert!(mesh.positions.iter().all(|p| {
+            (min.x..=max.x).contains(&p.x)
+                && (min.y..=max.y).contains(&p.y)
+                && (min.z..=max.z).contains(&p.z)
+        }));
+    }
+}
@@ -1,13 +1,25 @@
+mod animation;
 mod camera;
 mod material;
+mod mesh;
+mod noise;
 mod object;
+mod primitive;
 mod ray;
 mod scene;
+mod sdf;
+mod stereo;
 mod texture;
+mod transform;
+mod volume;
 
-pub use camera::{Camera, CameraInput};
-pub use material::Material;
-pub use object::{Object3D, ObjectList};
+pub use camera::{Camera, CameraInput, Projection};
+pub use material::{Material, SurfaceMap};
+pub use mesh::Mesh;
+pub use object::{CsgOperation, Object3D, ObjectList};
 pub use ray::{Ray, RayHit};
-pub use scene::{Scene, SceneList, RENDER};
-pub use texture::Texture;
+pub use scene::{Scene, SceneList, RENDER, SEQUENCE};
+pub use stereo::{Stereo, StereoMode};
+pub use texture::{Texture, UvTransform, WrapMode};
+pub use transform::Transform;
+pub use volume::register_volume_file;
@@ -0,0 +1,220 @@
+use crate::vector::{Vec3D, VectorOperation};
+
+// --------------------------------------------------
+
+// Gradient noise without a permutation table - lattice gradients come from hashing the lattice
+// point, so there is no state to store in scene files
+
+fn hash(x: i32, y: i32, z: i32) -> u32 {
+    let mut h = (x as u32)
+        .wrapping_mul(0x8da6_b343)
+        .wrapping_add((y as u32).wrapping_mul(0xd816_3841))
+        .wrapping_add((z as u32).wrapping_mul(0xcb1a_b31f));
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    h = h.wrapping_mul(0x297a_2d39);
+    h ^ (h >> 15)
+}
+
+// One of the 12 edge directions of a cube
+fn gradient(hash: u32, offset: Vec3D<f32>) -> f32 {
+    let (x, y, z) = (offset.x, offset.y, offset.z);
+
+    match hash % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+// Perlin noise, roughly between -1 and 1
+pub fn perlin(p: Vec3D<f32>) -> f32 {
+    let cell = Vec3D::new(p.x.floor(), p.y.floor(), p.z.floor());
+    let local = p - cell;
+    let (x, y, z) = (cell.x as i32, cell.y as i32, cell.z as i32);
+    let (u, v, w) = (fade(local.x), fade(local.y), fade(local.z));
+
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        gradient(
+            hash(x + dx, y + dy, z + dz),
+            local - Vec3D::new(dx as f32, dy as f32, dz as f32),
+        )
+    };
+
+    lerp(
+        lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        ),
+        lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        ),
+        w,
+    )
+}
+
+// Octaves of perlin noise, each with double the frequency and half the amplitude of the last one.
+// Normalized to stay roughly between -1 and 1
+pub fn fbm(p: Vec3D<f32>, octaves: u32) -> f32 {
+    let (mut sum, mut amplitude, mut total) = (0.0, 1.0, 0.0);
+    let mut p = p;
+
+    for _ in 0..octaves.max(1) {
+        sum += perlin(p) * amplitude;
+        total += amplitude;
+        amplitude *= 0.5;
+        p *= 2.0;
+    }
+
+    sum / total
+}
+
+// Moved from around -1..1 to 0..1, for densities and other values that can't be negative
+pub fn fbm_normalized(p: Vec3D<f32>, octaves: u32) -> f32 {
+    (fbm(p, octaves) * 0.5 + 0.5).clamp(0.0, 1.0)
+}
+
+// Octaves like fbm, but of the absolute noise - creases where it crosses 0. Between 0 and 1
+pub fn turbulence(p: Vec3D<f32>, octaves: u32) -> f32 {
+    let (mut sum, mut amplitude, mut total) = (0.0, 1.0, 0.0);
+    let mut p = p;
+
+    for _ in 0..octaves.max(1) {
+        sum += perlin(p).abs() * amplitude;
+        total += amplitude;
+        amplitude *= 0.5;
+        p *= 2.0;
+    }
+
+    (sum / total * 2.0).min(1.0)
+}
+
+// Worley noise - the distance to the closest of points scattered one per unit cell. Between 0 and
+// about 1, 0 at the points
+pub fn worley(p: Vec3D<f32>) -> f32 {
+    let cell = Vec3D::new(p.x.floor(), p.y.floor(), p.z.floor());
+    let (x, y, z) = (cell.x as i32, cell.y as i32, cell.z as i32);
+    let mut closest = f32::INFINITY;
+
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            for dz in -1..=1 {
+                let h = hash(x + dx, y + dy, z + dz);
+                let point = Vec3D::new(
+                    (x + dx) as f32 + (h & 0x3ff) as f32 / 1023.0,
+                    (y + dy) as f32 + ((h >> 10) & 0x3ff) as f32 / 1023.0,
+                    (z + dz) as f32 + ((h >> 20) & 0x3ff) as f32 / 1023.0,
+                );
+                closest = closest.min((point - p).length());
+            }
+        }
+    }
+
+    closest.min(1.0)
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_points(seed: u64, count: usize) -> Vec<Vec3D<f32>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|_| {
+                Vec3D::new(
+                    rng.gen_range(-50.0..50.0),
+                    rng.gen_range(-50.0..50.0),
+                    rng.gen_range(-50.0..50.0),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn noise_stays_in_its_range() {
+        let points = random_points(42, 5000);
+
+        for p in &points {
+            assert!(perlin(*p).abs() <= 1.1);
+            assert!(fbm(*p, 5).abs() <= 1.1);
+            assert!((0.0..=1.0).contains(&fbm_normalized(*p, 5)));
+            assert!((0.0..=1.0).contains(&turbulence(*p, 5)));
+            assert!((0.0..=1.0).contains(&worley(*p)));
+        }
+        // It isn't flat either
+        assert!(points.iter().any(|p| perlin(*p) > 0.4));
+        assert!(points.iter().any(|p| perlin(*p) < -0.4));
+        assert!(points.iter().any(|p| worley(*p) < 0.1));
+    }
+
+    #[test]
+    fn noise_is_deterministic() {
+        for p in random_points(43, 100) {
+            assert_eq!(perlin(p), perlin(p));
+            assert_eq!(fbm(p, 4), fbm(p, 4));
+            assert_eq!(turbulence(p, 4), turbulence(p, 4));
+            assert_eq!(worley(p), worley(p));
+        }
+        // At least one octave is always used
+        let p = Vec3D::new(0.3, 1.7, -2.2);
+        assert_eq!(fbm(p, 0), perlin(p));
+        // Gradient noise is 0 on the lattice
+        assert_eq!(perlin(Vec3D::new(3.0, -2.0, 7.0)), 0.0);
+    }
+
+    #[test]
+    fn noise_is_continuous_across_cells() {
+        const EPSILON: f32 = 0.0001;
+
+        for p in random_points(44, 500) {
+            // Just before and after a cell boundary along every axis
+            let boundaries = [
+                (
+                    Vec3D::new(p.x.round(), p.y, p.z),
+                    Vec3D::new(EPSILON, 0.0, 0.0),
+                ),
+                (
+                    Vec3D::new(p.x, p.y.round(), p.z),
+                    Vec3D::new(0.0, EPSILON, 0.0),
+                ),
+                (
+                    Vec3D::new(p.x, p.y, p.z.round()),
+                    Vec3D::new(0.0, 0.0, EPSILON),
+                ),
+            ];
+
+            for (boundary, step) in boundaries {
+                let (before, after) = (boundary - step, boundary + step);
+                assert!((perlin(before) - perlin(after)).abs() < 0.001);
+                assert!((fbm(before, 4) - fbm(after, 4)).abs() < 0.005);
+                assert!((turbulence(before, 4) - turbulence(after, 4)).abs() < 0.01);
+                assert!((worley(before) - worley(after)).abs() < 0.001);
+            }
+        }
+    }
+}
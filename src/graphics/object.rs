@@ -2,14 +2,49 @@ use std::cmp::Ordering;
 use std::sync::Arc;
 
 use super::camera::Camera;
+use super::mesh::{intersect_triangle, triangle_shading, triangle_tangents, Mesh};
+use super::primitive::{
+    intersect_cone, intersect_cylinder, intersect_disk, intersect_plane, intersect_torus, LocalHit,
+};
 use super::ray::{Ray, RayHit};
+use super::sdf::Sdf;
+use super::transform::Transform;
+use super::volume::DensityField;
 use super::Material;
-use super::Texture;
 use crate::vector::{Vec2D, Vec3D, VectorOperation};
+use lazy_static::lazy_static;
+use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 
 // --------------------------------------------------
 
+lazy_static! {
+    // Reported for instances of empty groups, which have no material of their own
+    static ref EMPTY_INSTANCE_MATERIAL: Arc<Material> = Arc::new(Material::default());
+}
+
+// --------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    // The left object with the right one cut out of it
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+// --------------------------------------------------
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum Object3D {
     Sphere {
@@ -41,6 +76,107 @@ pub enum Object3D {
         k: f32,
         material: Arc<Material>,
     },
+    // Parallelogram spanned by the edges u and v from pos
+    Quad {
+        pos: Vec3D<f32>,
+        u: Vec3D<f32>,
+        v: Vec3D<f32>,
+        material: Arc<Material>,
+    },
+    // Brick rotated around its corner pos (in degrees, around the x, y and z axis), built from
+    // quads
+    OrientedBrick {
+        pos: Vec3D<f32>,
+        size: Vec3D<f32>,
+        rotation: Vec3D<f32>,
+        sides: ObjectList,
+        material: Arc<Material>,
+    },
+    Triangle {
+        vertices: [Vec3D<f32>; 3],
+        #[serde(default)]
+        normals: Option<[Vec3D<f32>; 3]>,
+        #[serde(default)]
+        uvs: Option<[Vec2D<f32>; 3]>,
+        material: Arc<Material>,
+    },
+    Mesh {
+        pos: Vec3D<f32>,
+        mesh: Arc<Mesh>,
+        material: Arc<Material>,
+    },
+    // Fog / smoke filling the boundary, rays scatter inside at random distances which get shorter
+    // the denser it is. Boundaries have to be closed and convex
+    ConstantMedium {
+        boundary: Box<Object3D>,
+        density: f32,
+        material: Arc<Material>,
+    },
+    // Like a constant medium, but the density changes from point to point (clouds, explosions).
+    // The field goes from 0 to 1 and is scaled by max_density
+    HeterogeneousMedium {
+        boundary: Box<Object3D>,
+        density: DensityField,
+        max_density: f32,
+        material: Arc<Material>,
+    },
+    // A shared group of objects placed with a transform, rays are moved into the group's space
+    // instead of copying it
+    Instance {
+        objects: Arc<ObjectList>,
+        #[serde(default)]
+        transform: Transform,
+    },
+    // Constructive solid geometry - combines the insides of two closed objects. Nested csg
+    // objects work too
+    Csg {
+        operation: CsgOperation,
+        left: Box<Object3D>,
+        right: Box<Object3D>,
+    },
+    // Analytic primitives, placed by their transform. Before it they stand on the origin with the
+    // y axis going up through them, see primitive.rs. The plane is endless
+    Plane {
+        #[serde(default)]
+        transform: Transform,
+        material: Arc<Material>,
+    },
+    Disk {
+        radius: f32,
+        #[serde(default)]
+        transform: Transform,
+        material: Arc<Material>,
+    },
+    Cylinder {
+        radius: f32,
+        height: f32,
+        #[serde(default)]
+        transform: Transform,
+        material: Arc<Material>,
+    },
+    Cone {
+        radius: f32,
+        height: f32,
+        #[serde(default)]
+        transform: Transform,
+        material: Arc<Material>,
+    },
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+        #[serde(default)]
+        transform: Transform,
+        material: Arc<Material>,
+    },
+    // Surface of a signed distance field around pos, found by sphere tracing. The bound is the
+    // radius of a sphere around pos the field fits into, rays missing it are skipped
+    Sdf {
+        pos: Vec3D<f32>,
+        sdf: Sdf,
+        #[serde(default)]
+        bound: Option<f32>,
+        material: Arc<Material>,
+    },
 }
 
 impl Object3D {
@@ -108,6 +244,314 @@ impl Object3D {
                     material: material.clone(),
                 }
             }
+            Object3D::Quad { .. }
+            | Object3D::OrientedBrick { .. }
+            | Object3D::Triangle { .. }
+            | Object3D::Mesh { .. }
+            | Object3D::ConstantMedium { .. }
+            | Object3D::HeterogeneousMedium { .. }
+            | Object3D::Instance { .. }
+            | Object3D::Csg { .. }
+            | Object3D::Plane { .. }
+            | Object3D::Disk { .. }
+            | Object3D::Cylinder { .. }
+            | Object3D::Cone { .. }
+            | Object3D::Torus { .. }
+            | Object3D::Sdf { .. } => self.translated(-camera.pos),
+        }
+    }
+
+    // Reference point of the object, the minimal corner for bricks and rects
+    pub fn position(&self) -> Vec3D<f32> {
+        match self {
+            Object3D::Sphere { pos, .. }
+            | Object3D::Brick { pos, .. }
+            | Object3D::Quad { pos, .. }
+            | Object3D::OrientedBrick { pos, .. } => *pos,
+            Object3D::XYRect { pos, k, .. } => Vec3D::new(pos.x, pos.y, *k),
+            Object3D::XZRect { pos, k, .. } => Vec3D::new(pos.x, *k, pos.y),
+            Object3D::YZRect { pos, k, .. } => Vec3D::new(*k, pos.x, pos.y),
+            Object3D::Triangle { vertices, .. } => vertices[0],
+            Object3D::Mesh { pos, .. } => *pos,
+            Object3D::ConstantMedium { boundary, .. }
+            | Object3D::HeterogeneousMedium { boundary, .. } => boundary.position(),
+            Object3D::Instance { transform, .. } => transform.translation,
+            Object3D::Csg { left, .. } => left.position(),
+            Object3D::Plane { transform, .. }
+            | Object3D::Disk { transform, .. }
+            | Object3D::Cylinder { transform, .. }
+            | Object3D::Cone { transform, .. }
+            | Object3D::Torus { transform, .. } => transform.translation,
+            Object3D::Sdf { pos, .. } => *pos,
+        }
+    }
+
+    pub fn translated(&self, offset: Vec3D<f32>) -> Object3D {
+        match self {
+            Object3D::Sphere {
+                pos,
+                radius,
+                material,
+            } => Object3D::Sphere {
+                pos: *pos + offset,
+                radius: *radius,
+                material: material.clone(),
+            },
+            Object3D::Brick {
+                pos,
+                corner,
+                material,
+                ..
+            } => Object3D::brick(*pos + offset, *corner - *pos, material.clone()),
+            Object3D::XYRect {
+                pos,
+                corner,
+                k,
+                material,
+            } => {
+                let shift = Vec2D::new(offset.x, offset.y);
+                Object3D::XYRect {
+                    pos: *pos + shift,
+                    corner: *corner + shift,
+                    k: *k + offset.z,
+                    material: material.clone(),
+                }
+            }
+            Object3D::XZRect {
+                pos,
+                corner,
+                k,
+                material,
+            } => {
+                let shift = Vec2D::new(offset.x, offset.z);
+                Object3D::XZRect {
+                    pos: *pos + shift,
+                    corner: *corner + shift,
+                    k: *k + offset.y,
+                    material: material.clone(),
+                }
+            }
+            Object3D::YZRect {
+                pos,
+                corner,
+                k,
+                material,
+            } => {
+                let shift = Vec2D::new(offset.y, offset.z);
+                Object3D::YZRect {
+                    pos: *pos + shift,
+                    corner: *corner + shift,
+                    k: *k + offset.x,
+                    material: material.clone(),
+                }
+            }
+            Object3D::Quad {
+                pos,
+                u,
+                v,
+                material,
+            } => Object3D::Quad {
+                pos: *pos + offset,
+                u: *u,
+                v: *v,
+                material: material.clone(),
+            },
+            Object3D::OrientedBrick {
+                pos,
+                size,
+                rotation,
+                material,
+                ..
+            } => Object3D::oriented_brick(*pos + offset, *size, *rotation, material.clone()),
+            Object3D::Triangle {
+                vertices,
+                normals,
+                uvs,
+                material,
+            } => Object3D::Triangle {
+                vertices: vertices.map(|vertex| vertex + offset),
+                normals: *normals,
+                uvs: *uvs,
+                material: material.clone(),
+            },
+            Object3D::Mesh {
+                pos,
+                mesh,
+                material,
+            } => Object3D::Mesh {
+                pos: *pos + offset,
+                mesh: mesh.clone(),
+                material: material.clone(),
+            },
+            Object3D::ConstantMedium {
+                boundary,
+                density,
+                material,
+            } => Object3D::ConstantMedium {
+                boundary: Box::new(boundary.translated(offset)),
+                density: *density,
+                material: material.clone(),
+            },
+            Object3D::HeterogeneousMedium {
+                boundary,
+                density,
+                max_density,
+                material,
+            } => Object3D::HeterogeneousMedium {
+                boundary: Box::new(boundary.translated(offset)),
+                density: density.translated(offset),
+                max_density: *max_density,
+                material: material.clone(),
+            },
+            Object3D::Instance { objects, transform } => Object3D::Instance {
+                objects: objects.clone(),
+                transform: transform.translated(offset),
+            },
+            Object3D::Csg {
+                operation,
+                left,
+                right,
+            } => Object3D::Csg {
+                operation: *operation,
+                left: Box::new(left.translated(offset)),
+                right: Box::new(right.translated(offset)),
+            },
+            Object3D::Plane { .. }
+            | Object3D::Disk { .. }
+            | Object3D::Cylinder { .. }
+            | Object3D::Cone { .. }
+            | Object3D::Torus { .. } => {
+                let mut out = self.clone();
+                match &mut out {
+                    Object3D::Plane { transform, .. }
+                    | Object3D::Disk { transform, .. }
+                    | Object3D::Cylinder { transform, .. }
+                    | Object3D::Cone { transform, .. }
+                    | Object3D::Torus { transform, .. } => *transform = transform.translated(offset),
+                    _ => unreachable!(),
+                }
+                out
+            }
+            Object3D::Sdf {
+                pos,
+                sdf,
+                bound,
+                material,
+            } => Object3D::Sdf {
+                pos: *pos + offset,
+                sdf: sdf.clone(),
+                bound: *bound,
+                material: material.clone(),
+            },
+        }
+    }
+
+    // Only spheres have a radius, other objects are returned unchanged
+    pub fn with_radius(&self, radius: f32) -> Object3D {
+        match self {
+            Object3D::Sphere { pos, material, .. } => Object3D::Sphere {
+                pos: *pos,
+                radius,
+                material: material.clone(),
+            },
+            Object3D::Disk { .. } | Object3D::Cylinder { .. } | Object3D::Cone { .. } => {
+                let mut out = self.clone();
+                if let Object3D::Disk { radius: r, .. }
+                | Object3D::Cylinder { radius: r, .. }
+                | Object3D::Cone { radius: r, .. } = &mut out
+                {
+                    *r = radius;
+                }
+                out
+            }
+            _ => self.clone(),
+        }
+    }
+
+    pub fn material(&self) -> &Arc<Material> {
+        match self {
+            Object3D::Sphere { material, .. }
+            | Object3D::Brick { material, .. }
+            | Object3D::XYRect { material, .. }
+            | Object3D::XZRect { material, .. }
+            | Object3D::YZRect { material, .. }
+            | Object3D::Quad { material, .. }
+            | Object3D::OrientedBrick { material, .. }
+            | Object3D::Triangle { material, .. }
+            | Object3D::Mesh { material, .. }
+            | Object3D::ConstantMedium { material, .. }
+            | Object3D::HeterogeneousMedium { material, .. }
+            | Object3D::Plane { material, .. }
+            | Object3D::Disk { material, .. }
+            | Object3D::Cylinder { material, .. }
+            | Object3D::Cone { material, .. }
+            | Object3D::Torus { material, .. }
+            | Object3D::Sdf { material, .. } => material,
+            // The material of the first object of the group
+            Object3D::Instance { objects, .. } => objects
+                .objects
+                .first()
+                .map_or(&EMPTY_INSTANCE_MATERIAL, |object| object.material()),
+            Object3D::Csg { left, .. } => left.material(),
+        }
+    }
+
+    pub fn with_material(&self, material: Material) -> Object3D {
+        let material = Arc::new(material);
+
+        match self {
+            Object3D::Brick { pos, corner, .. } => Object3D::brick(*pos, *corner - *pos, material),
+            Object3D::OrientedBrick {
+                pos,
+                size,
+                rotation,
+                ..
+            } => Object3D::oriented_brick(*pos, *size, *rotation, material),
+            // Every object of the group gets the material
+            Object3D::Instance { objects, transform } => Object3D::Instance {
+                objects: Arc::new(ObjectList::new(
+                    objects
+                        .objects
+                        .iter()
+                        .map(|object| object.with_material((*material).clone()))
+                        .collect(),
+                )),
+                transform: transform.clone(),
+            },
+            Object3D::Csg {
+                operation,
+                left,
+                right,
+            } => Object3D::Csg {
+                operation: *operation,
+                left: Box::new(left.with_material((*material).clone())),
+                right: Box::new(right.with_material((*material).clone())),
+            },
+            _ => {
+                let mut out = self.clone();
+                match &mut out {
+                    Object3D::Sphere { material: m, .. }
+                    | Object3D::XYRect { material: m, .. }
+                    | Object3D::XZRect { material: m, .. }
+                    | Object3D::YZRect { material: m, .. }
+                    | Object3D::Quad { material: m, .. }
+                    | Object3D::Triangle { material: m, .. }
+                    | Object3D::Mesh { material: m, .. }
+                    | Object3D::ConstantMedium { material: m, .. }
+                    | Object3D::HeterogeneousMedium { material: m, .. }
+                    | Object3D::Plane { material: m, .. }
+                    | Object3D::Disk { material: m, .. }
+                    | Object3D::Cylinder { material: m, .. }
+                    | Object3D::Cone { material: m, .. }
+                    | Object3D::Torus { material: m, .. }
+                    | Object3D::Sdf { material: m, .. } => *m = material,
+                    Object3D::Brick { .. }
+                    | Object3D::OrientedBrick { .. }
+                    | Object3D::Instance { .. }
+                    | Object3D::Csg { .. } => unreachable!(),
+                }
+                out
+            }
         }
     }
 
@@ -157,7 +601,60 @@ impl Object3D {
         }
     }
 
+    pub fn oriented_brick(
+        pos: Vec3D<f32>,
+        size: Vec3D<f32>,
+        rotation: Vec3D<f32>,
+        material: Arc<Material>,
+    ) -> Object3D {
+        let orientation = Transform::new(Vec3D::new(0.0, 0.0, 0.0), rotation, Vec3D::new(1.0, 1.0, 1.0));
+        let (x, y, z) = (
+            orientation.to_world_vector(Vec3D::new(size.x, 0.0, 0.0)),
+            orientation.to_world_vector(Vec3D::new(0.0, size.y, 0.0)),
+            orientation.to_world_vector(Vec3D::new(0.0, 0.0, size.z)),
+        );
+        let quad = |pos: Vec3D<f32>, u: Vec3D<f32>, v: Vec3D<f32>| Object3D::Quad {
+            pos,
+            u,
+            v,
+            material: material.clone(),
+        };
+
+        Object3D::OrientedBrick {
+            pos,
+            size,
+            rotation,
+            sides: ObjectList::new(vec![
+                quad(pos, x, y),
+                quad(pos + z, x, y),
+                quad(pos, y, z),
+                quad(pos + x, y, z),
+                quad(pos, x, z),
+                quad(pos + y, x, z),
+            ]),
+            material,
+        }
+    }
+
+    // Hits on transparent parts of cutout materials are skipped, the ray goes on behind them
     pub fn hit(&self, ray: &Ray, dist_min: Option<f32>, dist_max: Option<f32>) -> Option<RayHit> {
+        let mut dist_min = dist_min;
+        loop {
+            let record = self.surface_hit(ray, dist_min, dist_max)?;
+            if !record.material.transparent_at(&record) {
+                return Some(record);
+            }
+
+            dist_min = Some(ray.dist_to(record.pos) + 0.0001);
+        }
+    }
+
+    fn surface_hit(
+        &self,
+        ray: &Ray,
+        dist_min: Option<f32>,
+        dist_max: Option<f32>,
+    ) -> Option<RayHit> {
         const DIST_MIN_DEFAULT: f32 = 0.0;
         const DIST_MAX_DEFAULT: f32 = f32::INFINITY;
 
@@ -192,15 +689,11 @@ impl Object3D {
                         let ray_hit_pos = ray.at(root);
 
                         let d = (ray_hit_pos - *pos).unit_vec();
-                        let (u, v) = if let Some(texture) = material.texture() {
-                            if !matches!(texture, Texture::SolidColor { .. }) {
-                                (
-                                    0.5 + (((d.x).atan2(d.z)) / (2.0 * std::f32::consts::PI)),
-                                    0.5 + (((d.y).asin()) / (std::f32::consts::PI)),
-                                )
-                            } else {
-                                (0.0, 0.0)
-                            }
+                        let (u, v) = if material.uses_uv() {
+                            (
+                                0.5 + (((d.x).atan2(d.z)) / (2.0 * std::f32::consts::PI)),
+                                0.5 + (((d.y).asin()) / (std::f32::consts::PI)),
+                            )
                         } else {
                             (0.0, 0.0)
                         };
@@ -215,6 +708,7 @@ impl Object3D {
                             v,
                             material.clone(),
                         )
+                        .with_tangents(sphere_tangents(d))
                     })
                 } else {
                     None
@@ -243,7 +737,8 @@ impl Object3D {
                             (x - pos.x) / (corner.x - pos.x),
                             (y - pos.y) / (corner.y - pos.y),
                             material.clone(),
-                        ))
+                        )
+                        .with_tangents((Vec3D::new(1.0, 0.0, 0.0), Vec3D::new(0.0, 1.0, 0.0))))
                     } else {
                         None
                     }
@@ -274,7 +769,8 @@ impl Object3D {
                             (x - pos.x) / (corner.x - pos.x),
                             (z - pos.y) / (corner.y - pos.y),
                             material.clone(),
-                        ))
+                        )
+                        .with_tangents((Vec3D::new(1.0, 0.0, 0.0), Vec3D::new(0.0, 0.0, 1.0))))
                     } else {
                         None
                     }
@@ -305,7 +801,8 @@ impl Object3D {
                             (y - pos.x) / (corner.x - pos.x),
                             (z - pos.y) / (corner.y - pos.y),
                             material.clone(),
-                        ))
+                        )
+                        .with_tangents((Vec3D::new(0.0, 1.0, 0.0), Vec3D::new(0.0, 0.0, 1.0))))
                     } else {
                         None
                     }
@@ -313,7 +810,270 @@ impl Object3D {
                     None
                 }
             }
-            Object3D::Brick { sides, .. } => sides.hit(ray, dist_min, dist_max),
+            Object3D::Quad {
+                pos,
+                u,
+                v,
+                material,
+            } => {
+                let (dist, alpha, beta) = intersect_quad(
+                    *pos,
+                    *u,
+                    *v,
+                    ray,
+                    dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                    dist_max.unwrap_or(DIST_MAX_DEFAULT),
+                )?;
+                let outwards_normal = u.cross(v).unit_vec();
+
+                Some(
+                    RayHit::new(
+                        ray.at(dist),
+                        outwards_normal,
+                        ray.dir.dot(&outwards_normal) < 0.0,
+                        alpha,
+                        beta,
+                        material.clone(),
+                    )
+                    .with_tangents((*u, *v)),
+                )
+            }
+            Object3D::Brick { sides, .. } | Object3D::OrientedBrick { sides, .. } => {
+                sides.hit_closest(ray, dist_min, dist_max)
+            }
+            Object3D::Triangle {
+                vertices,
+                normals,
+                uvs,
+                material,
+            } => {
+                let (dist, b1, b2) = intersect_triangle(
+                    vertices,
+                    ray,
+                    dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                    dist_max.unwrap_or(DIST_MAX_DEFAULT),
+                )?;
+                let (geometric_normal, normal, uv) =
+                    triangle_shading(vertices, normals.as_ref(), uvs.as_ref(), b1, b2);
+
+                Some(
+                    RayHit::new(
+                        ray.at(dist),
+                        normal,
+                        ray.dir.dot(&geometric_normal) < 0.0,
+                        uv.x,
+                        uv.y,
+                        material.clone(),
+                    )
+                    .with_tangents(triangle_tangents(vertices, uvs.as_ref())),
+                )
+            }
+            Object3D::Mesh {
+                pos,
+                mesh,
+                material,
+            } => {
+                // Meshes are hit in their own space, so they never need to be copied
+                let (dist, triangle, b1, b2) = mesh.hit(
+                    &Ray::new(ray.from - *pos, ray.dir),
+                    dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                    dist_max.unwrap_or(DIST_MAX_DEFAULT),
+                )?;
+                let (geometric_normal, normal, uv) = mesh.shading(triangle, b1, b2);
+
+                Some(
+                    RayHit::new(
+                        ray.at(dist),
+                        normal,
+                        ray.dir.dot(&geometric_normal) < 0.0,
+                        uv.x,
+                        uv.y,
+                        material.clone(),
+                    )
+                    .with_vertex_color(mesh.vertex_color(triangle, b1, b2))
+                    .with_tangents(mesh.tangents(triangle)),
+                )
+            }
+            Object3D::ConstantMedium {
+                boundary,
+                density,
+                material,
+            } => {
+                let (enter, exit) = medium_interval(
+                    boundary,
+                    ray,
+                    dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                    dist_max.unwrap_or(DIST_MAX_DEFAULT),
+                )?;
+
+                let ray_length = ray.dir.length();
+                let hit_distance = -thread_rng().gen::<f32>().ln() / density;
+                if hit_distance > (exit - enter) * ray_length {
+                    return None;
+                }
+
+                Some(medium_hit(
+                    ray.at(enter + hit_distance / ray_length),
+                    material,
+                ))
+            }
+            Object3D::HeterogeneousMedium {
+                boundary,
+                density,
+                max_density,
+                material,
+            } => {
+                if *max_density <= 0.0 {
+                    return None;
+                }
+                let (enter, exit) = medium_interval(
+                    boundary,
+                    ray,
+                    dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                    dist_max.unwrap_or(DIST_MAX_DEFAULT),
+                )?;
+
+                // Delta tracking - steps through the medium as if it had max_density everywhere,
+                // then keeps each collision with the chance of the real density at that point
+                let step = max_density * ray.dir.length();
+                let mut rng = thread_rng();
+                let mut dist = enter;
+                loop {
+                    dist -= (1.0 - rng.gen::<f32>()).ln() / step;
+                    if dist >= exit {
+                        return None;
+                    }
+
+                    let pos = ray.at(dist);
+                    if rng.gen::<f32>() < density.density(pos) {
+                        return Some(medium_hit(pos, material));
+                    }
+                }
+            }
+            Object3D::Instance { objects, transform } => {
+                let record = objects.hit_closest(
+                    &Ray::new(
+                        transform.to_object_point(ray.from),
+                        transform.to_object_vector(ray.dir),
+                    ),
+                    dist_min,
+                    dist_max,
+                )?;
+
+                // The normal already faces the ray, transforming it keeps that
+                Some(RayHit {
+                    pos: transform.to_world_point(record.pos),
+                    normal: transform.to_world_normal(record.normal).unit_vec(),
+                    tangents: record.tangents.map(|(u, v)| {
+                        (transform.to_world_vector(u), transform.to_world_vector(v))
+                    }),
+                    ..record
+                })
+            }
+            Object3D::Csg { .. } => {
+                let (dist_min, dist_max) = (
+                    dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                    dist_max.unwrap_or(DIST_MAX_DEFAULT),
+                );
+
+                self.surface_crossings(ray)
+                    .into_iter()
+                    .find(|(dist, _)| *dist > dist_min && *dist < dist_max)
+                    .map(|(_, record)| record)
+            }
+            Object3D::Plane {
+                transform,
+                material,
+            } => primitive_hit(
+                transform,
+                material,
+                ray,
+                dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                dist_max.unwrap_or(DIST_MAX_DEFAULT),
+                intersect_plane,
+            ),
+            Object3D::Disk {
+                radius,
+                transform,
+                material,
+            } => primitive_hit(
+                transform,
+                material,
+                ray,
+                dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                dist_max.unwrap_or(DIST_MAX_DEFAULT),
+                |from, dir, min, max| intersect_disk(from, dir, *radius, min, max),
+            ),
+            Object3D::Cylinder {
+                radius,
+                height,
+                transform,
+                material,
+            } => primitive_hit(
+                transform,
+                material,
+                ray,
+                dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                dist_max.unwrap_or(DIST_MAX_DEFAULT),
+                |from, dir, min, max| intersect_cylinder(from, dir, *radius, *height, min, max),
+            ),
+            Object3D::Cone {
+                radius,
+                height,
+                transform,
+                material,
+            } => primitive_hit(
+                transform,
+                material,
+                ray,
+                dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                dist_max.unwrap_or(DIST_MAX_DEFAULT),
+                |from, dir, min, max| intersect_cone(from, dir, *radius, *height, min, max),
+            ),
+            Object3D::Torus {
+                major_radius,
+                minor_radius,
+                transform,
+                material,
+            } => primitive_hit(
+                transform,
+                material,
+                ray,
+                dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                dist_max.unwrap_or(DIST_MAX_DEFAULT),
+                |from, dir, min, max| {
+                    intersect_torus(from, dir, *major_radius, *minor_radius, min, max)
+                },
+            ),
+            Object3D::Sdf {
+                pos,
+                sdf,
+                bound,
+                material,
+            } => {
+                let from = ray.from - *pos;
+                let dist = sdf.trace(
+                    from,
+                    ray.dir,
+                    *bound,
+                    dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                    dist_max.unwrap_or(DIST_MAX_DEFAULT),
+                )?;
+                let normal = sdf.normal(from + ray.dir * dist);
+
+                // Mapped like a sphere by the direction of the normal
+                Some(
+                    RayHit::new(
+                        ray.at(dist),
+                        normal,
+                        ray.dir.dot(&normal) < 0.0,
+                        0.5 + normal.x.atan2(normal.z) / (2.0 * std::f32::consts::PI),
+                        0.5 + normal.y.clamp(-1.0, 1.0).asin() / std::f32::consts::PI,
+                        material.clone(),
+                    )
+                    .with_tangents(sphere_tangents(normal)),
+                )
+            }
         }
     }
 
@@ -406,6 +1166,59 @@ impl Object3D {
                     None
                 }
             }
+            Object3D::Quad { pos, u, v, .. } => intersect_quad(
+                *pos,
+                *u,
+                *v,
+                ray,
+                dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                dist_max.unwrap_or(DIST_MAX_DEFAULT),
+            )
+            .map(|_| self),
+            Object3D::OrientedBrick { sides, .. } => sides
+                .hit_closest(ray, dist_min, dist_max)
+                .map(|_| self),
+            Object3D::Triangle { vertices, .. } => intersect_triangle(
+                vertices,
+                ray,
+                dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                dist_max.unwrap_or(DIST_MAX_DEFAULT),
+            )
+            .map(|_| self),
+            Object3D::Mesh { pos, mesh, .. } => mesh
+                .hit(
+                    &Ray::new(ray.from - *pos, ray.dir),
+                    dist_min.unwrap_or(DIST_MIN_DEFAULT),
+                    dist_max.unwrap_or(DIST_MAX_DEFAULT),
+                )
+                .map(|_| self),
+            Object3D::ConstantMedium { boundary, .. }
+            | Object3D::HeterogeneousMedium { boundary, .. } => boundary
+                .hit(ray, dist_min, dist_max)
+                .map(|_| self),
+            Object3D::Instance { objects, transform } => objects
+                .hit_closest(
+                    &Ray::new(
+                        transform.to_object_point(ray.from),
+                        transform.to_object_vector(ray.dir),
+                    ),
+                    dist_min,
+                    dist_max,
+                )
+                .map(|_| self),
+            Object3D::Csg { .. }
+            | Object3D::Plane { .. }
+            | Object3D::Disk { .. }
+            | Object3D::Cylinder { .. }
+            | Object3D::Cone { .. }
+            | Object3D::Torus { .. }
+            | Object3D::Sdf { .. } => {
+                if self.hit(ray, dist_min, dist_max).is_some() {
+                    Some(self)
+                } else {
+                    None
+                }
+            }
         }
     }
 
@@ -439,10 +1252,259 @@ impl Object3D {
                     + (pos.y - cam.pos.y).powf(2.0)
                     + (pos.z - cam.pos.z).powf(2.0)
             }
+            Object3D::Quad { pos, u, v, .. } => {
+                let pos = *pos + (*u + *v) / 2.0;
+                (pos.x - cam.pos.x).powf(2.0)
+                    + (pos.y - cam.pos.y).powf(2.0)
+                    + (pos.z - cam.pos.z).powf(2.0)
+            }
+            Object3D::OrientedBrick {
+                pos,
+                size,
+                rotation,
+                ..
+            } => {
+                let pos = *pos
+                    + Transform::new(Vec3D::new(0.0, 0.0, 0.0), *rotation, Vec3D::new(1.0, 1.0, 1.0))
+                        .to_world_vector(*size)
+                        / 2.0;
+                (pos.x - cam.pos.x).powf(2.0)
+                    + (pos.y - cam.pos.y).powf(2.0)
+                    + (pos.z - cam.pos.z).powf(2.0)
+            }
+            Object3D::Triangle { vertices, .. } => {
+                let pos = (vertices[0] + vertices[1] + vertices[2]) / 3.0;
+                (pos.x - cam.pos.x).powf(2.0)
+                    + (pos.y - cam.pos.y).powf(2.0)
+                    + (pos.z - cam.pos.z).powf(2.0)
+            }
+            Object3D::Mesh { pos, mesh, .. } => {
+                let (min, max) = mesh.bounds();
+                let pos = *pos + (min + max) / 2.0;
+                (pos.x - cam.pos.x).powf(2.0)
+                    + (pos.y - cam.pos.y).powf(2.0)
+                    + (pos.z - cam.pos.z).powf(2.0)
+            }
+            Object3D::ConstantMedium { boundary, .. }
+            | Object3D::HeterogeneousMedium { boundary, .. } => boundary.distance_from_camera(cam),
+            Object3D::Csg { left, .. } => left.distance_from_camera(cam),
+            // Endless, so it's checked after everything else
+            Object3D::Plane { .. } => f32::INFINITY,
+            Object3D::Disk { transform, .. }
+            | Object3D::Cylinder { transform, .. }
+            | Object3D::Cone { transform, .. }
+            | Object3D::Torus { transform, .. } => {
+                let pos = transform.translation;
+                (pos.x - cam.pos.x).powf(2.0)
+                    + (pos.y - cam.pos.y).powf(2.0)
+                    + (pos.z - cam.pos.z).powf(2.0)
+            }
+            Object3D::Sdf { pos, .. } => {
+                (pos.x - cam.pos.x).powf(2.0)
+                    + (pos.y - cam.pos.y).powf(2.0)
+                    + (pos.z - cam.pos.z).powf(2.0)
+            }
+            Object3D::Instance { transform, .. } => {
+                let pos = transform.translation;
+                (pos.x - cam.pos.x).powf(2.0)
+                    + (pos.y - cam.pos.y).powf(2.0)
+                    + (pos.z - cam.pos.z).powf(2.0)
+            }
+        }
+    }
+
+    // Every point where the ray goes in or out of the object, sorted by distance. Coming from
+    // outside, every other crossing enters it - front_face is set by that rather than the normals,
+    // which aren't facing out for brick sides. Only closed objects have pairs of crossings
+    fn surface_crossings(&self, ray: &Ray) -> Vec<(f32, RayHit)> {
+        const MAX_CROSSINGS: usize = 64;
+
+        match self {
+            Object3D::Csg {
+                operation,
+                left,
+                right,
+            } => {
+                let mut crossings: Vec<(f32, RayHit, bool)> = left
+                    .surface_crossings(ray)
+                    .into_iter()
+                    .map(|(dist, record)| (dist, record, true))
+                    .chain(
+                        right
+                            .surface_crossings(ray)
+                            .into_iter()
+                            .map(|(dist, record)| (dist, record, false)),
+                    )
+                    .collect();
+                crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+                // Only crossings that change whether the point is inside of the result are kept
+                let (mut in_left, mut in_right) = (false, false);
+                let mut out = vec![];
+                for (dist, mut record, from_left) in crossings {
+                    let was_inside = operation.contains(in_left, in_right);
+                    if from_left {
+                        in_left = !in_left;
+                    } else {
+                        in_right = !in_right;
+                    }
+
+                    let inside = operation.contains(in_left, in_right);
+                    if inside != was_inside {
+                        record.front_face = inside;
+                        out.push((dist, record));
+                    }
+                }
+                out
+            }
+            _ => {
+                let mut crossings = vec![];
+                let mut dist_min = f32::NEG_INFINITY;
+                while crossings.len() < MAX_CROSSINGS {
+                    match self.hit(ray, Some(dist_min), None) {
+                        Some(mut record) => {
+                            let dist = ray.dist_to(record.pos);
+                            record.front_face = crossings.len() % 2 == 0;
+                            crossings.push((dist, record));
+                            dist_min = dist + 0.0001;
+                        }
+                        None => break,
+                    }
+                }
+
+                // An unpaired crossing means the object isn't closed (or the ray grazed an edge)
+                if crossings.len() % 2 == 1 {
+                    crossings.pop();
+                }
+                crossings
+            }
+        }
+    }
+}
+
+// Returns the distance and the position of the hit along u and v (0 to 1)
+fn intersect_quad(
+    pos: Vec3D<f32>,
+    u: Vec3D<f32>,
+    v: Vec3D<f32>,
+    ray: &Ray,
+    dist_min: f32,
+    dist_max: f32,
+) -> Option<(f32, f32, f32)> {
+    let normal = u.cross(&v);
+    let denominator = normal.dot(&ray.dir);
+    if denominator.abs() < 1e-8 {
+        return None;
+    }
+
+    let dist = normal.dot(&(pos - ray.from)) / denominator;
+    if dist <= dist_min || dist >= dist_max {
+        return None;
+    }
+
+    let planar = ray.at(dist) - pos;
+    let w = normal / normal.length_squared();
+    let (alpha, beta) = (w.dot(&planar.cross(&v)), w.dot(&u.cross(&planar)));
+
+    if (0.0..=1.0).contains(&alpha) && (0.0..=1.0).contains(&beta) {
+        Some((dist, alpha, beta))
+    } else {
+        None
+    }
+}
+
+// Moves the ray into the primitive's space and the hit back out of it
+fn primitive_hit(
+    transform: &Transform,
+    material: &Arc<Material>,
+    ray: &Ray,
+    dist_min: f32,
+    dist_max: f32,
+    intersect: impl Fn(Vec3D<f32>, Vec3D<f32>, f32, f32) -> Option<LocalHit>,
+) -> Option<RayHit> {
+    let hit = intersect(
+        transform.to_object_point(ray.from),
+        transform.to_object_vector(ray.dir),
+        dist_min,
+        dist_max,
+    )?;
+    let normal = transform.to_world_normal(hit.normal).unit_vec();
+
+    Some(RayHit::new(
+        ray.at(hit.dist),
+        normal,
+        ray.dir.dot(&normal) < 0.0,
+        hit.u,
+        hit.v,
+        material.clone(),
+    ))
+}
+
+// Directions u and v of the sphere mapping grow in at the unit direction d from the center - around
+// the y axis and towards the top
+fn sphere_tangents(d: Vec3D<f32>) -> (Vec3D<f32>, Vec3D<f32>) {
+    (
+        Vec3D::new(d.z, 0.0, -d.x),
+        Vec3D::new(-d.x * d.y, d.x * d.x + d.z * d.z, -d.y * d.z),
+    )
+}
+
+// Where the ray enters and leaves the boundary of a medium, limited to dist_min..dist_max
+fn medium_interval(
+    boundary: &Object3D,
+    ray: &Ray,
+    dist_min: f32,
+    dist_max: f32,
+) -> Option<(f32, f32)> {
+    let enter = ray.dist_to(boundary.hit(ray, Some(f32::NEG_INFINITY), None)?.pos);
+    let exit = ray.dist_to(boundary.hit(ray, Some(enter + 0.0001), None)?.pos);
+
+    let (enter, exit) = (enter.max(dist_min).max(0.0), exit.min(dist_max));
+    if enter < exit {
+        Some((enter, exit))
+    } else {
+        None
+    }
+}
+
+// How far apart in uv the ray and its differential hit the object. Hits on different surfaces
+// can't be compared, their footprint stays unknown
+fn uv_footprint(
+    object: &Object3D,
+    ray: &Ray,
+    record: &RayHit,
+    dist_min: Option<f32>,
+    dist_max: Option<f32>,
+) -> f32 {
+    let Some((from, dir)) = ray.differential else {
+        return 0.0;
+    };
+
+    match object.hit(&Ray::new(from, dir), dist_min, dist_max) {
+        Some(neighbour) if Arc::ptr_eq(&neighbour.material, &record.material) => {
+            // Uvs wrap around, the shorter way is taken
+            let delta = |a: f32, b: f32| {
+                let delta = (a - b).abs().fract();
+                delta.min(1.0 - delta)
+            };
+            delta(record.u, neighbour.u).max(delta(record.v, neighbour.v))
         }
+        _ => 0.0,
     }
 }
 
+// Isotropic materials scatter the same way everywhere, the normal is arbitrary
+fn medium_hit(pos: Vec3D<f32>, material: &Arc<Material>) -> RayHit {
+    RayHit::new(
+        pos,
+        Vec3D::new(1.0, 0.0, 0.0),
+        true,
+        0.0,
+        0.0,
+        material.clone(),
+    )
+}
+
 // --------------------------------------------------
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -500,12 +1562,35 @@ impl ObjectList {
     pub fn hit(&self, ray: &Ray, dist_min: Option<f32>, dist_max: Option<f32>) -> Option<RayHit> {
         for object in &self.objects {
             if let Some(record) = object.hit(ray, dist_min, dist_max) {
-                return Some(record);
+                return Some(RayHit {
+                    uv_footprint: uv_footprint(object, ray, &record, dist_min, dist_max),
+                    ..record
+                });
             }
         }
         None
     }
 
+    // Unlike `hit`, which trusts the camera sorting, this checks every object and keeps the
+    // nearest hit. Distances are measured in multiples of the ray direction
+    pub fn hit_closest(
+        &self,
+        ray: &Ray,
+        dist_min: Option<f32>,
+        dist_max: Option<f32>,
+    ) -> Option<RayHit> {
+        let mut closest: Option<(f32, RayHit)> = None;
+
+        for object in &self.objects {
+            let dist_max = closest.as_ref().map_or(dist_max, |(dist, _)| Some(*dist));
+
+            if let Some(record) = object.hit(ray, dist_min, dist_max) {
+                closest = Some((ray.dist_to(record.pos), record));
+            }
+        }
+        closest.map(|(_, record)| record)
+    }
+
     pub fn hit_object3d(
         &mut self,
         ray: &Ray,
@@ -544,3 +1629,398 @@ impl ObjectList {
     //     out
     // }
 }
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn sphere(x: f32, material: &Arc<Material>) -> Box<Object3D> {
+        Box::new(Object3D::Sphere {
+            pos: Vec3D::new(x, 0.0, 0.0),
+            radius: 1.0,
+            material: material.clone(),
+        })
+    }
+
+    // Spheres around x = 0 and x = 1, so they overlap from x = 0 to 1
+    fn csg(operation: CsgOperation) -> (Object3D, Arc<Material>, Arc<Material>) {
+        let (left, right) = (
+            Arc::new(Material::default()),
+            Arc::new(Material::light(Vec3D::new(1.0, 1.0, 1.0))),
+        );
+        let object = Object3D::Csg {
+            operation,
+            left: sphere(0.0, &left),
+            right: sphere(1.0, &right),
+        };
+        (object, left, right)
+    }
+
+    const INF: f32 = f32::INFINITY;
+
+    // Along the x axis, starting at x = -5
+    const RAY: Ray = Ray::new(Vec3D::new(-5.0, 0.0, 0.0), Vec3D::new(1.0, 0.0, 0.0));
+
+    fn crossings(object: &Object3D, ray: &Ray) -> Vec<(f32, bool)> {
+        object
+            .surface_crossings(ray)
+            .into_iter()
+            .map(|(dist, record)| ((dist * 1000.0).round() / 1000.0, record.front_face))
+            .collect()
+    }
+
+    #[test]
+    fn crossings_of_every_operation() {
+        let expected = [
+            (CsgOperation::Union, [4.0, 7.0]),
+            (CsgOperation::Intersection, [5.0, 6.0]),
+            (CsgOperation::Difference, [4.0, 5.0]),
+        ];
+
+        for (operation, [enter, exit]) in expected {
+            let (object, _, _) = csg(operation);
+            assert_eq!(
+                crossings(&object, &RAY),
+                vec![(enter, true), (exit, false)],
+                "{operation:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn hits_come_from_the_surface_they_are_on() {
+        let (object, left, right) = csg(CsgOperation::Difference);
+
+        let record = object.hit(&RAY, None, None).unwrap();
+        assert!((record.pos.x + 1.0).abs() < 1e-4);
+        assert!(Arc::ptr_eq(&record.material, &left));
+
+        // The cut out part shows the right sphere's surface, from its inside
+        let record = object.hit(&RAY, Some(4.5), None).unwrap();
+        assert!(record.pos.x.abs() < 1e-4);
+        assert!(Arc::ptr_eq(&record.material, &right));
+        assert!(!record.front_face);
+
+        // Rays starting inside only leave the object
+        let inside = Ray::new(Vec3D::new(-0.5, 0.0, 0.0), RAY.dir);
+        assert!(object.hit(&inside, None, None).unwrap().pos.x.abs() < 1e-4);
+        assert!(object.hit(&RAY, Some(5.5), None).is_none());
+    }
+
+    #[test]
+    fn nested_csg_objects() {
+        let (union, _, material) = csg(CsgOperation::Union);
+        // Cuts the middle out of the union
+        let object = Object3D::Csg {
+            operation: CsgOperation::Difference,
+            left: Box::new(union),
+            right: Box::new(Object3D::Sphere {
+                pos: Vec3D::new(0.5, 0.0, 0.0),
+                radius: 0.25,
+                material,
+            }),
+        };
+
+        assert_eq!(
+            crossings(&object, &RAY),
+            vec![(4.0, true), (5.25, false), (5.75, true), (7.0, false)]
+        );
+        // Missing rays cross nothing
+        let miss = Ray::new(Vec3D::new(-5.0, 3.0, 0.0), RAY.dir);
+        assert!(crossings(&object, &miss).is_empty());
+    }
+
+    #[test]
+    fn instance_hits_match_the_moved_object() {
+        let material = Arc::new(Material::default());
+        let instance = Object3D::Instance {
+            objects: Arc::new(ObjectList::new(vec![*sphere(0.0, &material)])),
+            transform: Transform::new(
+                Vec3D::new(1.0, 2.0, 3.0),
+                Vec3D::new(30.0, 40.0, 50.0),
+                Vec3D::new(2.0, 2.0, 2.0),
+            ),
+        };
+        let moved = Object3D::Sphere {
+            pos: Vec3D::new(1.0, 2.0, 3.0),
+            radius: 2.0,
+            material,
+        };
+
+        let mut rng = StdRng::seed_from_u64(35);
+        let mut random_point = |center: Vec3D<f32>, size: f32| {
+            center
+                + Vec3D::new(
+                    rng.gen_range(-size..size),
+                    rng.gen_range(-size..size),
+                    rng.gen_range(-size..size),
+                )
+        };
+        let mut hits = 0;
+        for _ in 0..200 {
+            let from = random_point(Vec3D::new(0.0, 0.0, 0.0), 8.0);
+            let ray = Ray::new(from, random_point(Vec3D::new(1.0, 2.0, 3.0), 3.0) - from);
+
+            match (instance.hit(&ray, None, None), moved.hit(&ray, None, None)) {
+                (Some(a), Some(b)) => {
+                    assert!((a.pos - b.pos).length() < 1e-3);
+                    assert!((a.normal - b.normal).length() < 1e-3);
+                    assert_eq!(a.front_face, b.front_face);
+                    hits += 1;
+                }
+                (a, b) => assert_eq!(a.is_some(), b.is_some()),
+            }
+        }
+        assert!(hits > 50);
+    }
+
+    fn medium(density: f32) -> Object3D {
+        Object3D::ConstantMedium {
+            boundary: sphere(0.0, &Arc::new(Material::default())),
+            density,
+            material: Arc::new(Material::default()),
+        }
+    }
+
+    #[test]
+    fn medium_interval_is_the_part_inside_the_boundary() {
+        let boundary = sphere(0.0, &Arc::new(Material::default()));
+        let interval = |from: Vec3D<f32>, dir: Vec3D<f32>, dist_max: f32| {
+            medium_interval(&boundary, &Ray::new(from, dir), 0.001, dist_max).map(
+                |(enter, exit)| {
+                    (
+                        (enter * 1000.0).round() / 1000.0,
+                        (exit * 1000.0).round() / 1000.0,
+                    )
+                },
+            )
+        };
+        let dir = Vec3D::new(1.0, 0.0, 0.0);
+
+        assert_eq!(
+            interval(Vec3D::new(-5.0, 0.0, 0.0), dir, INF),
+            Some((4.0, 6.0))
+        );
+        assert_eq!(
+            interval(Vec3D::new(-5.0, 0.0, 0.0), dir * 2.0, INF),
+            Some((2.0, 3.0))
+        );
+        // Starting inside, the interval starts at dist_min
+        assert_eq!(
+            interval(Vec3D::new(0.0, 0.0, 0.0), dir, INF),
+            Some((0.001, 1.0))
+        );
+        assert_eq!(
+            interval(Vec3D::new(0.0, 0.0, 0.0), dir, 0.5),
+            Some((0.001, 0.5))
+        );
+        // Missing, behind the ray and too far away
+        assert_eq!(interval(Vec3D::new(-5.0, 2.0, 0.0), dir, INF), None);
+        assert_eq!(interval(Vec3D::new(5.0, 0.0, 0.0), dir, INF), None);
+        assert_eq!(interval(Vec3D::new(-5.0, 0.0, 0.0), dir, 3.0), None);
+
+        // Sdf boundaries without a bound work from inside too
+        let sdf = Object3D::Sdf {
+            pos: Vec3D::new(0.0, 0.0, 0.0),
+            sdf: Sdf::Sphere { radius: 1.0 },
+            bound: None,
+            material: Arc::new(Material::default()),
+        };
+        let (enter, exit) =
+            medium_interval(&sdf, &Ray::new(Vec3D::new(0.0, 0.0, 0.0), dir), 0.0, INF).unwrap();
+        assert!(enter.abs() < 1e-3 && (exit - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn dense_media_scatter_inside_the_boundary() {
+        let medium = medium(20.0);
+
+        for from in [Vec3D::new(0.0, 0.0, 0.0), Vec3D::new(-5.0, 0.0, 0.0)] {
+            let ray = Ray::new(from, Vec3D::new(1.0, 0.0, 0.0));
+            for _ in 0..100 {
+                // Rays are almost always scattered before getting through 2 units of it
+                if let Some(record) = medium.hit(&ray, None, None) {
+                    assert!(record.pos.length() <= 1.0 + 1e-4);
+                    assert!(record.pos.x >= from.x.max(-1.0) - 1e-4);
+                }
+            }
+            let hits = (0..100)
+                .filter(|_| medium.hit(&ray, None, None).is_some())
+                .count();
+            assert!(hits > 90);
+        }
+    }
+
+    #[test]
+    fn empty_media_let_rays_through() {
+        let empty = medium(0.0);
+
+        for from in [Vec3D::new(0.0, 0.0, 0.0), Vec3D::new(-5.0, 0.0, 0.0)] {
+            let ray = Ray::new(from, Vec3D::new(1.0, 0.0, 0.0));
+            assert!((0..100).all(|_| empty.hit(&ray, None, None).is_none()));
+        }
+        // Missing rays never scatter, however dense the medium is
+        let miss = Ray::new(Vec3D::new(-5.0, 2.0, 0.0), Vec3D::new(1.0, 0.0, 0.0));
+        assert!((0..100).all(|_| medium(1000.0).hit(&miss, None, None).is_none()));
+    }
+
+    fn assert_close(a: Vec3D<f32>, b: Vec3D<f32>) {
+        assert!((a - b).length() < 1e-4, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn quad_intersections() {
+        // A parallelogram leaning along x
+        let (pos, u, v) = (
+            Vec3D::new(0.0, 0.0, 2.0),
+            Vec3D::new(2.0, 0.0, 0.0),
+            Vec3D::new(1.0, 1.0, 0.0),
+        );
+        let intersect = |from: Vec3D<f32>, dir: Vec3D<f32>, dist_max: f32| {
+            intersect_quad(pos, u, v, &Ray::new(from, dir), 0.0, dist_max)
+        };
+        let forward = Vec3D::new(0.0, 0.0, 1.0);
+
+        let (dist, alpha, beta) = intersect(Vec3D::new(1.5, 0.5, 0.0), forward, INF).unwrap();
+        assert!((dist - 2.0).abs() < 1e-5);
+        assert!((alpha - 0.5).abs() < 1e-5 && (beta - 0.5).abs() < 1e-5);
+        let (dist, ..) = intersect(Vec3D::new(1.5, 0.5, 0.0), forward * 4.0, INF).unwrap();
+        assert!((dist - 0.5).abs() < 1e-5);
+
+        // Inside the bounding box but outside of the leaning sides, parallel, behind and too far
+        assert!(intersect(Vec3D::new(0.25, 0.75, 0.0), forward, INF).is_none());
+        assert!(intersect(Vec3D::new(2.9, 0.1, 0.0), forward, INF).is_none());
+        assert!(intersect(Vec3D::new(1.5, 0.5, 0.0), Vec3D::new(1.0, 0.0, 0.0), INF).is_none());
+        assert!(intersect(Vec3D::new(1.5, 0.5, 3.0), forward, INF).is_none());
+        assert!(intersect(Vec3D::new(1.5, 0.5, 0.0), forward, 1.5).is_none());
+    }
+
+    #[test]
+    fn quad_hits_face_the_ray() {
+        let quad = Object3D::Quad {
+            pos: Vec3D::new(0.0, 0.0, 2.0),
+            u: Vec3D::new(2.0, 0.0, 0.0),
+            v: Vec3D::new(0.0, 1.0, 0.0),
+            material: Arc::new(Material::default()),
+        };
+
+        // u x v points along +z
+        let front = quad
+            .hit(
+                &Ray::new(Vec3D::new(0.5, 0.75, 5.0), Vec3D::new(0.0, 0.0, -1.0)),
+                None,
+                None,
+            )
+            .unwrap();
+        assert_close(front.pos, Vec3D::new(0.5, 0.75, 2.0));
+        assert_close(front.normal, Vec3D::new(0.0, 0.0, 1.0));
+        assert!(front.front_face);
+        assert!((front.u - 0.25).abs() < 1e-5 && (front.v - 0.75).abs() < 1e-5);
+        assert_eq!(
+            front.tangents,
+            Some((Vec3D::new(2.0, 0.0, 0.0), Vec3D::new(0.0, 1.0, 0.0)))
+        );
+
+        let back = quad
+            .hit(
+                &Ray::new(Vec3D::new(0.5, 0.75, 0.0), Vec3D::new(0.0, 0.0, 1.0)),
+                None,
+                None,
+            )
+            .unwrap();
+        assert_close(back.normal, Vec3D::new(0.0, 0.0, -1.0));
+        assert!(!back.front_face);
+    }
+
+    #[test]
+    fn rotated_brick_hits() {
+        // Turned a quarter around z, its x side goes along +y from the corner (1, 0, 0). It
+        // fills x 0..1, y 0..2 and z 0..1
+        let brick = Object3D::oriented_brick(
+            Vec3D::new(1.0, 0.0, 0.0),
+            Vec3D::new(2.0, 1.0, 1.0),
+            Vec3D::new(0.0, 0.0, 90.0),
+            Arc::new(Material::default()),
+        );
+        let hit = |from: Vec3D<f32>, dir: Vec3D<f32>| brick.hit(&Ray::new(from, dir), None, None);
+
+        let top = hit(Vec3D::new(0.25, 5.0, 0.75), Vec3D::new(0.0, -1.0, 0.0)).unwrap();
+        assert_close(top.pos, Vec3D::new(0.25, 2.0, 0.75));
+        assert_close(top.normal, Vec3D::new(0.0, 1.0, 0.0));
+        // Along the brick's y side (-x) and z side from the corner at (1, 2, 0)
+        assert!((top.u - 0.75).abs() < 1e-5 && (top.v - 0.75).abs() < 1e-5);
+
+        let side = hit(Vec3D::new(-5.0, 1.5, 0.5), Vec3D::new(1.0, 0.0, 0.0)).unwrap();
+        assert_close(side.pos, Vec3D::new(0.0, 1.5, 0.5));
+        assert_close(side.normal, Vec3D::new(-1.0, 0.0, 0.0));
+        // From inside the normal faces the ray too
+        let inside = hit(Vec3D::new(0.5, 1.0, 0.5), Vec3D::new(0.0, 0.0, 1.0)).unwrap();
+        assert_close(inside.pos, Vec3D::new(0.5, 1.0, 1.0));
+        assert_close(inside.normal, Vec3D::new(0.0, 0.0, -1.0));
+
+        // Where the unrotated brick would be
+        assert!(hit(Vec3D::new(2.0, 0.5, 5.0), Vec3D::new(0.0, 0.0, -1.0)).is_none());
+        assert!(hit(Vec3D::new(0.5, 2.5, 5.0), Vec3D::new(0.0, 0.0, -1.0)).is_none());
+    }
+
+    #[test]
+    fn rays_go_through_cutout_holes() {
+        use crate::graphics::Texture;
+        use crate::image::{ImageData, ImageSource};
+
+        let cutout = |opacity: Texture| {
+            Arc::new(Material::Cutout {
+                material: Box::new(Material::default()),
+                opacity,
+                cutoff: 0.5,
+            })
+        };
+        // Transparent on the left half of the quad, opaque on the right
+        let image = ImageData::new(
+            ImageSource::Named("cutout_hit_test.png".to_string()),
+            2,
+            vec![255; 6],
+        )
+        .with_alpha(vec![0, 255]);
+        let quad = Object3D::Quad {
+            pos: Vec3D::new(0.0, 0.0, 2.0),
+            u: Vec3D::new(2.0, 0.0, 0.0),
+            v: Vec3D::new(0.0, 2.0, 0.0),
+            material: cutout(Texture::Image {
+                data: Arc::new(image),
+                uv: Default::default(),
+            }),
+        };
+        let behind = Object3D::Sphere {
+            pos: Vec3D::new(1.0, 1.0, 6.0),
+            radius: 1.0,
+            material: Arc::new(Material::default()),
+        };
+        let scene = ObjectList::new(vec![quad.clone(), behind]);
+        let forward = Vec3D::new(0.0, 0.0, 1.0);
+
+        let hit = |x: f32| scene.hit(&Ray::new(Vec3D::new(x, 1.0, 0.0), forward), None, None);
+        assert!((hit(0.5).unwrap().pos.z - (6.0 - 0.75_f32.sqrt())).abs() < 1e-3);
+        assert!((hit(1.5).unwrap().pos.z - 2.0).abs() < 1e-3);
+        // Nothing behind the hole
+        assert!(quad
+            .hit(&Ray::new(Vec3D::new(0.5, 1.0, 0.0), forward), None, None)
+            .is_none());
+
+        // Both sides of a fully transparent sphere are skipped
+        let black = Texture::SolidColor {
+            color: crate::draw::Color::new(0, 0, 0),
+        };
+        let hidden = Object3D::Sphere {
+            pos: Vec3D::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: cutout(black),
+        };
+        assert!(hidden.hit(&RAY, None, None).is_none());
+        assert!(hidden.hit(&RAY, Some(0.001), Some(INF)).is_none());
+    }
+}
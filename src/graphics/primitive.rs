@@ -0,0 +1,471 @@
+use std::f32::consts::PI;
+
+use crate::vector::{Vec3D, VectorOperation};
+
+// --------------------------------------------------
+
+// Analytic primitives in their own space - the y axis is up, the base of cylinders and cones and
+// the center of disks and tori is at the origin. Rays aren't normalized, distances are in
+// multiples of their direction
+
+pub struct LocalHit {
+    pub dist: f32,
+    // Facing out of the primitive, not normalized
+    pub normal: Vec3D<f32>,
+    pub u: f32,
+    pub v: f32,
+}
+
+// The closest of the hits that exist
+fn closest(hits: impl IntoIterator<Item = Option<LocalHit>>) -> Option<LocalHit> {
+    hits.into_iter()
+        .flatten()
+        .min_by(|a, b| a.dist.total_cmp(&b.dist))
+}
+
+// Roots of a*t^2 + b*t + c in ascending order
+fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return None;
+        }
+        return Some((-c / b, -c / b));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let sqrt = discriminant.sqrt();
+    let (t0, t1) = ((-b - sqrt) / (2.0 * a), (-b + sqrt) / (2.0 * a));
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+// Around the y axis, 0 to 1
+fn angle_u(x: f32, z: f32) -> f32 {
+    0.5 + x.atan2(z) / (2.0 * PI)
+}
+
+// --------------------------------------------------
+
+// The xz plane, textures repeat every unit
+pub fn intersect_plane(
+    from: Vec3D<f32>,
+    dir: Vec3D<f32>,
+    dist_min: f32,
+    dist_max: f32,
+) -> Option<LocalHit> {
+    if dir.y.abs() < 1e-8 {
+        return None;
+    }
+
+    let dist = -from.y / dir.y;
+    if dist <= dist_min || dist >= dist_max {
+        return None;
+    }
+
+    let p = from + dir * dist;
+    Some(LocalHit {
+        dist,
+        normal: Vec3D::new(0.0, 1.0, 0.0),
+        u: p.x.rem_euclid(1.0),
+        v: p.z.rem_euclid(1.0),
+    })
+}
+
+// A disk in the xz plane at the height y, facing up or down. Textures are stretched over it
+fn intersect_cap(
+    from: Vec3D<f32>,
+    dir: Vec3D<f32>,
+    radius: f32,
+    y: f32,
+    up: bool,
+    dist_min: f32,
+    dist_max: f32,
+) -> Option<LocalHit> {
+    let hit = intersect_plane(from - Vec3D::new(0.0, y, 0.0), dir, dist_min, dist_max)?;
+    let p = from + dir * hit.dist;
+    if p.x * p.x + p.z * p.z > radius * radius {
+        return None;
+    }
+
+    Some(LocalHit {
+        normal: Vec3D::new(0.0, if up { 1.0 } else { -1.0 }, 0.0),
+        u: 0.5 + p.x / (2.0 * radius),
+        v: 0.5 + p.z / (2.0 * radius),
+        ..hit
+    })
+}
+
+pub fn intersect_disk(
+    from: Vec3D<f32>,
+    dir: Vec3D<f32>,
+    radius: f32,
+    dist_min: f32,
+    dist_max: f32,
+) -> Option<LocalHit> {
+    intersect_cap(from, dir, radius, 0.0, true, dist_min, dist_max)
+}
+
+// From y = 0 up to the height, closed by a disk on each end
+pub fn intersect_cylinder(
+    from: Vec3D<f32>,
+    dir: Vec3D<f32>,
+    radius: f32,
+    height: f32,
+    dist_min: f32,
+    dist_max: f32,
+) -> Option<LocalHit> {
+    let side = |dist: f32| {
+        let p = from + dir * dist;
+        if dist <= dist_min || dist >= dist_max || p.y < 0.0 || p.y > height {
+            return None;
+        }
+
+        Some(LocalHit {
+            dist,
+            normal: Vec3D::new(p.x, 0.0, p.z),
+            u: angle_u(p.x, p.z),
+            v: p.y / height,
+        })
+    };
+
+    let sides = solve_quadratic(
+        dir.x * dir.x + dir.z * dir.z,
+        2.0 * (from.x * dir.x + from.z * dir.z),
+        from.x * from.x + from.z * from.z - radius * radius,
+    );
+
+    closest([
+        sides.and_then(|(t0, _)| side(t0)),
+        sides.and_then(|(_, t1)| side(t1)),
+        intersect_cap(from, dir, radius, 0.0, false, dist_min, dist_max),
+        intersect_cap(from, dir, radius, height, true, dist_min, dist_max),
+    ])
+}
+
+// Narrows from the radius at y = 0 to a point at the height, the base is closed by a disk
+pub fn intersect_cone(
+    from: Vec3D<f32>,
+    dir: Vec3D<f32>,
+    radius: f32,
+    height: f32,
+    dist_min: f32,
+    dist_max: f32,
+) -> Option<LocalHit> {
+    // The radius at y is r - k * y
+    let k = radius / height;
+    let r = radius - k * from.y;
+
+    let side = |dist: f32| {
+        let p = from + dir * dist;
+        if dist <= dist_min || dist >= dist_max || p.y < 0.0 || p.y > height {
+            return None;
+        }
+
+        Some(LocalHit {
+            dist,
+            normal: Vec3D::new(p.x, k * (p.x * p.x + p.z * p.z).sqrt(), p.z),
+            u: angle_u(p.x, p.z),
+            v: p.y / height,
+        })
+    };
+
+    let sides = solve_quadratic(
+        dir.x * dir.x + dir.z * dir.z - k * k * dir.y * dir.y,
+        2.0 * (from.x * dir.x + from.z * dir.z + k * dir.y * r),
+        from.x * from.x + from.z * from.z - r * r,
+    );
+
+    closest([
+        sides.and_then(|(t0, _)| side(t0)),
+        sides.and_then(|(_, t1)| side(t1)),
+        intersect_cap(from, dir, radius, 0.0, false, dist_min, dist_max),
+    ])
+}
+
+// A ring around the y axis. The torus is a quartic, so its roots are found by stepping through
+// the bounding sphere and refining every sign change by bisection. Steps go as far as the distance
+// to the tube, which can't skip over it, so only rays grazing it for less than a hundredth of its
+// radius are missed
+pub fn intersect_torus(
+    from: Vec3D<f32>,
+    dir: Vec3D<f32>,
+    major_radius: f32,
+    minor_radius: f32,
+    dist_min: f32,
+    dist_max: f32,
+) -> Option<LocalHit> {
+    const BISECTIONS: usize = 24;
+
+    let (big, small) = (major_radius, minor_radius);
+    let implicit = |dist: f32| {
+        let p = from + dir * dist;
+        let sum = p.length_squared() + big * big - small * small;
+        sum * sum - 4.0 * big * big * (p.x * p.x + p.z * p.z)
+    };
+
+    let (enter, exit) = solve_quadratic(
+        dir.length_squared(),
+        2.0 * from.dot(&dir),
+        from.length_squared() - (big + small) * (big + small),
+    )?;
+    let (start, end) = (enter.max(dist_min), exit.min(dist_max));
+    if start >= end {
+        return None;
+    }
+
+    // Negative inside of the tube
+    let distance = |dist: f32| {
+        let p = from + dir * dist;
+        let ring = (p.x * p.x + p.z * p.z).sqrt() - big;
+        (ring * ring + p.y * p.y).sqrt() - small
+    };
+    let (length, min_step) = (dir.length(), small * 0.01);
+
+    let mut t0 = start;
+    let mut f0 = implicit(t0);
+    while t0 < end {
+        let t1 = (t0 + distance(t0).abs().max(min_step) / length).min(end);
+        let f1 = implicit(t1);
+
+        if f0.signum() != f1.signum() {
+            let (mut low, mut high, mut f_low) = (t0, t1, f0);
+            for _ in 0..BISECTIONS {
+                let mid = (low + high) / 2.0;
+                let f_mid = implicit(mid);
+                if f_mid.signum() == f_low.signum() {
+                    (low, f_low) = (mid, f_mid);
+                } else {
+                    high = mid;
+                }
+            }
+
+            let dist = (low + high) / 2.0;
+            if dist <= dist_min {
+                t0 = t1;
+                f0 = f1;
+                continue;
+            }
+
+            let p = from + dir * dist;
+            let ring = (p.x * p.x + p.z * p.z).sqrt();
+            return Some(LocalHit {
+                dist,
+                normal: p * (p.length_squared() + big * big - small * small)
+                    - Vec3D::new(p.x, 0.0, p.z) * (2.0 * big * big),
+                u: angle_u(p.x, p.z),
+                v: 0.5 + p.y.atan2(ring - big) / (2.0 * PI),
+            });
+        }
+
+        t0 = t1;
+        f0 = f1;
+    }
+
+    None
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const INF: f32 = f32::INFINITY;
+
+    fn assert_close(a: Vec3D<f32>, b: Vec3D<f32>) {
+        assert!((a - b).length() < 1e-3, "{a:?} != {b:?}");
+    }
+
+    fn v(x: f32, y: f32, z: f32) -> Vec3D<f32> {
+        Vec3D::new(x, y, z)
+    }
+
+    // Checks the distance, the normal and that the normal faces away from the given center
+    fn assert_hit(hit: Option<LocalHit>, dist: f32, normal: Vec3D<f32>) -> LocalHit {
+        let hit = hit.expect("Expected a hit");
+        assert!((hit.dist - dist).abs() < 1e-3, "{:} != {dist}", hit.dist);
+        assert_close(hit.normal.unit_vec(), normal);
+        hit
+    }
+
+    #[test]
+    fn plane_hits() {
+        let hit = assert_hit(
+            intersect_plane(v(0.25, 2.0, -3.5), v(0.0, -2.0, 0.0), 0.0, INF),
+            1.0,
+            v(0.0, 1.0, 0.0),
+        );
+        // Textures repeat every unit
+        assert!((hit.u - 0.25).abs() < 1e-5 && (hit.v - 0.5).abs() < 1e-5);
+
+        // Parallel, behind and too far away
+        assert!(intersect_plane(v(0.0, 2.0, 0.0), v(1.0, 0.0, 0.0), 0.0, INF).is_none());
+        assert!(intersect_plane(v(0.0, 2.0, 0.0), v(0.0, 1.0, 0.0), 0.0, INF).is_none());
+        assert!(intersect_plane(v(0.0, 2.0, 0.0), v(0.0, -1.0, 0.0), 0.0, 1.5).is_none());
+    }
+
+    #[test]
+    fn disk_hits() {
+        let hit = assert_hit(
+            intersect_disk(v(0.5, 5.0, -0.5), v(0.0, -1.0, 0.0), 1.0, 0.0, INF),
+            5.0,
+            v(0.0, 1.0, 0.0),
+        );
+        assert!((hit.u - 0.75).abs() < 1e-5 && (hit.v - 0.25).abs() < 1e-5);
+        // Disks only have one side facing up
+        assert_hit(
+            intersect_disk(v(0.0, -1.0, 0.0), v(0.0, 1.0, 0.0), 1.0, 0.0, INF),
+            1.0,
+            v(0.0, 1.0, 0.0),
+        );
+
+        assert!(intersect_disk(v(1.5, 5.0, 0.0), v(0.0, -1.0, 0.0), 1.0, 0.0, INF).is_none());
+    }
+
+    #[test]
+    fn cylinder_hits() {
+        let (radius, height) = (1.0, 2.0);
+        let cylinder = |from, dir| intersect_cylinder(from, dir, radius, height, 0.0, INF);
+
+        let hit = assert_hit(
+            cylinder(v(-5.0, 1.0, 0.0), v(1.0, 0.0, 0.0)),
+            4.0,
+            v(-1.0, 0.0, 0.0),
+        );
+        assert!((hit.u - 0.25).abs() < 1e-5 && (hit.v - 0.5).abs() < 1e-5);
+        // Both caps
+        assert_hit(
+            cylinder(v(0.5, -5.0, 0.0), v(0.0, 1.0, 0.0)),
+            5.0,
+            v(0.0, -1.0, 0.0),
+        );
+        assert_hit(
+            cylinder(v(0.5, 5.0, 0.0), v(0.0, -1.0, 0.0)),
+            3.0,
+            v(0.0, 1.0, 0.0),
+        );
+        // From inside the normal still faces out
+        assert_hit(
+            cylinder(v(0.0, 1.0, 0.0), v(1.0, 0.0, 0.0)),
+            1.0,
+            v(1.0, 0.0, 0.0),
+        );
+        assert_hit(
+            cylinder(v(0.0, 1.0, 0.0), v(0.0, 1.0, 0.0)),
+            1.0,
+            v(0.0, 1.0, 0.0),
+        );
+
+        // Above, next to it and past the end of the side without reaching a cap
+        assert!(cylinder(v(-5.0, 3.0, 0.0), v(1.0, 0.0, 0.0)).is_none());
+        assert!(cylinder(v(-5.0, 1.0, 1.5), v(1.0, 0.0, 0.0)).is_none());
+        assert!(cylinder(v(1.5, -5.0, 0.0), v(0.0, 1.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn cone_hits() {
+        let (radius, height) = (1.0, 2.0);
+        let cone = |from, dir| intersect_cone(from, dir, radius, height, 0.0, INF);
+
+        // Half way up the radius is halved, the normal leans up
+        let normal = v(-2.0, 1.0, 0.0).unit_vec();
+        let hit = assert_hit(cone(v(-5.0, 1.0, 0.0), v(1.0, 0.0, 0.0)), 4.5, normal);
+        assert!((hit.v - 0.5).abs() < 1e-5);
+        assert_hit(
+            cone(v(0.25, 5.0, 0.0), v(0.0, -1.0, 0.0)),
+            3.5,
+            v(2.0, 1.0, 0.0).unit_vec(),
+        );
+        assert_hit(
+            cone(v(0.2, -5.0, 0.0), v(0.0, 1.0, 0.0)),
+            5.0,
+            v(0.0, -1.0, 0.0),
+        );
+
+        // Outside of the narrower part and on the mirrored cone above the tip
+        assert!(cone(v(-5.0, 1.0, 0.6), v(1.0, 0.0, 0.0)).is_none());
+        assert!(cone(v(-5.0, 3.0, 0.0), v(1.0, 0.0, 0.0)).is_none());
+        assert!(cone(v(1.5, -5.0, 0.0), v(0.0, 1.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn torus_hits_the_closest_of_its_roots() {
+        let torus = |from, dir, dist_min| intersect_torus(from, dir, 2.0, 0.5, dist_min, INF);
+        let (from, dir) = (v(-5.0, 0.0, 0.0), v(1.0, 0.0, 0.0));
+
+        // The line through the middle crosses the surface at x = -2.5, -1.5, 1.5 and 2.5
+        assert_hit(torus(from, dir, 0.0), 2.5, v(-1.0, 0.0, 0.0));
+        assert_hit(torus(from, dir, 3.0), 3.5, v(1.0, 0.0, 0.0));
+        assert_hit(torus(from, dir, 4.0), 6.5, v(-1.0, 0.0, 0.0));
+        assert_hit(torus(from, dir, 7.0), 7.5, v(1.0, 0.0, 0.0));
+        assert!(torus(from, dir, 8.0).is_none());
+        // Inside the tube and in the hole
+        assert_hit(torus(v(-2.0, 0.0, 0.0), dir, 0.0), 0.5, v(1.0, 0.0, 0.0));
+        assert_hit(torus(v(0.0, 0.0, 0.0), dir, 0.0), 1.5, v(-1.0, 0.0, 0.0));
+        // Straight down onto the top of the tube
+        let hit = assert_hit(
+            torus(v(0.0, 5.0, 2.0), v(0.0, -1.0, 0.0), 0.0),
+            4.5,
+            v(0.0, 1.0, 0.0),
+        );
+        assert!((hit.v - 0.75).abs() < 1e-5);
+
+        // Through the hole and above it
+        assert!(torus(v(0.0, 5.0, 0.0), v(0.0, -1.0, 0.0), 0.0).is_none());
+        assert!(torus(v(-5.0, 0.6, 0.0), dir, 0.0).is_none());
+    }
+
+    #[test]
+    fn torus_grazing_hits() {
+        let (big, small) = (2.0, 0.5);
+
+        // Just below the top of the tube, the ray is inside of it for only a short distance
+        for y in [0.45, 0.49, 0.499] {
+            let hit = intersect_torus(v(-5.0, y, 0.0), v(1.0, 0.0, 0.0), big, small, 0.0, INF);
+            let expected = 5.0 - big - (small * small - y * y).sqrt();
+            assert!((hit.expect("Missed a grazing hit").dist - expected).abs() < 1e-3);
+        }
+        // A long way off to the side
+        let hit = intersect_torus(v(-50.0, 0.49, 0.0), v(1.0, 0.0, 0.0), big, small, 0.0, INF);
+        assert!(hit.is_some());
+    }
+
+    #[test]
+    fn torus_hit_matches_fine_sampling() {
+        let (big, small) = (1.5, 0.25);
+        let mut rng = StdRng::seed_from_u64(40);
+        let mut random_point = |size: f32| {
+            v(
+                rng.gen_range(-size..size),
+                rng.gen_range(-size..size),
+                rng.gen_range(-size..size),
+            )
+        };
+
+        for _ in 0..200 {
+            let from = random_point(4.0);
+            let dir = random_point(2.0) - from;
+
+            // The first sign change of the implicit function in small steps
+            let implicit = |dist: f32| {
+                let p = from + dir * dist;
+                let sum = p.length_squared() + big * big - small * small;
+                sum * sum - 4.0 * big * big * (p.x * p.x + p.z * p.z)
+            };
+            let brute_force = (1..20000)
+                .map(|i| i as f32 * 0.0001)
+                .find(|dist| implicit(*dist).signum() != implicit(dist - 0.0001).signum());
+
+            let hit = intersect_torus(from, dir, big, small, 0.0, 2.0).map(|hit| hit.dist);
+            match (hit, brute_force) {
+                (Some(hit), Some(expected)) => assert!((hit - expected).abs() < 1e-3),
+                (hit, expected) => assert_eq!(hit.is_some(), expected.is_some()),
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use super::material::Material;
 use super::object::ObjectList;
 use crate::draw::Color;
-use crate::vector::Vec3D;
+use crate::vector::{Vec3D, VectorOperation};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -11,17 +11,35 @@ use std::sync::Arc;
 pub struct Ray {
     pub from: Vec3D<f32>,
     pub dir: Vec3D<f32>,
+    // Origin and direction of the ray through the neighbouring pixel, only camera rays have one.
+    // Textures get filtered by how far apart the two hit
+    #[serde(default)]
+    pub differential: Option<(Vec3D<f32>, Vec3D<f32>)>,
 }
 
 impl Ray {
     pub const fn new(from: Vec3D<f32>, dir: Vec3D<f32>) -> Ray {
-        Ray { from, dir }
+        Ray {
+            from,
+            dir,
+            differential: None,
+        }
+    }
+
+    pub fn with_differential(mut self, neighbour: Ray) -> Ray {
+        self.differential = Some((neighbour.from, neighbour.dir));
+        self
     }
 
     pub fn at(&self, dist: f32) -> Vec3D<f32> {
         self.from + self.dir * dist
     }
 
+    // Inverse of `at` for points on the ray, in multiples of the direction
+    pub fn dist_to(&self, point: Vec3D<f32>) -> f32 {
+        (point - self.from).dot(&self.dir) / self.dir.length_squared()
+    }
+
     pub fn ray_color(
         &self,
         object_list: &ObjectList,
@@ -31,10 +49,11 @@ impl Ray {
         if depth <= 0 {
             Color::new(0, 0, 0)
         } else if let Some(record) = object_list.hit(self, Some(0.001), None) {
-            let color_emmited = record.material.emmited(record.u, record.v, record.pos);
+            let color_emmited = record.material.emmited(&record);
             if let Some((ray, attenuation)) = record.material.scatter(&record, self) {
                 let ray_color = ray.ray_color(object_list, background_color, depth - 1);
-                Color::from_vec3d(
+                // Not clamped until the pixel is done, so bright lights stay bright after bounces
+                Color(
                     color_emmited.0
                         + Vec3D::new(
                             attenuation.0.x * ray_color.0.x,
@@ -56,7 +75,7 @@ impl Ray {
         background_color: Option<Color>,
     ) -> Color {
         if let Some(record) = object_list.hit(self, None, None) {
-            record.material.color(record.u, record.v, record.pos)
+            record.material.color(&record)
         } else {
             background_color.unwrap_or(Ray::background_gradient(&self.dir))
         }
@@ -79,6 +98,13 @@ pub struct RayHit {
     pub u: f32,
     pub v: f32,
     pub material: Arc<Material>,
+    // Interpolated vertex color of meshes that have them
+    pub vertex_color: Option<Color>,
+    // How big the pixel the hit is seen through is in uv units, 0 when it isn't known
+    pub uv_footprint: f32,
+    // Directions u and v grow in along the surface, not normalized. Surface maps bend the normal
+    // along them
+    pub tangents: Option<(Vec3D<f32>, Vec3D<f32>)>,
 }
 
 impl RayHit {
@@ -99,6 +125,46 @@ impl RayHit {
             u,
             v,
             material,
+            vertex_color: None,
+            uv_footprint: 0.0,
+            tangents: None,
+        }
+    }
+
+    pub fn with_vertex_color(mut self, vertex_color: Option<Color>) -> RayHit {
+        self.vertex_color = vertex_color;
+        self
+    }
+
+    pub fn with_tangents(mut self, tangents: (Vec3D<f32>, Vec3D<f32>)) -> RayHit {
+        self.tangents = Some(tangents);
+        self
+    }
+
+    // Unit vectors along u and v, perpendicular to the normal. Without tangents (or with ones
+    // along the normal) they're picked arbitrarily
+    pub fn tangent_frame(&self) -> (Vec3D<f32>, Vec3D<f32>) {
+        let normal = self.normal;
+        let along_surface = |v: Vec3D<f32>| v - normal * v.dot(&normal);
+
+        let tangent = self
+            .tangents
+            .map(|(u, _)| along_surface(u))
+            .filter(|tangent| tangent.length_squared() > 1e-12)
+            .unwrap_or_else(|| {
+                along_surface(if normal.x.abs() < 0.9 {
+                    Vec3D::new(1.0, 0.0, 0.0)
+                } else {
+                    Vec3D::new(0.0, 1.0, 0.0)
+                })
+            })
+            .unit_vec();
+
+        // The normal faces the ray, so v can be on either side of it
+        let bitangent = normal.cross(&tangent);
+        match self.tangents {
+            Some((_, v)) if v.dot(&bitangent) < 0.0 => (tangent, -bitangent),
+            _ => (tangent, bitangent),
         }
     }
 }
@@ -1,6 +1,8 @@
-use super::camera::Camera;
+use super::animation::Animation;
+use super::camera::{Camera, Orientation, Projection};
 use super::object::ObjectList;
 use super::ray::Ray;
+use super::stereo::Stereo;
 use super::Object3D;
 
 use crate::draw::{Color, FrameBuffer};
@@ -17,6 +19,12 @@ use std::sync::{Arc, Mutex};
 pub static RENDER: Mutex<(bool, FrameBuffer)> =
     Mutex::new((false, FrameBuffer::new_dummy(Vec2D::new(0, 0))));
 
+// (file name, png data)
+pub type FrameFile = (String, Vec<u8>);
+
+// Finished image sequence, one file per frame
+pub static SEQUENCE: Mutex<Option<Vec<FrameFile>>> = Mutex::new(None);
+
 // --------------------------------------------------
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -24,27 +32,36 @@ pub struct Scene {
     pub camera: Camera,
     pub object_list: ObjectList,
     pub background_color: Option<Color>,
+    #[serde(default)]
+    pub animation: Animation,
     #[serde(skip)]
     pub size: Vec2D<u32>,
+    // Point of the animation shown, in seconds
+    #[serde(skip)]
+    pub time: f32,
 }
 
 impl Scene {
     pub fn new(
         size: Vec2D<u32>,
         vfov: f32,
+        projection: Projection,
         background_color: Option<Color>,
         object_list: ObjectList,
     ) -> Scene {
         Scene {
             camera: Camera::new(
                 Vec3D::new(0.0, 0.0, 0.0),
-                Vec3D::new(0.0, 0.0, 1.0),
+                Orientation::default(),
                 (size.x as f32) / (size.y as f32),
                 vfov,
+                projection,
             ),
             object_list,
             background_color,
+            animation: Animation::default(),
             size,
+            time: 0.0,
         }
     }
 
@@ -53,9 +70,14 @@ impl Scene {
         self.camera.resize((size.x as f32) / (size.y as f32));
     }
 
+    // Object list with the object tracks of the animation applied at the current time
+    pub fn animated_objects(&self) -> ObjectList {
+        self.animation.objects_at(&self.object_list, self.time)
+    }
+
     pub fn get_pointed_at_object(&mut self) -> Option<&mut Object3D> {
         self.object_list.hit_object3d(
-            &Ray::new(self.camera.pos, self.camera.pos + self.camera.rotation),
+            &Ray::new(self.camera.pos, self.camera.direction()),
             None,
             None,
         )
@@ -63,7 +85,7 @@ impl Scene {
 
     pub fn get_pointed_at_object_index(&mut self) -> Option<usize> {
         if let Some(object) = self.object_list.clone().hit_object3d(
-            &Ray::new(self.camera.pos, self.camera.pos + self.camera.rotation),
+            &Ray::new(self.camera.pos, self.camera.direction()),
             None,
             None,
         ) {
@@ -79,7 +101,13 @@ impl Scene {
         }
     }
 
-    pub fn render_full(&self, rays_per_pixel: u32, diffuse_bounce_depth: i32, all_cores: bool) {
+    pub fn render_full(
+        &self,
+        rays_per_pixel: u32,
+        diffuse_bounce_depth: i32,
+        all_cores: bool,
+        stereo: Stereo,
+    ) {
         web_sys::console::time_with_label("Render start");
 
         let num_of_threads = if all_cores { cpu_cores() } else { 1 };
@@ -88,7 +116,7 @@ impl Scene {
         let working_threads = Arc::new(Mutex::new(num_of_threads));
         let out_color_vec: Arc<Mutex<Vec<Color>>> = Arc::new(Mutex::new(vec![]));
         let objects = Arc::new(
-            self.object_list
+            self.animated_objects()
                 .camera_shifted(&self.camera)
                 .camera_sorted(&self.camera),
         );
@@ -105,6 +133,7 @@ impl Scene {
                     rays_per_pixel_per_thread,
                     diffuse_bounce_depth,
                     &*objects,
+                    &stereo,
                 );
 
                 {
@@ -134,13 +163,119 @@ impl Scene {
         }
     }
 
+    // The scene as it looks in the given frame of its animation
+    pub fn at_frame(&self, frame: u32) -> Scene {
+        let mut out = self.clone();
+        out.time = self.animation.frame_time(frame);
+
+        if let Some(keyframe) = self.animation.camera_at(out.time) {
+            out.camera.pos = keyframe.pos;
+            out.camera.orientation = keyframe.orientation;
+            out.camera.change_vfov(keyframe.vfov);
+        }
+
+        out
+    }
+
+    pub fn frame_file_name(frame: u32) -> String {
+        format!("frame_{frame:05}.png")
+    }
+
+    // Renders the given frames one after another on the calling thread
+    pub fn render_frames(
+        &self,
+        frames: impl Iterator<Item = u32>,
+        rays_per_pixel: u32,
+        diffuse_bounce_depth: i32,
+        stereo: &Stereo,
+    ) -> Vec<(u32, FrameBuffer)> {
+        frames
+            .map(|frame| {
+                log::info!("Rendering frame {frame:?}");
+
+                let scene = self.at_frame(frame);
+                let objects = scene
+                    .animated_objects()
+                    .camera_shifted(&scene.camera)
+                    .camera_sorted(&scene.camera);
+
+                let mut frame_buffer = FrameBuffer::new(scene.size);
+                frame_buffer
+                    .set_buffer(&scene.do_render(
+                        rays_per_pixel,
+                        diffuse_bounce_depth,
+                        &objects,
+                        stereo,
+                    ))
+                    .unwrap();
+
+                (frame, frame_buffer)
+            })
+            .collect()
+    }
+
+    // Frames are split between threads, every thread renders whole frames. Once all are done,
+    // the png encoded frames are put into SEQUENCE and the last frame is shown through RENDER.
+    pub fn render_sequence(&self, rays_per_pixel: u32, diffuse_bounce_depth: i32, stereo: Stereo) {
+        web_sys::console::time_with_label("Render start");
+
+        let frame_count = self.animation.frame_count();
+        let num_of_threads = cpu_cores().min(frame_count);
+
+        let working_threads = Arc::new(Mutex::new(num_of_threads));
+        let out_frames: Arc<Mutex<Vec<(u32, FrameBuffer)>>> = Arc::new(Mutex::new(vec![]));
+
+        let self_clone = Arc::new(self.clone());
+        for thread_index in 0..num_of_threads {
+            let self_clone = Arc::clone(&self_clone);
+            let working_threads_2 = Arc::clone(&working_threads);
+            let out_frames_2 = Arc::clone(&out_frames);
+
+            wasm_thread::spawn(move || {
+                let render_output = self_clone.render_frames(
+                    (thread_index..frame_count).step_by(num_of_threads as usize),
+                    rays_per_pixel,
+                    diffuse_bounce_depth,
+                    &stereo,
+                );
+                out_frames_2.lock().unwrap().extend(render_output);
+
+                {
+                    let mut working_threads = working_threads_2.lock().unwrap();
+
+                    *working_threads -= 1;
+                    if *working_threads == 0 {
+                        let mut out_frames = out_frames_2.lock().unwrap();
+                        out_frames.sort_by_key(|(frame, _)| *frame);
+
+                        *SEQUENCE.lock().unwrap() = Some(
+                            out_frames
+                                .iter()
+                                .map(|(frame, frame_buffer)| {
+                                    (Scene::frame_file_name(*frame), frame_buffer.to_png())
+                                })
+                                .collect(),
+                        );
+                        *RENDER.lock().unwrap() = (
+                            true,
+                            out_frames
+                                .pop()
+                                .map(|(_, frame_buffer)| frame_buffer)
+                                .unwrap(),
+                        );
+                    }
+                }
+            });
+        }
+    }
+
     pub fn render_simple(&self) -> FrameBuffer {
         let mut rng = thread_rng();
 
         // let size = self.size / 2;
         let mut frame_buffer = FrameBuffer::new(self.size);
         let objects_processed = self
-            .object_list
+            .animated_objects()
             .camera_shifted(&self.camera)
             .camera_sorted(&self.camera);
         let size_minus_1 = Vec2D::new((self.size.x - 1) as f32, (self.size.y - 1) as f32);
@@ -151,9 +286,10 @@ impl Scene {
                     .into_iter()
                     .map(|pixel| {
                         self.camera
-                            .get_ray(
+                            .get_pixel_ray(
                                 (pixel.x + rng.gen::<f32>()) / size_minus_1.x,
                                 (pixel.y + rng.gen::<f32>()) / size_minus_1.y,
+                                1.0 / size_minus_1.x,
                             )
                             .ray_color_simple(&objects_processed, self.background_color)
                     })
@@ -169,28 +305,69 @@ impl Scene {
         rays_per_pixel: u32,
         diffuse_bounce_depth: i32,
         object_list: &ObjectList,
+        stereo: &Stereo,
+    ) -> Vec<Color> {
+        if stereo.is_enabled() {
+            let (left_size, right_size) = stereo.eye_sizes(self.size);
+            let (left_camera, right_camera) = stereo.eye_cameras(&self.camera, self.size);
+
+            stereo.combine(
+                &self.do_render_camera(
+                    &left_camera,
+                    left_size,
+                    rays_per_pixel,
+                    diffuse_bounce_depth,
+                    object_list,
+                ),
+                &self.do_render_camera(
+                    &right_camera,
+                    right_size,
+                    rays_per_pixel,
+                    diffuse_bounce_depth,
+                    object_list,
+                ),
+                self.size,
+            )
+        } else {
+            self.do_render_camera(
+                &self.camera,
+                self.size,
+                rays_per_pixel,
+                diffuse_bounce_depth,
+                object_list,
+            )
+        }
+    }
+
+    fn do_render_camera(
+        &self,
+        camera: &Camera,
+        size: Vec2D<u32>,
+        rays_per_pixel: u32,
+        diffuse_bounce_depth: i32,
+        object_list: &ObjectList,
     ) -> Vec<Color> {
         let mut rng = thread_rng();
-        let size_minus_1 = Vec2D::new((self.size.x - 1) as f32, (self.size.y - 1) as f32);
+        let size_minus_1 = Vec2D::new((size.x - 1) as f32, (size.y - 1) as f32);
 
-        Scene::x_y_vec(self.size)
+        Scene::x_y_vec(size)
             .into_iter()
             .map(|pixel| {
                 if pixel.x == 0.0 && pixel.y.rem_euclid(8.0) == 0.0 {
                     log::info!(
                         "{:?} - {:.3} %",
                         wasm_thread::current().id(),
-                        100.0 - ((100.0 / (self.size.y as f32)) * pixel.y)
+                        100.0 - ((100.0 / (size.y as f32)) * pixel.y)
                     )
                 }
 
                 let mut color = Color::new(0, 0, 0);
                 for _ in 0..rays_per_pixel {
-                    color.0 += self
-                        .camera
-                        .get_ray(
+                    color.0 += camera
+                        .get_pixel_ray(
                             (pixel.x + rng.gen::<f32>()) / size_minus_1.x,
                             (pixel.y + rng.gen::<f32>()) / size_minus_1.y,
+                            1.0 / size_minus_1.x,
                         )
                         .ray_color(object_list, self.background_color, diffuse_bounce_depth)
                         .0;
@@ -236,11 +413,14 @@ impl SceneList {
         object_lists: Vec<(ObjectList, Option<Color>)>,
         size: Vec2D<u32>,
         vfov: f32,
+        projection: Projection,
     ) -> SceneList {
         SceneList {
             scenes: object_lists
                 .into_iter()
-                .map(|object_list| Scene::new(size, vfov, object_list.1, object_list.0))
+                .map(|object_list| {
+                    Scene::new(size, vfov, projection, object_list.1, object_list.0)
+                })
                 .collect(),
             current_scene: 0,
         }
@@ -258,6 +438,19 @@ impl SceneList {
             .for_each(|scene| scene.camera.change_vfov(new_vfov));
     }
 
+    pub fn change_projection(&mut self, new_projection: Projection) {
+        self.scenes
+            .iter_mut()
+            .for_each(|scene| scene.camera.change_projection(new_projection));
+    }
+
+    // Time is given as a fraction of each scene's animation duration
+    pub fn change_animation_time(&mut self, fraction: f32) {
+        self.scenes
+            .iter_mut()
+            .for_each(|scene| scene.time = scene.animation.duration * fraction);
+    }
+
     pub fn camera_reset(&mut self) {
         self.scenes
             .iter_mut()
@@ -0,0 +1,468 @@
+use super::transform::Transform;
+use crate::vector::{Vec3D, VectorOperation};
+use serde::{Deserialize, Serialize};
+
+// --------------------------------------------------
+
+// Signed distance field expression - negative inside, positive outside. Primitives are centered on
+// the origin, the y axis goes up through cylinders and tori. A smoothness above 0 blends the
+// children of the operations together over about that distance
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum Sdf {
+    Sphere {
+        radius: f32,
+    },
+    Brick {
+        size: Vec3D<f32>,
+    },
+    Cylinder {
+        radius: f32,
+        height: f32,
+    },
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    Capsule {
+        start: Vec3D<f32>,
+        end: Vec3D<f32>,
+        radius: f32,
+    },
+    Union {
+        children: Vec<Sdf>,
+        #[serde(default)]
+        smoothness: f32,
+    },
+    Intersection {
+        children: Vec<Sdf>,
+        #[serde(default)]
+        smoothness: f32,
+    },
+    // The base with the cut taken out of it
+    Difference {
+        base: Box<Sdf>,
+        cut: Box<Sdf>,
+        #[serde(default)]
+        smoothness: f32,
+    },
+    Transformed {
+        transform: Box<Transform>,
+        sdf: Box<Sdf>,
+    },
+    // Rotates the xz plane by the angle (in degrees) times the height
+    Twist {
+        angle: f32,
+        sdf: Box<Sdf>,
+    },
+    // Repeats the child endlessly along every axis with a period above 0
+    Repeat {
+        period: Vec3D<f32>,
+        sdf: Box<Sdf>,
+    },
+    // Grows the child outwards, rounding its edges
+    Round {
+        radius: f32,
+        sdf: Box<Sdf>,
+    },
+}
+
+// Polynomial smooth minimum, the plain one for a smoothness of 0
+fn smooth_min(a: f32, b: f32, smoothness: f32) -> f32 {
+    if smoothness <= 0.0 {
+        a.min(b)
+    } else {
+        let h = (smoothness - (a - b).abs()).max(0.0) / smoothness;
+        a.min(b) - h * h * smoothness / 4.0
+    }
+}
+
+impl Sdf {
+    pub fn distance(&self, p: Vec3D<f32>) -> f32 {
+        match self {
+            Sdf::Sphere { radius } => p.length() - radius,
+            Sdf::Brick { size } => {
+                let q = Vec3D::new(
+                    p.x.abs() - size.x / 2.0,
+                    p.y.abs() - size.y / 2.0,
+                    p.z.abs() - size.z / 2.0,
+                );
+                Vec3D::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length()
+                    + q.x.max(q.y).max(q.z).min(0.0)
+            }
+            Sdf::Cylinder { radius, height } => {
+                let (dx, dy) = (
+                    (p.x * p.x + p.z * p.z).sqrt() - radius,
+                    p.y.abs() - height / 2.0,
+                );
+                dx.max(dy).min(0.0) + (dx.max(0.0).powi(2) + dy.max(0.0).powi(2)).sqrt()
+            }
+            Sdf::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let ring = (p.x * p.x + p.z * p.z).sqrt() - major_radius;
+                (ring * ring + p.y * p.y).sqrt() - minor_radius
+            }
+            Sdf::Capsule { start, end, radius } => {
+                let (pa, ba) = (p - *start, *end - *start);
+                let h = (pa.dot(&ba) / ba.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+                (pa - ba * h).length() - radius
+            }
+            Sdf::Union {
+                children,
+                smoothness,
+            } => children
+                .iter()
+                .map(|child| child.distance(p))
+                .reduce(|a, b| smooth_min(a, b, *smoothness))
+                .unwrap_or(f32::INFINITY),
+            Sdf::Intersection {
+                children,
+                smoothness,
+            } => children
+                .iter()
+                .map(|child| child.distance(p))
+                .reduce(|a, b| -smooth_min(-a, -b, *smoothness))
+                .unwrap_or(f32::INFINITY),
+            Sdf::Difference {
+                base,
+                cut,
+                smoothness,
+            } => -smooth_min(-base.distance(p), cut.distance(p), *smoothness),
+            // Scaling changes distances, the smallest scale keeps them from being too long
+            Sdf::Transformed { transform, sdf } => {
+                let scale = transform.scale;
+                sdf.distance(transform.to_object_point(p))
+                    * scale.x.abs().min(scale.y.abs()).min(scale.z.abs())
+            }
+            // Twisting stretches distances further from the axis, they're shortened to match
+            Sdf::Twist { angle, sdf } => {
+                let angle = angle.to_radians();
+                let (sin, cos) = (angle * p.y).sin_cos();
+                let q = Vec3D::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z);
+                let stretch = (1.0 + (angle * (p.x * p.x + p.z * p.z).sqrt()).powi(2)).sqrt();
+
+                sdf.distance(q) / stretch
+            }
+            Sdf::Repeat { period, sdf } => {
+                let repeat = |v: f32, period: f32| {
+                    if period > 0.0 {
+                        v - period * (v / period).round()
+                    } else {
+                        v
+                    }
+                };
+                sdf.distance(Vec3D::new(
+                    repeat(p.x, period.x),
+                    repeat(p.y, period.y),
+                    repeat(p.z, period.z),
+                ))
+            }
+            Sdf::Round { radius, sdf } => sdf.distance(p) - radius,
+        }
+    }
+
+    // Gradient of the distance by central differences
+    pub fn normal(&self, p: Vec3D<f32>) -> Vec3D<f32> {
+        const H: f32 = 0.0005;
+        let axis = |offset: Vec3D<f32>| self.distance(p + offset) - self.distance(p - offset);
+
+        Vec3D::new(
+            axis(Vec3D::new(H, 0.0, 0.0)),
+            axis(Vec3D::new(0.0, H, 0.0)),
+            axis(Vec3D::new(0.0, 0.0, H)),
+        )
+        .unit_vec()
+    }
+
+    // Sphere tracing - steps along the ray by the distance to the surface, which can't go through
+    // it. Rays starting inside march the same way to where they leave. Marching stops at the
+    // bounding sphere around the origin, or far away without one. Returns the distance in
+    // multiples of the direction
+    pub fn trace(
+        &self,
+        from: Vec3D<f32>,
+        dir: Vec3D<f32>,
+        bound: Option<f32>,
+        dist_min: f32,
+        dist_max: f32,
+    ) -> Option<f32> {
+        const MAX_STEPS: usize = 256;
+        const MAX_DISTANCE: f32 = 1000.0;
+        const EPSILON: f32 = 0.0001;
+
+        // Crossings behind the ray are searched from -infinity, which is as far away as the end
+        let length = dir.length();
+        let (mut dist, mut dist_max) = (
+            dist_min.max(-MAX_DISTANCE / length),
+            dist_max.min(MAX_DISTANCE / length),
+        );
+        if let Some(bound) = bound {
+            let (a, half_b) = (dir.length_squared(), from.dot(&dir));
+            let discriminant = half_b * half_b - a * (from.length_squared() - bound * bound);
+            if discriminant < 0.0 {
+                return None;
+            }
+
+            let sqrt = discriminant.sqrt();
+            dist = dist.max((-half_b - sqrt) / a);
+            dist_max = dist_max.min((-half_b + sqrt) / a);
+        }
+        if dist >= dist_max {
+            return None;
+        }
+
+        let side = self.distance(from + dir * dist).signum();
+        for step in 0..MAX_STEPS {
+            let surface = self.distance(from + dir * dist) * side;
+            // The first step can start on the surface that was just left
+            if surface < EPSILON && step > 0 {
+                return Some(dist);
+            }
+
+            dist += surface.max(EPSILON) / length;
+            if dist >= dist_max {
+                return None;
+            }
+        }
+
+        None
+    }
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{a} != {b}");
+    }
+
+    fn sphere(radius: f32) -> Sdf {
+        Sdf::Sphere { radius }
+    }
+
+    fn brick(x: f32, y: f32, z: f32) -> Sdf {
+        Sdf::Brick {
+            size: Vec3D::new(x, y, z),
+        }
+    }
+
+    #[test]
+    fn primitive_distances() {
+        let cases = [
+            (sphere(1.0), Vec3D::new(3.0, 0.0, 0.0), 2.0),
+            (sphere(1.0), Vec3D::new(0.0, 0.0, 0.0), -1.0),
+            (brick(2.0, 4.0, 6.0), Vec3D::new(3.0, 0.0, 0.0), 2.0),
+            // Next to an edge
+            (
+                brick(2.0, 4.0, 6.0),
+                Vec3D::new(2.0, 3.0, 0.0),
+                2.0f32.sqrt(),
+            ),
+            (brick(2.0, 4.0, 6.0), Vec3D::new(0.0, 0.0, 0.0), -1.0),
+            (
+                Sdf::Cylinder {
+                    radius: 1.0,
+                    height: 2.0,
+                },
+                Vec3D::new(0.0, 3.0, 0.0),
+                2.0,
+            ),
+            (
+                Sdf::Cylinder {
+                    radius: 1.0,
+                    height: 2.0,
+                },
+                Vec3D::new(2.0, 2.0, 0.0),
+                2.0f32.sqrt(),
+            ),
+            (
+                Sdf::Cylinder {
+                    radius: 1.0,
+                    height: 4.0,
+                },
+                Vec3D::new(0.0, 0.0, 0.0),
+                -1.0,
+            ),
+            (
+                Sdf::Torus {
+                    major_radius: 2.0,
+                    minor_radius: 0.5,
+                },
+                Vec3D::new(0.0, 0.0, 2.0),
+                -0.5,
+            ),
+            (
+                Sdf::Torus {
+                    major_radius: 2.0,
+                    minor_radius: 0.5,
+                },
+                Vec3D::new(0.0, 3.0, 0.0),
+                13.0f32.sqrt() - 0.5,
+            ),
+            (
+                Sdf::Capsule {
+                    start: Vec3D::new(0.0, 0.0, 0.0),
+                    end: Vec3D::new(0.0, 2.0, 0.0),
+                    radius: 0.5,
+                },
+                Vec3D::new(1.0, 1.0, 0.0),
+                0.5,
+            ),
+            (
+                Sdf::Capsule {
+                    start: Vec3D::new(0.0, 0.0, 0.0),
+                    end: Vec3D::new(0.0, 2.0, 0.0),
+                    radius: 0.5,
+                },
+                Vec3D::new(0.0, -1.0, 0.0),
+                0.5,
+            ),
+        ];
+
+        for (sdf, p, expected) in cases {
+            assert_close(sdf.distance(p), expected);
+        }
+    }
+
+    #[test]
+    fn operation_distances() {
+        let p = Vec3D::new(1.5, 0.0, 0.0);
+        let union = |smoothness| Sdf::Union {
+            children: vec![sphere(1.0), sphere(2.0)],
+            smoothness,
+        };
+
+        assert_close(union(0.0).distance(p), -0.5);
+        assert_close(
+            Sdf::Intersection {
+                children: vec![sphere(1.0), sphere(2.0)],
+                smoothness: 0.0,
+            }
+            .distance(p),
+            0.5,
+        );
+        let difference = Sdf::Difference {
+            base: Box::new(sphere(2.0)),
+            cut: Box::new(sphere(1.0)),
+            smoothness: 0.0,
+        };
+        assert_close(difference.distance(p), -0.5);
+        // The middle is cut out
+        assert_close(difference.distance(Vec3D::new(0.0, 0.0, 0.0)), 1.0);
+
+        // Smoothing only pulls in distances where the children are close to each other
+        let twice = Sdf::Union {
+            children: vec![sphere(1.0), sphere(1.0)],
+            smoothness: 0.4,
+        };
+        assert_close(twice.distance(Vec3D::new(2.0, 0.0, 0.0)), 0.9);
+        assert_close(union(0.4).distance(Vec3D::new(5.0, 0.0, 0.0)), 3.0);
+
+        let empty = Sdf::Union {
+            children: vec![],
+            smoothness: 0.0,
+        };
+        assert_eq!(empty.distance(p), f32::INFINITY);
+    }
+
+    #[test]
+    fn modifier_distances() {
+        // A sphere with a radius of 2 around (1, 0, 0)
+        let transformed = Sdf::Transformed {
+            transform: Box::new(Transform::new(
+                Vec3D::new(1.0, 0.0, 0.0),
+                Vec3D::new(0.0, 0.0, 0.0),
+                Vec3D::new(2.0, 2.0, 2.0),
+            )),
+            sdf: Box::new(sphere(1.0)),
+        };
+        assert_close(transformed.distance(Vec3D::new(4.0, 0.0, 0.0)), 1.0);
+
+        // A quarter turn at y = 1 turns the long side of the brick from x to z
+        let twist = Sdf::Twist {
+            angle: 90.0,
+            sdf: Box::new(brick(4.0, 4.0, 1.0)),
+        };
+        let stretch = (1.0 + (FRAC_PI_2 * 1.5).powi(2)).sqrt();
+        assert_close(twist.distance(Vec3D::new(0.0, 1.0, 1.5)), -0.5 / stretch);
+        // Points on the axis aren't moved
+        assert_close(twist.distance(Vec3D::new(0.0, 3.0, 0.0)), 1.0);
+
+        // Repeated along x only
+        let repeat = Sdf::Repeat {
+            period: Vec3D::new(4.0, 0.0, 0.0),
+            sdf: Box::new(sphere(1.0)),
+        };
+        assert_close(repeat.distance(Vec3D::new(8.5, 0.0, 0.0)), -0.5);
+        assert_close(repeat.distance(Vec3D::new(-2.0, 0.0, 0.0)), 1.0);
+        assert_close(repeat.distance(Vec3D::new(0.0, 5.0, 0.0)), 4.0);
+
+        let round = Sdf::Round {
+            radius: 0.5,
+            sdf: Box::new(brick(2.0, 2.0, 2.0)),
+        };
+        assert_close(round.distance(Vec3D::new(3.0, 0.0, 0.0)), 1.5);
+    }
+
+    #[test]
+    fn tracing_hits_and_misses() {
+        let sphere = sphere(1.0);
+        let dir = Vec3D::new(2.0, 0.0, 0.0);
+
+        for bound in [None, Some(1.5)] {
+            // Distances are in multiples of the direction
+            let dist = sphere.trace(Vec3D::new(-5.0, 0.0, 0.0), dir, bound, 0.0, f32::INFINITY);
+            assert_close(dist.unwrap(), 2.0);
+
+            let miss = Vec3D::new(-5.0, 1.1, 0.0);
+            assert_eq!(sphere.trace(miss, dir, bound, 0.0, f32::INFINITY), None);
+            // Too short
+            assert_eq!(
+                sphere.trace(Vec3D::new(-5.0, 0.0, 0.0), dir, bound, 0.0, 1.5),
+                None
+            );
+
+            // Starting inside, closer to the surface than the hit distance, still finds where
+            // the ray leaves rather than where it starts
+            let dist = sphere.trace(
+                Vec3D::new(-1.0, 0.0, 0.0),
+                dir,
+                bound,
+                0.00002,
+                f32::INFINITY,
+            );
+            assert_close(dist.unwrap(), 1.0);
+        }
+
+        // The bound cuts off the part of the surface outside of it
+        let bound = Some(0.5);
+        assert_eq!(
+            sphere.trace(Vec3D::new(-5.0, 0.0, 0.0), dir, bound, 0.0, f32::INFINITY),
+            None
+        );
+    }
+
+    #[test]
+    fn tracing_from_negative_infinity() {
+        let sphere = sphere(1.0);
+        let dir = Vec3D::new(1.0, 0.0, 0.0);
+
+        for bound in [None, Some(1.5)] {
+            let outside = Vec3D::new(-5.0, 0.0, 0.0);
+            let dist = sphere.trace(outside, dir, bound, f32::NEG_INFINITY, f32::INFINITY);
+            assert_close(dist.unwrap(), 4.0);
+
+            // From inside the first crossing is behind the ray, then comes the one in front
+            let inside = Vec3D::new(0.0, 0.0, 0.0);
+            let enter = sphere.trace(inside, dir, bound, f32::NEG_INFINITY, f32::INFINITY);
+            assert_close(enter.unwrap(), -1.0);
+            let exit = sphere.trace(inside, dir, bound, enter.unwrap() + 0.001, f32::INFINITY);
+            assert_close(exit.unwrap(), 1.0);
+        }
+    }
+}
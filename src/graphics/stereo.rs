@@ -0,0 +1,166 @@
+use super::camera::Camera;
+use crate::draw::Color;
+use crate::vector::{Vec2D, Vec3D};
+
+// --------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StereoMode {
+    #[default]
+    Off,
+    SideBySide,
+    TopBottom,
+    Anaglyph,
+}
+
+// --------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stereo {
+    pub mode: StereoMode,
+    pub interocular_distance: f32,
+    pub convergence_distance: f32,
+}
+
+impl Stereo {
+    pub const fn new(
+        mode: StereoMode,
+        interocular_distance: f32,
+        convergence_distance: f32,
+    ) -> Stereo {
+        Stereo {
+            mode,
+            interocular_distance,
+            convergence_distance,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.mode != StereoMode::Off
+    }
+
+    // Sizes of the (left, right) eye images that make up an output image of the given size
+    pub fn eye_sizes(&self, size: Vec2D<u32>) -> (Vec2D<u32>, Vec2D<u32>) {
+        match self.mode {
+            StereoMode::Off | StereoMode::Anaglyph => (size, size),
+            StereoMode::SideBySide => (
+                Vec2D::new(size.x / 2, size.y),
+                Vec2D::new(size.x - size.x / 2, size.y),
+            ),
+            StereoMode::TopBottom => (
+                Vec2D::new(size.x, size.y / 2),
+                Vec2D::new(size.x, size.y - size.y / 2),
+            ),
+        }
+    }
+
+    pub fn eye_cameras(&self, camera: &Camera, size: Vec2D<u32>) -> (Camera, Camera) {
+        let (left_size, right_size) = self.eye_sizes(size);
+        let eye = |eye_size: Vec2D<u32>, eye_offset: f32| {
+            let mut out = camera.clone();
+            out.resize((eye_size.x as f32) / (eye_size.y as f32));
+            out.stereo_eye(eye_offset, self.convergence_distance)
+        };
+
+        (
+            eye(left_size, -self.interocular_distance / 2.0),
+            eye(right_size, self.interocular_distance / 2.0),
+        )
+    }
+
+    // Both eye images are expected row by row, top row first - same as FrameBuffer
+    pub fn combine(&self, left: &[Color], right: &[Color], size: Vec2D<u32>) -> Vec<Color> {
+        let (left_size, right_size) = self.eye_sizes(size);
+
+        match self.mode {
+            StereoMode::Off => left.to_vec(),
+            StereoMode::SideBySide => (0..(size.y as usize))
+                .flat_map(|y| {
+                    let left_row = y * left_size.x as usize;
+                    let right_row = y * right_size.x as usize;
+
+                    left[left_row..(left_row + left_size.x as usize)]
+                        .iter()
+                        .chain(right[right_row..(right_row + right_size.x as usize)].iter())
+                        .copied()
+                        .collect::<Vec<Color>>()
+                })
+                .collect(),
+            StereoMode::TopBottom => left.iter().chain(right.iter()).copied().collect(),
+            StereoMode::Anaglyph => left
+                .iter()
+                .zip(right.iter())
+                .map(|(left, right)| Color(Vec3D::new(left.0.x, right.0.y, right.0.z)))
+                .collect(),
+        }
+    }
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::camera::{Orientation, Projection};
+    use crate::vector::VectorOperation;
+
+    #[test]
+    fn eye_rays_meet_at_the_convergence_distance() {
+        let camera = Camera::new(
+            Vec3D::new(1.0, 2.0, 3.0),
+            Orientation::new(30.0, -10.0, 5.0),
+            1.0,
+            70.0,
+            Projection::Perspective,
+        );
+        let stereo = Stereo::new(StereoMode::SideBySide, 0.1, 4.0);
+        let (left, right) = stereo.eye_cameras(&camera, Vec2D::new(200, 100));
+
+        // The eyes sit half the interocular distance to either side of the camera
+        assert!((left.pos - camera.pos + camera.orientation.right() * 0.05).length() < 1e-5);
+        assert!((right.pos - camera.pos - camera.orientation.right() * 0.05).length() < 1e-5);
+
+        // Every pixel has zero parallax at the convergence distance, which is measured along the
+        // view direction
+        for (x, y) in [(0.5, 0.5), (0.0, 0.0), (0.9, 0.2), (0.3, 1.0)] {
+            let meet = |eye: &Camera| {
+                let ray = eye.get_ray(x, y);
+                ray.at(4.0 / ray.dir.dot(&camera.direction()))
+            };
+            assert!((meet(&left) - meet(&right)).length() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn eye_sizes_cover_the_whole_image() {
+        let size = Vec2D::new(101, 51);
+
+        let (left, right) = Stereo::new(StereoMode::SideBySide, 0.1, 4.0).eye_sizes(size);
+        assert_eq!((left.x + right.x, left.y, right.y), (101, 51, 51));
+        let (left, right) = Stereo::new(StereoMode::TopBottom, 0.1, 4.0).eye_sizes(size);
+        assert_eq!((left.x, right.x, left.y + right.y), (101, 101, 51));
+    }
+
+    #[test]
+    fn combine_places_the_eye_images() {
+        let (red, cyan) = (Color::new(255, 0, 0), Color::new(0, 255, 255));
+        let (left, right) = (vec![red; 4], vec![cyan; 4]);
+
+        // Rows of 2 left pixels followed by 2 right ones
+        let side_by_side =
+            Stereo::new(StereoMode::SideBySide, 0.1, 4.0).combine(&left, &right, Vec2D::new(4, 2));
+        assert_eq!(
+            side_by_side,
+            vec![red, red, cyan, cyan, red, red, cyan, cyan]
+        );
+
+        // Red from the left eye, green and blue from the right one
+        let white = Color::new(255, 255, 255);
+        let anaglyph = Stereo::new(StereoMode::Anaglyph, 0.1, 4.0).combine(
+            &[white, white],
+            &[Color::new(0, 0, 0), white],
+            Vec2D::new(2, 1),
+        );
+        assert_eq!(anaglyph, vec![red, white]);
+    }
+}
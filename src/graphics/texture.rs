@@ -2,12 +2,82 @@ use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
+use super::noise::{fbm, fbm_normalized, turbulence, worley};
+use super::ray::RayHit;
 use crate::{
     draw::Color,
     image::ImageData,
-    vector::{Vec2D, Vec3D},
+    vector::{Vec2D, Vec3D, VectorOperation},
 };
 
+// What happens to uvs outside of 0..1
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum WrapMode {
+    #[default]
+    Clamp,
+    Repeat,
+    // Repeats, every other copy flipped
+    Mirror,
+}
+
+impl WrapMode {
+    pub fn wrap(self, t: f32) -> f32 {
+        match self {
+            WrapMode::Clamp => t.clamp(0.0, 1.0),
+            WrapMode::Repeat => t.rem_euclid(1.0),
+            WrapMode::Mirror => {
+                let t = t.rem_euclid(2.0);
+                if t > 1.0 {
+                    2.0 - t
+                } else {
+                    t
+                }
+            }
+        }
+    }
+}
+
+// Applied to the uvs of a texture - scaled, rotated around the origin (in degrees), offset and
+// then wrapped. A scale of 4 repeats the texture 4 times when it's wrapped
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct UvTransform {
+    pub scale: Vec2D<f32>,
+    pub offset: Vec2D<f32>,
+    pub rotation: f32,
+    pub wrap: WrapMode,
+}
+
+impl UvTransform {
+    pub fn apply(&self, u: f32, v: f32) -> Vec2D<f32> {
+        let (u, v) = (u * self.scale.x, v * self.scale.y);
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+
+        Vec2D::new(
+            self.wrap.wrap(cos * u - sin * v + self.offset.x),
+            self.wrap.wrap(sin * u + cos * v + self.offset.y),
+        )
+    }
+}
+
+impl Default for UvTransform {
+    fn default() -> Self {
+        UvTransform {
+            scale: Vec2D::new(1.0, 1.0),
+            offset: Vec2D::new(0.0, 0.0),
+            rotation: 0.0,
+            wrap: WrapMode::default(),
+        }
+    }
+}
+
+// Where a pattern is evaluated - in uv space points are (u, v, 0)
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum PatternSpace {
+    Uv,
+    World,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum Texture {
     SolidColor {
@@ -20,11 +90,91 @@ pub enum Texture {
     },
     Image {
         data: Arc<ImageData>,
+        #[serde(default)]
+        uv: UvTransform,
+    },
+    // Colors of the mesh vertices, white for objects without them
+    VertexColor,
+    // Solid textures from noise at the hit point - the color goes from the low color to the high
+    // one as the noise goes from 0 to 1. Features are about 1 / scale big, more octaves add detail
+    Noise {
+        low_color: Color,
+        high_color: Color,
+        scale: f32,
+        octaves: u32,
+    },
+    Turbulence {
+        low_color: Color,
+        high_color: Color,
+        scale: f32,
+        octaves: u32,
+    },
+    // Stripes along the z axis, bent by turbulence
+    Marble {
+        low_color: Color,
+        high_color: Color,
+        scale: f32,
+        octaves: u32,
+    },
+    // Rings around the y axis, scale of them per unit and bent by noise
+    Wood {
+        low_color: Color,
+        high_color: Color,
+        scale: f32,
+        octaves: u32,
+    },
+    // Cells around scattered points, low at the points and high at the borders
+    Voronoi {
+        low_color: Color,
+        high_color: Color,
+        scale: f32,
     },
+    // From the low color at start to the high one at end, constant past them
+    LinearGradient {
+        low_color: Color,
+        high_color: Color,
+        space: PatternSpace,
+        start: Vec3D<f32>,
+        end: Vec3D<f32>,
+    },
+    // From the low color at the center to the high one at the radius
+    RadialGradient {
+        low_color: Color,
+        high_color: Color,
+        space: PatternSpace,
+        center: Vec3D<f32>,
+        radius: f32,
+    },
+    // Count pairs of stripes across the texture, changing along u
+    Stripes {
+        odd_color: Color,
+        even_color: Color,
+        count: f32,
+    },
+    // Count cells along u and v, the line width is a fraction of a cell
+    Grid {
+        line_color: Color,
+        fill_color: Color,
+        count: f32,
+        line_width: f32,
+    },
+}
+
+fn color_ramp(low_color: Color, high_color: Color, t: f32) -> Color {
+    Color(low_color.0 + (high_color.0 - low_color.0) * t.clamp(0.0, 1.0))
+}
+
+fn pattern_point(space: PatternSpace, record: &RayHit) -> Vec3D<f32> {
+    match space {
+        PatternSpace::Uv => Vec3D::new(record.u, record.v, 0.0),
+        PatternSpace::World => record.pos,
+    }
 }
 
 impl Texture {
-    pub fn color_value(&self, u: f32, v: f32, point: Vec3D<f32>) -> Color {
+    pub fn color_value(&self, record: &RayHit) -> Color {
+        let (u, v, point) = (record.u, record.v, record.pos);
+
         match self {
             Texture::SolidColor { color } => *color,
             Texture::Checkered {
@@ -42,7 +192,138 @@ impl Texture {
                     *even_color
                 }
             }
-            Texture::Image { data } => data[Vec2D::new(u, 1.0 - v)],
+            Texture::Image {
+                data,
+                uv: transform,
+            } => {
+                let uv = transform.apply(u, v);
+                data.sample(
+                    Vec2D::new(uv.x, 1.0 - uv.y),
+                    record.uv_footprint * transform.scale.x.abs().max(transform.scale.y.abs()),
+                    transform.wrap,
+                )
+            }
+            Texture::VertexColor => record.vertex_color.unwrap_or_default(),
+            Texture::Noise {
+                low_color,
+                high_color,
+                scale,
+                octaves,
+            } => color_ramp(
+                *low_color,
+                *high_color,
+                fbm_normalized(point * *scale, *octaves),
+            ),
+            Texture::Turbulence {
+                low_color,
+                high_color,
+                scale,
+                octaves,
+            } => color_ramp(
+                *low_color,
+                *high_color,
+                turbulence(point * *scale, *octaves),
+            ),
+            Texture::Marble {
+                low_color,
+                high_color,
+                scale,
+                octaves,
+            } => {
+                let p = point * *scale;
+                color_ramp(
+                    *low_color,
+                    *high_color,
+                    0.5 + 0.5 * (p.z + 10.0 * turbulence(p, *octaves)).sin(),
+                )
+            }
+            Texture::Wood {
+                low_color,
+                high_color,
+                scale,
+                octaves,
+            } => {
+                let rings = (point.x * point.x + point.z * point.z).sqrt() * scale
+                    + fbm(point * 2.0, *octaves) * 0.6;
+                // Sharp on the outer side of every ring, like late wood
+                color_ramp(*low_color, *high_color, rings.fract().powi(3))
+            }
+            Texture::Voronoi {
+                low_color,
+                high_color,
+                scale,
+            } => color_ramp(*low_color, *high_color, worley(point * *scale)),
+            Texture::LinearGradient {
+                low_color,
+                high_color,
+                space,
+                start,
+                end,
+            } => {
+                let (p, axis) = (pattern_point(*space, record) - *start, *end - *start);
+                color_ramp(
+                    *low_color,
+                    *high_color,
+                    p.dot(&axis) / axis.length_squared().max(f32::EPSILON),
+                )
+            }
+            Texture::RadialGradient {
+                low_color,
+                high_color,
+                space,
+                center,
+                radius,
+            } => color_ramp(
+                *low_color,
+                *high_color,
+                (pattern_point(*space, record) - *center).length() / radius.max(f32::EPSILON),
+            ),
+            Texture::Stripes {
+                odd_color,
+                even_color,
+                count,
+            } => {
+                if (u * count).rem_euclid(1.0) < 0.5 {
+                    *odd_color
+                } else {
+                    *even_color
+                }
+            }
+            Texture::Grid {
+                line_color,
+                fill_color,
+                count,
+                line_width,
+            } => {
+                // Distance to the closest line, in cells
+                let line = |t: f32| {
+                    let t = (t * count).rem_euclid(1.0);
+                    t.min(1.0 - t)
+                };
+                if line(u).min(line(v)) < line_width / 2.0 {
+                    *line_color
+                } else {
+                    *fill_color
+                }
+            }
+        }
+    }
+
+    // 0 is fully transparent. Images with an alpha channel use it, everything else is a grayscale
+    // mask
+    pub fn opacity(&self, record: &RayHit) -> f32 {
+        match self {
+            Texture::Image {
+                data,
+                uv: transform,
+            } if data.has_alpha() => {
+                let uv = transform.apply(record.u, record.v);
+                data.alpha(Vec2D::new(uv.x, 1.0 - uv.y), transform.wrap)
+            }
+            _ => {
+                let color = self.color_value(record).0;
+                (color.x + color.y + color.z) / 3.0
+            }
         }
     }
 }
@@ -54,3 +335,216 @@ impl Default for Texture {
         }
     }
 }
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Material;
+
+    fn assert_close(a: Vec2D<f32>, b: Vec2D<f32>) {
+        assert!(
+            (a.x - b.x).abs() < 1e-5 && (a.y - b.y).abs() < 1e-5,
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let cases = [
+            (WrapMode::Clamp, [(-0.5, 0.0), (0.3, 0.3), (1.5, 1.0)]),
+            (WrapMode::Repeat, [(-0.25, 0.75), (1.25, 0.25), (2.0, 0.0)]),
+            (
+                WrapMode::Mirror,
+                [(-0.25, 0.25), (1.25, 0.75), (2.25, 0.25)],
+            ),
+        ];
+
+        for (wrap, values) in cases {
+            for (t, expected) in values {
+                assert!((wrap.wrap(t) - expected).abs() < 1e-5, "{wrap:?} {t}");
+            }
+        }
+        assert!((WrapMode::Mirror.wrap(-1.25) - 0.75).abs() < 1e-5);
+    }
+
+    #[test]
+    fn uv_transforms_scale_then_rotate_then_offset() {
+        assert_close(UvTransform::default().apply(0.3, 0.6), Vec2D::new(0.3, 0.6));
+
+        let transform = UvTransform {
+            scale: Vec2D::new(2.0, 1.0),
+            offset: Vec2D::new(0.5, 0.0),
+            rotation: 90.0,
+            wrap: WrapMode::Repeat,
+        };
+        // (0.5, 0.5) after scaling, (-0.5, 0.5) after turning, (0, 0.5) after the offset
+        assert_close(transform.apply(0.25, 0.5), Vec2D::new(0.0, 0.5));
+
+        let clamped = UvTransform {
+            wrap: WrapMode::Clamp,
+            ..transform
+        };
+        assert_close(clamped.apply(0.25, 0.75), Vec2D::new(0.0, 0.5));
+        assert_close(clamped.apply(0.0, -1.0), Vec2D::new(1.0, 0.0));
+    }
+
+    #[test]
+    fn uv_transform_fields_default() {
+        let transform: UvTransform =
+            serde_yaml::from_str("{ scale: { x: 4.0, y: 2.0 }, wrap: Repeat }").unwrap();
+
+        assert_eq!(
+            transform,
+            UvTransform {
+                scale: Vec2D::new(4.0, 2.0),
+                wrap: WrapMode::Repeat,
+                ..UvTransform::default()
+            }
+        );
+        assert_close(transform.apply(0.3, 0.6), Vec2D::new(0.2, 0.2));
+    }
+
+    const LOW: Color = Color::new(0, 0, 0);
+    const HIGH: Color = Color::new(255, 255, 255);
+
+    fn record(u: f32, v: f32, pos: Vec3D<f32>) -> RayHit {
+        RayHit::new(
+            pos,
+            Vec3D::new(0.0, 1.0, 0.0),
+            true,
+            u,
+            v,
+            Arc::new(Material::default()),
+        )
+    }
+
+    fn at_uv(texture: &Texture, u: f32, v: f32) -> Color {
+        texture.color_value(&record(u, v, Vec3D::new(0.0, 0.0, 0.0)))
+    }
+
+    fn at_pos(texture: &Texture, x: f32, y: f32, z: f32) -> Color {
+        texture.color_value(&record(0.0, 0.0, Vec3D::new(x, y, z)))
+    }
+
+    fn assert_gray(color: Color, value: f32) {
+        assert!(
+            (color.0 - Vec3D::new(value, value, value)).length() < 1e-4,
+            "{color:?} isn't {value}"
+        );
+    }
+
+    #[test]
+    fn stripes_alternate_along_u() {
+        let stripes = Texture::Stripes {
+            odd_color: LOW,
+            even_color: HIGH,
+            count: 2.0,
+        };
+
+        for (u, color) in [
+            (0.1, LOW),
+            (0.3, HIGH),
+            (0.6, LOW),
+            (0.9, HIGH),
+            (-0.1, HIGH),
+            (1.1, LOW),
+        ] {
+            assert_eq!(at_uv(&stripes, u, 0.5), color, "u = {u}");
+        }
+        // v doesn't change them
+        assert_eq!(at_uv(&stripes, 0.1, 0.9), LOW);
+    }
+
+    #[test]
+    fn grid_lines_are_a_fraction_of_a_cell_wide() {
+        let grid = Texture::Grid {
+            line_color: LOW,
+            fill_color: HIGH,
+            count: 4.0,
+            line_width: 0.1,
+        };
+
+        // Cell centers, then points 0.04 and 0.08 cells from a line along u or v
+        assert_eq!(at_uv(&grid, 0.125, 0.125), HIGH);
+        assert_eq!(at_uv(&grid, 0.625, 0.875), HIGH);
+        assert_eq!(at_uv(&grid, 0.26, 0.125), LOW);
+        assert_eq!(at_uv(&grid, 0.125, 0.74), LOW);
+        assert_eq!(at_uv(&grid, 0.27, 0.125), HIGH);
+        assert_eq!(at_uv(&grid, 0.0, 0.0), LOW);
+        assert_eq!(at_uv(&grid, 1.0, 0.5), LOW);
+    }
+
+    #[test]
+    fn gradients_go_from_low_to_high() {
+        let linear = Texture::LinearGradient {
+            low_color: LOW,
+            high_color: HIGH,
+            space: PatternSpace::Uv,
+            start: Vec3D::new(0.0, 0.0, 0.0),
+            end: Vec3D::new(1.0, 0.0, 0.0),
+        };
+        for (u, value) in [
+            (0.0, 0.0),
+            (0.25, 0.25),
+            (1.0, 1.0),
+            (-1.0, 0.0),
+            (2.0, 1.0),
+        ] {
+            assert_gray(at_uv(&linear, u, 0.7), value);
+        }
+
+        let linear = Texture::LinearGradient {
+            low_color: LOW,
+            high_color: HIGH,
+            space: PatternSpace::World,
+            start: Vec3D::new(0.0, 1.0, 0.0),
+            end: Vec3D::new(0.0, 3.0, 0.0),
+        };
+        assert_gray(at_pos(&linear, 5.0, 1.0, 5.0), 0.0);
+        assert_gray(at_pos(&linear, 5.0, 2.0, 5.0), 0.5);
+        assert_gray(at_pos(&linear, 5.0, 3.0, 5.0), 1.0);
+
+        let radial = Texture::RadialGradient {
+            low_color: LOW,
+            high_color: HIGH,
+            space: PatternSpace::World,
+            center: Vec3D::new(1.0, 0.0, 0.0),
+            radius: 2.0,
+        };
+        assert_gray(at_pos(&radial, 1.0, 0.0, 0.0), 0.0);
+        assert_gray(at_pos(&radial, 1.0, 0.0, 1.0), 0.5);
+        assert_gray(at_pos(&radial, 3.0, 0.0, 0.0), 1.0);
+        assert_gray(at_pos(&radial, 10.0, 0.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn wood_and_voronoi_stay_between_their_colors() {
+        let wood = Texture::Wood {
+            low_color: LOW,
+            high_color: HIGH,
+            scale: 4.0,
+            octaves: 3,
+        };
+        let voronoi = Texture::Voronoi {
+            low_color: LOW,
+            high_color: HIGH,
+            scale: 2.0,
+        };
+
+        for texture in [wood, voronoi] {
+            let values: Vec<f32> = (0..200)
+                .map(|i| {
+                    at_pos(&texture, i as f32 * 0.037, 0.3, i as f32 * 0.011)
+                        .0
+                        .x
+                })
+                .collect();
+            assert!(values.iter().all(|value| (0.0..=1.0).contains(value)));
+            // Both dark and light parts show up
+            assert!(values.iter().any(|value| *value < 0.2));
+            assert!(values.iter().any(|value| *value > 0.5));
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use crate::vector::{Mat4, Vec3D, VectorOperation};
+use serde::{Deserialize, Serialize};
+
+// --------------------------------------------------
+
+#[derive(Clone, Debug, Deserialize)]
+struct TransformData {
+    #[serde(default)]
+    translation: Vec3D<f32>,
+    #[serde(default)]
+    rotation: Vec3D<f32>,
+    #[serde(default = "scale_default")]
+    scale: Vec3D<f32>,
+}
+
+const fn scale_default() -> Vec3D<f32> {
+    Vec3D::new(1.0, 1.0, 1.0)
+}
+
+// Scale, then rotate around the x, y and z axis (in degrees), then translate. The matrices are
+// cached, as every ray hitting an instance goes through them
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(from = "TransformData")]
+pub struct Transform {
+    pub translation: Vec3D<f32>,
+    pub rotation: Vec3D<f32>,
+    pub scale: Vec3D<f32>,
+    #[serde(skip)]
+    matrix: Mat4,
+    #[serde(skip)]
+    inverse: Mat4,
+    #[serde(skip)]
+    normal_matrix: Mat4,
+}
+
+impl Transform {
+    pub fn new(translation: Vec3D<f32>, rotation: Vec3D<f32>, scale: Vec3D<f32>) -> Transform {
+        let matrix = Mat4::from_translation_rotation_scale(
+            translation,
+            Transform::quaternion(rotation),
+            scale,
+        );
+        // Zero scales can't be inverted, those objects are squashed flat
+        let inverse = matrix.inverse().unwrap_or_default();
+
+        Transform {
+            translation,
+            rotation,
+            scale,
+            matrix,
+            inverse,
+            normal_matrix: inverse.transposed(),
+        }
+    }
+
+    pub fn translated(&self, offset: Vec3D<f32>) -> Transform {
+        Transform::new(self.translation + offset, self.rotation, self.scale)
+    }
+
+    pub fn to_world_point(&self, p: Vec3D<f32>) -> Vec3D<f32> {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn to_object_point(&self, p: Vec3D<f32>) -> Vec3D<f32> {
+        self.inverse.transform_point(p)
+    }
+
+    pub fn to_world_vector(&self, v: Vec3D<f32>) -> Vec3D<f32> {
+        self.matrix.transform_vector(v)
+    }
+
+    // Directions aren't normalized, so distances along rays stay the same in both spaces
+    pub fn to_object_vector(&self, v: Vec3D<f32>) -> Vec3D<f32> {
+        self.inverse.transform_vector(v)
+    }
+
+    pub fn to_world_normal(&self, n: Vec3D<f32>) -> Vec3D<f32> {
+        self.normal_matrix.transform_vector(n)
+    }
+
+    // Rotation (in degrees) that turns the x and y axis towards the given directions. The y
+    // direction is made perpendicular to the x one first
+    pub fn rotation_from_axes(x: Vec3D<f32>, y: Vec3D<f32>) -> Vec3D<f32> {
+        let x = x.unit_vec();
+        let z = x.cross(&y).unit_vec();
+        let y = z.cross(&x);
+
+        Vec3D::new(
+            y.z.atan2(z.z).to_degrees(),
+            (-x.z).clamp(-1.0, 1.0).asin().to_degrees(),
+            x.y.atan2(x.x).to_degrees(),
+        )
+    }
+
+    // Rotation around x first, then y, then z
+    fn quaternion(rotation: Vec3D<f32>) -> [f32; 4] {
+        let (sx, cx) = (rotation.x.to_radians() / 2.0).sin_cos();
+        let (sy, cy) = (rotation.y.to_radians() / 2.0).sin_cos();
+        let (sz, cz) = (rotation.z.to_radians() / 2.0).sin_cos();
+
+        [
+            sx * cy * cz - cx * sy * sz,
+            cx * sy * cz + sx * cy * sz,
+            cx * cy * sz - sx * sy * cz,
+            cx * cy * cz + sx * sy * sz,
+        ]
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::new(
+            Vec3D::new(0.0, 0.0, 0.0),
+            Vec3D::new(0.0, 0.0, 0.0),
+            scale_default(),
+        )
+    }
+}
+
+impl From<TransformData> for Transform {
+    fn from(item: TransformData) -> Transform {
+        Transform::new(item.translation, item.rotation, item.scale)
+    }
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn assert_close(a: Vec3D<f32>, b: Vec3D<f32>) {
+        assert!((a - b).length() < 1e-4, "{a:?} != {b:?}");
+    }
+
+    fn random_vec3d(rng: &mut StdRng, min: f32, max: f32) -> Vec3D<f32> {
+        Vec3D::new(
+            rng.gen_range(min..max),
+            rng.gen_range(min..max),
+            rng.gen_range(min..max),
+        )
+    }
+
+    fn random_transform(rng: &mut StdRng) -> Transform {
+        Transform::new(
+            random_vec3d(rng, -5.0, 5.0),
+            random_vec3d(rng, -180.0, 180.0),
+            random_vec3d(rng, 0.5, 3.0),
+        )
+    }
+
+    #[test]
+    fn points_and_vectors_round_trip() {
+        let mut rng = StdRng::seed_from_u64(35);
+
+        for _ in 0..100 {
+            let transform = random_transform(&mut rng);
+            let p = random_vec3d(&mut rng, -2.0, 2.0);
+
+            assert_close(transform.to_object_point(transform.to_world_point(p)), p);
+            assert_close(transform.to_world_point(transform.to_object_point(p)), p);
+            assert_close(transform.to_object_vector(transform.to_world_vector(p)), p);
+        }
+    }
+
+    #[test]
+    fn scale_then_rotate_then_translate() {
+        let transform = Transform::new(
+            Vec3D::new(1.0, 2.0, 3.0),
+            Vec3D::new(0.0, 0.0, 90.0),
+            Vec3D::new(2.0, 1.0, 1.0),
+        );
+
+        // x is doubled, then turned onto y
+        assert_close(
+            transform.to_world_point(Vec3D::new(1.0, 0.0, 0.0)),
+            Vec3D::new(1.0, 4.0, 3.0),
+        );
+        assert_close(
+            transform.to_world_vector(Vec3D::new(0.0, 1.0, 0.0)),
+            Vec3D::new(-1.0, 0.0, 0.0),
+        );
+        // The x rotation comes first
+        let transform = Transform::new(
+            Vec3D::new(0.0, 0.0, 0.0),
+            Vec3D::new(90.0, 90.0, 0.0),
+            Vec3D::new(1.0, 1.0, 1.0),
+        );
+        assert_close(
+            transform.to_world_vector(Vec3D::new(0.0, 1.0, 0.0)),
+            Vec3D::new(1.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        let mut rng = StdRng::seed_from_u64(36);
+
+        for _ in 0..100 {
+            let transform = random_transform(&mut rng);
+            let normal = random_vec3d(&mut rng, -1.0, 1.0);
+            let tangent = normal.cross(&random_vec3d(&mut rng, -1.0, 1.0));
+
+            let (normal, tangent) = (
+                transform.to_world_normal(normal),
+                transform.to_world_vector(tangent),
+            );
+            assert!(normal.unit_vec().dot(&tangent.unit_vec()).abs() < 1e-4);
+        }
+
+        // A sphere squashed along y, its normals turn towards y
+        let squash = Transform::new(
+            Vec3D::new(0.0, 0.0, 0.0),
+            Vec3D::new(0.0, 0.0, 0.0),
+            Vec3D::new(1.0, 0.5, 1.0),
+        );
+        let normal = squash.to_world_normal(Vec3D::new(1.0, 1.0, 0.0).unit_vec());
+        assert_close(normal.unit_vec(), Vec3D::new(1.0, 2.0, 0.0).unit_vec());
+    }
+
+    #[test]
+    fn rotation_from_axes_turns_the_axes() {
+        let mut rng = StdRng::seed_from_u64(37);
+
+        for _ in 0..100 {
+            let x = random_vec3d(&mut rng, -1.0, 1.0).unit_vec();
+            let y = random_vec3d(&mut rng, -1.0, 1.0);
+            let transform = Transform::new(
+                Vec3D::new(0.0, 0.0, 0.0),
+                Transform::rotation_from_axes(x, y),
+                Vec3D::new(1.0, 1.0, 1.0),
+            );
+
+            let world_y = transform.to_world_vector(Vec3D::new(0.0, 1.0, 0.0));
+            assert_close(transform.to_world_vector(Vec3D::new(1.0, 0.0, 0.0)), x);
+            // y is only made perpendicular to x
+            assert!(world_y.dot(&x).abs() < 1e-4);
+            assert!(world_y.dot(&y) > 0.0);
+        }
+    }
+
+    #[test]
+    fn zero_scale_squashes_flat() {
+        let flat = Transform::new(
+            Vec3D::new(1.0, 0.0, 0.0),
+            Vec3D::new(0.0, 0.0, 0.0),
+            Vec3D::new(1.0, 0.0, 1.0),
+        );
+
+        assert_close(
+            flat.to_world_point(Vec3D::new(1.0, 1.0, 1.0)),
+            Vec3D::new(2.0, 0.0, 1.0),
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::noise::fbm_normalized;
+use crate::vector::Vec3D;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+// --------------------------------------------------
+
+lazy_static! {
+    // Raw voxel files waiting for a scene to refer to them, the resolution comes from the scene
+    static ref VOLUME_FILES: Mutex<HashMap<String, Arc<Vec<u8>>>> = Mutex::new(HashMap::new());
+}
+
+// Replaces any file registered under the same name
+pub fn register_volume_file(name: &str, data: Vec<u8>) {
+    VOLUME_FILES
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), Arc::new(data));
+}
+
+// --------------------------------------------------
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct DensityGridData {
+    file: String,
+    resolution: Vec3D<u32>,
+}
+
+// Voxels of a raw file, x changes fastest, then y, then z. Files can hold 8 or 16 bit unsigned
+// integers or 32 bit floats (little endian), which one is told by the file size. Densities are
+// kept between 0 and 1
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(try_from = "DensityGridData", into = "DensityGridData")]
+pub struct DensityGrid {
+    pub file: String,
+    pub resolution: Vec3D<u32>,
+    values: Vec<f32>,
+}
+
+impl DensityGrid {
+    pub fn new(file: &str, resolution: Vec3D<u32>, data: &[u8]) -> Result<DensityGrid, String> {
+        let count = (resolution.x as usize) * (resolution.y as usize) * (resolution.z as usize);
+        if count == 0 {
+            return Err("Resolution can't be zero".to_string());
+        }
+
+        let values: Vec<f32> = if data.len() == count {
+            data.iter().map(|value| *value as f32 / u8::MAX as f32).collect()
+        } else if data.len() == count * 2 {
+            data.chunks_exact(2)
+                .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / u16::MAX as f32)
+                .collect()
+        } else if data.len() == count * 4 {
+            data.chunks_exact(4)
+                .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .collect()
+        } else {
+            return Err(format!(
+                "{file:} has {:} bytes, which doesn't fit a resolution of {:}x{:}x{:}",
+                data.len(),
+                resolution.x,
+                resolution.y,
+                resolution.z
+            ));
+        };
+
+        Ok(DensityGrid {
+            file: file.to_string(),
+            resolution,
+            values: values
+                .into_iter()
+                .map(|value| if value.is_nan() { 0.0 } else { value.clamp(0.0, 1.0) })
+                .collect(),
+        })
+    }
+
+    fn voxel(&self, x: i64, y: i64, z: i64) -> f32 {
+        let res = self.resolution;
+        if x < 0 || y < 0 || z < 0 || x >= res.x as i64 || y >= res.y as i64 || z >= res.z as i64
+        {
+            0.0
+        } else {
+            self.values[(x + (y + z * res.y as i64) * res.x as i64) as usize]
+        }
+    }
+
+    // Trilinear interpolation between voxel centers, p goes from 0 to 1 over the whole grid
+    pub fn sample(&self, p: Vec3D<f32>) -> f32 {
+        let p = Vec3D::new(
+            p.x * self.resolution.x as f32 - 0.5,
+            p.y * self.resolution.y as f32 - 0.5,
+            p.z * self.resolution.z as f32 - 0.5,
+        );
+        let (x, y, z) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (tx, ty, tz) = (p.x - x, p.y - y, p.z - z);
+        let (x, y, z) = (x as i64, y as i64, z as i64);
+
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let row = |dy: i64, dz: i64| {
+            lerp(
+                self.voxel(x, y + dy, z + dz),
+                self.voxel(x + 1, y + dy, z + dz),
+                tx,
+            )
+        };
+
+        lerp(
+            lerp(row(0, 0), row(1, 0), ty),
+            lerp(row(0, 1), row(1, 1), ty),
+            tz,
+        )
+    }
+}
+
+impl TryFrom<DensityGridData> for DensityGrid {
+    type Error = String;
+
+    fn try_from(item: DensityGridData) -> Result<DensityGrid, String> {
+        let data = VOLUME_FILES
+            .lock()
+            .unwrap()
+            .get(&item.file)
+            .cloned()
+            .ok_or(format!("Volume file {:?} is not loaded", item.file))?;
+
+        DensityGrid::new(&item.file, item.resolution, &data)
+    }
+}
+
+impl From<DensityGrid> for DensityGridData {
+    fn from(item: DensityGrid) -> DensityGridData {
+        DensityGridData {
+            file: item.file,
+            resolution: item.resolution,
+        }
+    }
+}
+
+// --------------------------------------------------
+
+// Density between 0 and 1 at every point, scaled by the medium's max density
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum DensityField {
+    // Fractal noise, features are about 1 / frequency big. Noise below the threshold is empty and
+    // the rest is stretched back to 0..1, higher thresholds leave gaps like between clouds
+    Noise {
+        origin: Vec3D<f32>,
+        frequency: f32,
+        octaves: u32,
+        #[serde(default)]
+        threshold: f32,
+    },
+    // Stretched over the box from origin to origin + size, empty outside of it
+    Grid {
+        origin: Vec3D<f32>,
+        size: Vec3D<f32>,
+        grid: Arc<DensityGrid>,
+    },
+}
+
+impl DensityField {
+    pub fn density(&self, p: Vec3D<f32>) -> f32 {
+        match self {
+            DensityField::Noise {
+                origin,
+                frequency,
+                octaves,
+                threshold,
+            } => {
+                let noise = fbm_normalized((p - *origin) * *frequency, *octaves);
+                ((noise - threshold) / (1.0 - threshold).max(f32::EPSILON)).clamp(0.0, 1.0)
+            }
+            DensityField::Grid { origin, size, grid } => {
+                let p = p - *origin;
+                grid.sample(Vec3D::new(p.x / size.x, p.y / size.y, p.z / size.z))
+            }
+        }
+    }
+
+    pub fn translated(&self, offset: Vec3D<f32>) -> DensityField {
+        match self {
+            DensityField::Noise {
+                origin,
+                frequency,
+                octaves,
+                threshold,
+            } => DensityField::Noise {
+                origin: *origin + offset,
+                frequency: *frequency,
+                octaves: *octaves,
+                threshold: *threshold,
+            },
+            DensityField::Grid { origin, size, grid } => DensityField::Grid {
+                origin: *origin + offset,
+                size: *size,
+                grid: grid.clone(),
+            },
+        }
+    }
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{a} != {b}");
+    }
+
+    #[test]
+    fn byte_depth_follows_the_file_size() {
+        let resolution = Vec3D::new(2, 1, 1);
+        let files = [vec![0, 255], vec![0, 0, 255, 255], floats(&[0.0, 1.0])];
+
+        for data in files {
+            let grid = DensityGrid::new("test", resolution, &data).unwrap();
+            assert_eq!(grid.values, vec![0.0, 1.0]);
+        }
+        // 16 bit values are little endian
+        let grid = DensityGrid::new("test", resolution, &[0x00, 0x80, 0xff, 0x7f]).unwrap();
+        assert_close(grid.values[0], 0x8000 as f32 / u16::MAX as f32);
+        // Floats are kept between 0 and 1
+        let grid = DensityGrid::new("test", resolution, &floats(&[2.0, f32::NAN])).unwrap();
+        assert_eq!(grid.values, vec![1.0, 0.0]);
+    }
+
+    #[test]
+    fn sizes_that_dont_fit_are_rejected() {
+        let resolution = Vec3D::new(2, 2, 1);
+
+        for length in [0, 3, 5, 12, 17] {
+            assert!(DensityGrid::new("test", resolution, &vec![0; length]).is_err());
+        }
+        assert!(DensityGrid::new("test", Vec3D::new(0, 2, 2), &[]).is_err());
+    }
+
+    #[test]
+    fn samples_interpolate_between_voxel_centers() {
+        // Each voxel holds its index / 7
+        let values: Vec<f32> = (0..8).map(|i| i as f32 / 7.0).collect();
+        let grid = DensityGrid::new("test", Vec3D::new(2, 2, 2), &floats(&values)).unwrap();
+
+        assert_close(grid.sample(Vec3D::new(0.25, 0.25, 0.25)), 0.0);
+        assert_close(grid.sample(Vec3D::new(0.75, 0.25, 0.25)), 1.0 / 7.0);
+        assert_close(grid.sample(Vec3D::new(0.25, 0.75, 0.75)), 6.0 / 7.0);
+        // Half way between every voxel center
+        assert_close(grid.sample(Vec3D::new(0.5, 0.5, 0.5)), 0.5);
+        assert_close(grid.sample(Vec3D::new(0.5, 0.25, 0.25)), 0.5 / 7.0);
+    }
+
+    #[test]
+    fn samples_fade_out_at_the_edges() {
+        let grid = DensityGrid::new("test", Vec3D::new(2, 1, 1), &[255, 255]).unwrap();
+
+        // Inside the middle of the first voxel, on the grid's edges and outside of it
+        assert_close(grid.sample(Vec3D::new(0.25, 0.5, 0.5)), 1.0);
+        assert_close(grid.sample(Vec3D::new(0.0, 0.5, 0.5)), 0.5);
+        assert_close(grid.sample(Vec3D::new(1.0, 0.5, 0.5)), 0.5);
+        assert_close(grid.sample(Vec3D::new(0.25, 0.0, 0.5)), 0.5);
+        assert_close(grid.sample(Vec3D::new(-0.5, 0.5, 0.5)), 0.0);
+        assert_close(grid.sample(Vec3D::new(0.5, 0.5, 2.0)), 0.0);
+    }
+
+    #[test]
+    fn grids_load_registered_files() {
+        register_volume_file("volume_test.raw", vec![0, 128, 255]);
+
+        let grid: DensityGrid =
+            serde_yaml::from_str("{ file: volume_test.raw, resolution: { x: 1, y: 1, z: 3 } }")
+                .unwrap();
+        assert_close(grid.sample(Vec3D::new(0.5, 0.5, 0.5)), 128.0 / 255.0);
+        assert!(serde_yaml::from_str::<DensityGrid>(
+            "{ file: missing.raw, resolution: { x: 1, y: 1, z: 3 } }"
+        )
+        .is_err());
+    }
+}
@@ -1,39 +1,125 @@
-use std::ops::Index;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ops::{Add, Index, Mul};
+use std::sync::OnceLock;
 
-use serde::{ser::SerializeTuple, Deserialize, Deserializer, Serialize, Serializer};
+use serde::{
+    ser::{SerializeStruct, SerializeTuple},
+    Deserialize, Serialize, Serializer,
+};
 
-use super::{get_const_image, ImageID};
+use super::{get_image, register_image, ImageSource};
 use crate::draw::Color;
+use crate::graphics::WrapMode;
 use crate::vector::Vec2D;
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+thread_local! {
+    // Names of the images embedded so far while writing a self contained file, None otherwise
+    static EMBEDDED: RefCell<Option<HashSet<String>>> = const { RefCell::new(None) };
+}
+
+// Runs the serialization with named images embedded as base64 .png, each one at its first use -
+// the later uses are read after it and find it registered. Built-in images are only named
+pub fn with_embedded_images<T>(serialize: impl FnOnce() -> T) -> T {
+    EMBEDDED.with(|embedded| *embedded.borrow_mut() = Some(HashSet::new()));
+    let out = serialize();
+    EMBEDDED.with(|embedded| *embedded.borrow_mut() = None);
+
+    out
+}
+
+// Half the size of the level before it, the full image is level 0
+#[derive(Clone, Debug, PartialEq)]
+struct MipLevel {
+    size: Vec2D<u32>,
+    pixels: Vec<Color>,
+}
+
+// Scene files only name the image, the pixels and size are taken from the registered one - or
+// from the embedded .png, which gets registered under the name
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "ImageDataFile")]
 pub struct ImageData {
-    #[serde(deserialize_with = "data_from_file")]
-    #[serde(serialize_with = "data_to_file")]
-    pub data: (Vec<Color>, ImageID),
+    pub data: (Vec<Color>, ImageSource),
     pub size: Vec2D<u32>,
+    // One per pixel for images with transparency, empty for opaque ones
+    #[serde(skip)]
+    alpha: Vec<u8>,
+    // Levels from 1 down to 1x1, built on the first lookup that needs them
+    #[serde(skip)]
+    mips: OnceLock<Vec<MipLevel>>,
+}
+
+// The size written next to the data is ignored
+#[derive(Deserialize)]
+struct ImageDataFile {
+    data: (Vec<Color>, ImageSource),
+    #[serde(default)]
+    embedded: Option<String>,
+}
+
+impl TryFrom<ImageDataFile> for ImageData {
+    type Error = String;
+
+    fn try_from(item: ImageDataFile) -> Result<ImageData, String> {
+        if let Some(embedded) = item.embedded {
+            let data = base64::decode(embedded)
+                .map_err(|e| format!("Invalid embedded image {:}: {e:}", item.data.1))?;
+            return Ok((*register_image(ImageData::decode(item.data.1, &data)?)).clone());
+        }
+
+        match get_image(&item.data.1) {
+            Some(image) => Ok((*image).clone()),
+            None => Err(format!("Image {:?} is not loaded", item.data.1.to_string())),
+        }
+    }
 }
 
-fn data_from_file<'de, D>(deserializer: D) -> Result<(Vec<Color>, ImageID), D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let images: (Vec<Color>, ImageID) = Deserialize::deserialize(deserializer)?;
-    Ok((get_const_image(images.1.clone()).data.0.clone(), images.1).clone())
+struct DataFile<'a>(&'a ImageSource);
+
+impl Serialize for DataFile<'_> {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut tup = s.serialize_tuple(2)?;
+        tup.serialize_element(&Vec::<Color>::new())?;
+        tup.serialize_element(self.0)?;
+        tup.end()
+    }
 }
 
-fn data_to_file<S>(x: &(Vec<Color>, ImageID), s: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    let mut tup = s.serialize_tuple(2)?;
-    tup.serialize_element(&Vec::<Color>::new())?;
-    tup.serialize_element(&x.1)?;
-    tup.end()
+impl Serialize for ImageData {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let embed = match &self.data.1 {
+            ImageSource::Named(name) => EMBEDDED.with(|embedded| {
+                embedded
+                    .borrow_mut()
+                    .as_mut()
+                    .is_some_and(|embedded| embedded.insert(name.clone()))
+            }),
+            ImageSource::BuiltIn(_) => false,
+        };
+
+        let mut out = s.serialize_struct("ImageData", if embed { 3 } else { 2 })?;
+        out.serialize_field("data", &DataFile(&self.data.1))?;
+        out.serialize_field("size", &self.size)?;
+        if embed {
+            out.serialize_field(
+                "embedded",
+                &base64::encode(self.encode_png().map_err(serde::ser::Error::custom)?),
+            )?;
+        }
+        out.end()
+    }
 }
 
 impl ImageData {
-    pub(super) fn new(image_id: ImageID, width: u32, data: Vec<u8>) -> ImageData {
+    // Data is packed rgb, 3 bytes per pixel
+    pub fn new(source: impl Into<ImageSource>, width: u32, data: Vec<u8>) -> ImageData {
         let out_len = (data.len() / 3) as u32;
         assert!(out_len % width == 0, "Data length and width don't match up");
 
@@ -43,12 +129,185 @@ impl ImageData {
                     .into_iter()
                     .map(|x| Color::new(data[x * 3], data[x * 3 + 1], data[x * 3 + 2]))
                     .collect(),
-                image_id,
+                source.into(),
             ),
             size: Vec2D::new(
                 width,
                 ((data.len() / 3) / (width as usize)).try_into().unwrap(),
             ),
+            alpha: vec![],
+            mips: OnceLock::new(),
+        }
+    }
+
+    // One alpha byte per pixel
+    pub fn with_alpha(mut self, alpha: Vec<u8>) -> ImageData {
+        assert!(
+            alpha.len() == self.data.0.len(),
+            "Alpha length and pixel count don't match up"
+        );
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        !self.alpha.is_empty()
+    }
+
+    // .png or .jpeg files, the format is told by the data
+    pub fn decode(source: impl Into<ImageSource>, data: &[u8]) -> Result<ImageData, String> {
+        let source = source.into();
+        let decoded = ::image::load_from_memory(data)
+            .map_err(|e| format!("Failed decoding image {:}: {e:}", source))?;
+
+        if decoded.color().has_alpha() {
+            let decoded = decoded.to_rgba8();
+            let (rgb, alpha) = decoded.pixels().fold(
+                (vec![], vec![]),
+                |(mut rgb, mut alpha): (Vec<u8>, Vec<u8>), pixel| {
+                    rgb.extend_from_slice(&pixel.0[..3]);
+                    alpha.push(pixel.0[3]);
+                    (rgb, alpha)
+                },
+            );
+            Ok(ImageData::new(source, decoded.width(), rgb).with_alpha(alpha))
+        } else {
+            let decoded = decoded.to_rgb8();
+            Ok(ImageData::new(source, decoded.width(), decoded.into_raw()))
+        }
+    }
+
+    // With an alpha channel if the image has one
+    fn encode_png(&self) -> Result<Vec<u8>, String> {
+        let error = || format!("Image {:} has the wrong number of pixels", self.data.1);
+        let pixels = self
+            .data
+            .0
+            .iter()
+            // Rounded, truncating would darken the image a little on every save
+            .map(|pixel| [pixel.0.x, pixel.0.y, pixel.0.z].map(|c| (c * 255.0).round() as u8));
+        let image = if self.has_alpha() {
+            let pixels = pixels
+                .zip(&self.alpha)
+                .flat_map(|([r, g, b], a)| [r, g, b, *a])
+                .collect();
+            ::image::DynamicImage::ImageRgba8(
+                ::image::RgbaImage::from_raw(self.size.x, self.size.y, pixels).ok_or_else(error)?,
+            )
+        } else {
+            ::image::DynamicImage::ImageRgb8(
+                ::image::RgbImage::from_raw(self.size.x, self.size.y, pixels.flatten().collect())
+                    .ok_or_else(error)?,
+            )
+        };
+
+        let mut out = std::io::Cursor::new(vec![]);
+        image
+            .write_to(&mut out, ::image::ImageOutputFormat::Png)
+            .map_err(|e| format!("Failed encoding image {:}: {e:}", self.data.1))?;
+        Ok(out.into_inner())
+    }
+
+    // Every level averages 2x2 pixels of the one before it
+    fn build_mips(&self) -> Vec<MipLevel> {
+        let mut out: Vec<MipLevel> = vec![];
+        let (mut size, mut pixels) = (self.size, &self.data.0);
+
+        while size.x > 1 || size.y > 1 {
+            let next_size = Vec2D::new((size.x / 2).max(1), (size.y / 2).max(1));
+            let pixel = |x: u32, y: u32| {
+                pixels[(y.min(size.y - 1) * size.x + x.min(size.x - 1)) as usize].0
+            };
+            let next_pixels = (0..next_size.y)
+                .flat_map(|y| (0..next_size.x).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    Color(
+                        (pixel(x * 2, y * 2)
+                            + pixel(x * 2 + 1, y * 2)
+                            + pixel(x * 2, y * 2 + 1)
+                            + pixel(x * 2 + 1, y * 2 + 1))
+                            / 4.0,
+                    )
+                })
+                .collect();
+
+            out.push(MipLevel {
+                size: next_size,
+                pixels: next_pixels,
+            });
+            size = next_size;
+            pixels = &out.last().unwrap().pixels;
+        }
+
+        out
+    }
+
+    // Bilinear interpolation between pixel centers, past the edges the wrap mode picks the
+    // neighbouring pixels. Pixels are looked up by their index
+    fn bilinear<T>(
+        size: Vec2D<u32>,
+        index: Vec2D<f32>,
+        wrap: WrapMode,
+        pixel: impl Fn(usize) -> T,
+    ) -> T
+    where
+        T: Copy + Add<Output = T> + Mul<f32, Output = T>,
+    {
+        let (x, y) = (
+            index.x.clamp(0.0, 1.0) * size.x as f32 - 0.5,
+            index.y.clamp(0.0, 1.0) * size.y as f32 - 0.5,
+        );
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+
+        let wrapped = |p: f32, size: u32| {
+            ((wrap.wrap((p + 0.5) / size as f32) * size as f32) as u32).min(size - 1)
+        };
+        let pixel =
+            |x: f32, y: f32| pixel((wrapped(y, size.y) * size.x + wrapped(x, size.x)) as usize);
+
+        (pixel(x0, y0) * (1.0 - tx) + pixel(x0 + 1.0, y0) * tx) * (1.0 - ty)
+            + (pixel(x0, y0 + 1.0) * (1.0 - tx) + pixel(x0 + 1.0, y0 + 1.0) * tx) * ty
+    }
+
+    // Filtered lookup, the footprint is the size of the area to average in the same units as
+    // the index (0 to 1 over the image). Levels are picked so a pixel is about that size and
+    // blended between. A footprint of 0 only interpolates bilinearly
+    pub fn sample(&self, index: Vec2D<f32>, footprint: f32, wrap: WrapMode) -> Color {
+        let level = (footprint * self.size.x.max(self.size.y) as f32)
+            .max(1.0)
+            .log2();
+        if level <= 0.0 {
+            return Color(ImageData::bilinear(self.size, index, wrap, |i| {
+                self.data.0[i].0
+            }));
+        }
+
+        let mips = self.mips.get_or_init(|| self.build_mips());
+        let level = level.min(mips.len() as f32);
+        let sample_level = |level: usize| match level {
+            0 => ImageData::bilinear(self.size, index, wrap, |i| self.data.0[i].0),
+            _ => ImageData::bilinear(mips[level - 1].size, index, wrap, |i| {
+                mips[level - 1].pixels[i].0
+            }),
+        };
+
+        let (low, t) = (level.floor(), level.fract());
+        if t == 0.0 {
+            Color(sample_level(low as usize))
+        } else {
+            Color(sample_level(low as usize) * (1.0 - t) + sample_level(low as usize + 1) * t)
+        }
+    }
+
+    // Interpolated bilinearly, opaque images are 1 everywhere
+    pub fn alpha(&self, index: Vec2D<f32>, wrap: WrapMode) -> f32 {
+        if self.has_alpha() {
+            ImageData::bilinear(self.size, index, wrap, |i| {
+                self.alpha[i] as f32 / u8::MAX as f32
+            })
+        } else {
+            1.0
         }
     }
 }
@@ -81,3 +340,146 @@ impl Index<Vec2D<f32>> for ImageData {
         &self[index_u32]
     }
 }
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WRAP_MODES: [WrapMode; 3] = [WrapMode::Clamp, WrapMode::Repeat, WrapMode::Mirror];
+
+    fn gray_image(name: &str, width: u32, values: &[u8]) -> ImageData {
+        let data = values.iter().flat_map(|value| [*value; 3]).collect();
+        ImageData::new(ImageSource::Named(name.to_string()), width, data)
+    }
+
+    fn assert_gray(color: Color, value: f32) {
+        for channel in [color.0.x, color.0.y, color.0.z] {
+            assert!((channel - value).abs() < 1e-4, "{color:?} isn't {value}");
+        }
+    }
+
+    #[test]
+    fn constant_images_sample_the_same_everywhere() {
+        let image = gray_image("constant", 5, &[100; 15]);
+
+        for wrap in WRAP_MODES {
+            for footprint in [0.0, 0.05, 0.3, 0.5, 1.0, 100.0] {
+                for index in [(0.0, 0.0), (0.3, 0.7), (1.0, 0.5), (0.99, 1.0)] {
+                    let color = image.sample(Vec2D::new(index.0, index.1), footprint, wrap);
+                    assert_gray(color, 100.0 / 255.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn checker_averages_at_the_top_level() {
+        let image = gray_image("checker", 2, &[0, 255, 255, 0]);
+
+        // Bilinear lookups hit the pixel centers exactly
+        assert_gray(
+            image.sample(Vec2D::new(0.25, 0.25), 0.0, WrapMode::Clamp),
+            0.0,
+        );
+        assert_gray(
+            image.sample(Vec2D::new(0.75, 0.25), 0.0, WrapMode::Clamp),
+            1.0,
+        );
+        assert_gray(
+            image.sample(Vec2D::new(0.5, 0.5), 0.0, WrapMode::Clamp),
+            0.5,
+        );
+        // A footprint as big as the image only sees the average
+        for footprint in [1.0, 4.0] {
+            assert_gray(
+                image.sample(Vec2D::new(0.25, 0.25), footprint, WrapMode::Clamp),
+                0.5,
+            );
+        }
+        // Half way between the levels
+        let footprint = 2.0f32.sqrt() / 2.0;
+        assert_gray(
+            image.sample(Vec2D::new(0.25, 0.25), footprint, WrapMode::Clamp),
+            0.25,
+        );
+    }
+
+    #[test]
+    fn edges_of_every_level_follow_the_wrap_mode() {
+        // A ramp from black to white, its first level is 1/6 and 5/6
+        let image = gray_image("ramp", 4, &[0, 85, 170, 255]);
+        let sample = |x: f32, footprint: f32, wrap: WrapMode| {
+            image.sample(Vec2D::new(x, 0.5), footprint, wrap)
+        };
+
+        // Halfway between the edge pixel and the one next to it past the edge
+        for (footprint, first, last) in [(0.0, 0.0, 1.0), (0.5, 1.0 / 6.0, 5.0 / 6.0)] {
+            assert_gray(sample(0.0, footprint, WrapMode::Clamp), first);
+            assert_gray(sample(1.0, footprint, WrapMode::Clamp), last);
+            assert_gray(
+                sample(0.0, footprint, WrapMode::Repeat),
+                (first + last) / 2.0,
+            );
+            assert_gray(
+                sample(1.0, footprint, WrapMode::Repeat),
+                (first + last) / 2.0,
+            );
+            assert_gray(sample(0.0, footprint, WrapMode::Mirror), first);
+            assert_gray(sample(1.0, footprint, WrapMode::Mirror), last);
+        }
+        // Inside the image the wrap mode doesn't matter
+        for wrap in WRAP_MODES {
+            assert_gray(sample(0.5, 0.0, wrap), 0.5);
+        }
+    }
+
+    #[test]
+    fn embedded_images_load_without_the_registered_one() {
+        use crate::graphics::{Material, Texture};
+
+        let name = "round_trip.png";
+        let image = ImageData::new(
+            ImageSource::Named(name.to_string()),
+            2,
+            vec![255, 0, 0, 0, 255, 0, 0, 0, 255, 10, 20, 30],
+        )
+        .with_alpha(vec![255, 128, 0, 64]);
+        let texture = Texture::Image {
+            data: register_image(image.clone()),
+            uv: Default::default(),
+        };
+        // The second use only names the image
+        let materials = vec![
+            Material::Lambertian {
+                texture: texture.clone(),
+            },
+            Material::Metal { texture, fuzz: 0.5 },
+        ];
+        let yaml = with_embedded_images(|| serde_yaml::to_string(&materials)).unwrap();
+        assert_eq!(yaml.matches("embedded:").count(), 1);
+
+        // Something else registered under the name is replaced by the embedded image
+        register_image(gray_image(name, 1, &[0]));
+        let loaded: Vec<Material> = serde_yaml::from_str(&yaml).unwrap();
+        for material in &loaded {
+            match material.texture() {
+                Some(Texture::Image { data, .. }) => {
+                    assert_eq!(data.data, image.data);
+                    assert_eq!(data.size, image.size);
+                    assert_eq!(data.alpha, image.alpha);
+                }
+                texture => panic!("{texture:?} isn't an image"),
+            }
+        }
+        assert_eq!(
+            *get_image(&ImageSource::Named(name.to_string())).unwrap(),
+            image
+        );
+
+        // Without embedding the file only names the image
+        let yaml = serde_yaml::to_string(&materials).unwrap();
+        assert!(!yaml.contains("embedded:"));
+    }
+}
@@ -1,5 +1,7 @@
 mod image_data;
 mod image_consts;
+mod registry;
 
 pub use image_consts::{ImageID, get_const_image};
-pub use image_data::ImageData; 
+pub use image_data::{with_embedded_images, ImageData};
+pub use registry::{get_image, image_names, register_image, ImageSource};
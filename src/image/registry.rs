@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use super::{get_const_image, ImageData, ImageID};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+// --------------------------------------------------
+
+// Built-in images are baked in by build.rs, named ones are registered at runtime (e.g. by the
+// importers). Plain IDs are tried first, so old scene files keep loading
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum ImageSource {
+    BuiltIn(ImageID),
+    Named(String),
+}
+
+impl fmt::Display for ImageSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageSource::BuiltIn(id) => write!(f, "{id:}"),
+            ImageSource::Named(name) => write!(f, "{name:}"),
+        }
+    }
+}
+
+impl From<ImageID> for ImageSource {
+    fn from(item: ImageID) -> ImageSource {
+        ImageSource::BuiltIn(item)
+    }
+}
+
+// --------------------------------------------------
+
+lazy_static! {
+    static ref IMAGES: Mutex<HashMap<String, Arc<ImageData>>> = Mutex::new(HashMap::new());
+}
+
+// Replaces any image registered under the same name
+pub fn register_image(image: ImageData) -> Arc<ImageData> {
+    let image = Arc::new(image);
+    IMAGES
+        .lock()
+        .unwrap()
+        .insert(image.data.1.to_string(), image.clone());
+
+    image
+}
+
+pub fn get_image(source: &ImageSource) -> Option<Arc<ImageData>> {
+    match source {
+        ImageSource::BuiltIn(id) => Some(get_const_image(id.clone())),
+        ImageSource::Named(name) => IMAGES.lock().unwrap().get(name).cloned(),
+    }
+}
+
+pub fn image_names() -> Vec<String> {
+    IMAGES.lock().unwrap().keys().cloned().collect()
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str) -> ImageSource {
+        ImageSource::Named(name.to_string())
+    }
+
+    #[test]
+    fn sources_deserialize_from_plain_names() {
+        let source: ImageSource = serde_yaml::from_str("Brick").unwrap();
+        assert_eq!(source, ImageSource::BuiltIn(ImageID::Brick));
+        let source: ImageSource = serde_yaml::from_str("wall.png").unwrap();
+        assert_eq!(source, named("wall.png"));
+        // Built-in IDs win over names that look the same
+        let source: ImageSource = serde_yaml::from_str("Mars").unwrap();
+        assert_eq!(source, ImageSource::BuiltIn(ImageID::Mars));
+
+        for source in [ImageSource::BuiltIn(ImageID::Sun), named("texture_1.jpg")] {
+            let yaml = serde_yaml::to_string(&source).unwrap();
+            assert_eq!(serde_yaml::from_str::<ImageSource>(&yaml).unwrap(), source);
+        }
+    }
+
+    #[test]
+    fn registered_images_can_be_looked_up() {
+        let image = ImageData::new(named("registry_test.png"), 2, vec![10, 20, 30, 40, 50, 60]);
+        let registered = register_image(image.clone());
+
+        let found = get_image(&named("registry_test.png")).unwrap();
+        assert!(Arc::ptr_eq(&found, &registered));
+        assert_eq!(*found, image);
+        assert!(image_names().contains(&"registry_test.png".to_string()));
+
+        // Registering again replaces the image
+        register_image(ImageData::new(named("registry_test.png"), 1, vec![1, 2, 3]));
+        assert_eq!(get_image(&named("registry_test.png")).unwrap().size.x, 1);
+    }
+
+    #[test]
+    fn unknown_names_are_not_found() {
+        assert!(get_image(&named("registry_missing.png")).is_none());
+
+        let error = serde_yaml::from_str::<ImageData>("{ data: [[], registry_missing.png] }")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("Image \"registry_missing.png\" is not loaded"),
+            "{error}"
+        );
+    }
+}
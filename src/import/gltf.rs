@@ -0,0 +1,1068 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use super::mesh_object;
+use crate::draw::Color;
+use crate::graphics::{
+    Material, Object3D, ObjectList, Projection, Scene, SurfaceMap, Texture, UvTransform, WrapMode,
+};
+use crate::image::{register_image, ImageData, ImageSource};
+use crate::vector::{Mat4, Vec2D, Vec3D, VectorOperation};
+use serde::Deserialize;
+
+// --------------------------------------------------
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_CHUNK_JSON: u32 = 0x4E4F534A;
+const GLB_CHUNK_BIN: u32 = 0x004E4942;
+
+const MODE_TRIANGLES: u32 = 4;
+
+// Sampler wrap modes, anything else repeats like the default
+const CLAMP_TO_EDGE: u32 = 33071;
+const MIRRORED_REPEAT: u32 = 33648;
+
+const DEFAULT_VFOV: f32 = 90.0;
+const DEFAULT_REFRACTION_INDEX: f32 = 1.5;
+
+// We have no punctual lights, they become small glowing spheres instead - bright enough to give off
+// the light's intensity (candela for point lights, lux at the scene for directional ones)
+const POINT_LIGHT_RADIUS: f32 = 0.1;
+const DIRECTIONAL_LIGHT_DISTANCE: f32 = 1000.0;
+const DIRECTIONAL_LIGHT_RADIUS: f32 = 50.0;
+
+// --------------------------------------------------
+
+// Only the parts of the format that map onto our scenes are read
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Gltf {
+    scene: Option<usize>,
+    #[serde(default)]
+    scenes: Vec<GltfScene>,
+    #[serde(default)]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    meshes: Vec<GltfMesh>,
+    #[serde(default)]
+    accessors: Vec<Accessor>,
+    #[serde(default)]
+    buffer_views: Vec<BufferView>,
+    #[serde(default)]
+    buffers: Vec<Buffer>,
+    #[serde(default)]
+    materials: Vec<GltfMaterial>,
+    #[serde(default)]
+    textures: Vec<GltfTexture>,
+    #[serde(default)]
+    samplers: Vec<Sampler>,
+    #[serde(default)]
+    images: Vec<GltfImage>,
+    #[serde(default)]
+    cameras: Vec<GltfCamera>,
+    #[serde(default)]
+    extensions: RootExtensions,
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfScene {
+    #[serde(default)]
+    nodes: Vec<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Node {
+    #[serde(default)]
+    children: Vec<usize>,
+    mesh: Option<usize>,
+    camera: Option<usize>,
+    matrix: Option<[f32; 16]>,
+    translation: Option<[f32; 3]>,
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+    #[serde(default)]
+    extensions: NodeExtensions,
+}
+
+impl Node {
+    fn local_matrix(&self) -> Mat4 {
+        match &self.matrix {
+            Some(matrix) => Mat4::from_cols_array(matrix),
+            None => {
+                let [tx, ty, tz] = self.translation.unwrap_or([0.0, 0.0, 0.0]);
+                let [sx, sy, sz] = self.scale.unwrap_or([1.0, 1.0, 1.0]);
+
+                Mat4::from_translation_rotation_scale(
+                    Vec3D::new(tx, ty, tz),
+                    self.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]),
+                    Vec3D::new(sx, sy, sz),
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct NodeExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    light: Option<NodeLight>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeLight {
+    light: usize,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RootExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    lights: Option<Lights>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Lights {
+    #[serde(default)]
+    lights: Vec<Light>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Light {
+    #[serde(rename = "type")]
+    kind: String,
+    color: Option<[f32; 3]>,
+    #[serde(default = "default_intensity")]
+    intensity: f32,
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfMesh {
+    primitives: Vec<Primitive>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Primitive {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    mode: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Buffer {
+    uri: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfMaterial {
+    #[serde(default)]
+    pbr_metallic_roughness: PbrMetallicRoughness,
+    emissive_factor: Option<[f32; 3]>,
+    normal_texture: Option<TextureInfo>,
+    alpha_mode: Option<String>,
+    alpha_cutoff: Option<f32>,
+    #[serde(default)]
+    extensions: MaterialExtensions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PbrMetallicRoughness {
+    base_color_factor: Option<[f32; 4]>,
+    base_color_texture: Option<TextureInfo>,
+    metallic_factor: Option<f32>,
+    roughness_factor: Option<f32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct MaterialExtensions {
+    #[serde(rename = "KHR_materials_transmission")]
+    transmission: Option<Transmission>,
+    #[serde(rename = "KHR_materials_ior")]
+    ior: Option<Ior>,
+    #[serde(rename = "KHR_materials_emissive_strength")]
+    emissive_strength: Option<EmissiveStrength>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Transmission {
+    #[serde(default)]
+    transmission_factor: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ior {
+    ior: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EmissiveStrength {
+    emissive_strength: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TextureInfo {
+    index: usize,
+    // Only normal textures have one
+    scale: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfTexture {
+    source: Option<usize>,
+    sampler: Option<usize>,
+}
+
+// Only the horizontal wrap mode is used, textures have one for both directions
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Sampler {
+    wrap_s: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfImage {
+    name: Option<String>,
+    uri: Option<String>,
+    buffer_view: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfCamera {
+    #[serde(rename = "type")]
+    kind: String,
+    perspective: Option<Perspective>,
+    orthographic: Option<Orthographic>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Perspective {
+    yfov: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Orthographic {
+    ymag: f32,
+}
+
+// --------------------------------------------------
+
+struct Importer<'a> {
+    name: &'a str,
+    gltf: Gltf,
+    buffers: Vec<Vec<u8>>,
+    resource: &'a dyn Fn(&str) -> Option<Vec<u8>>,
+    images: HashMap<usize, image::RgbaImage>,
+    materials: HashMap<usize, Arc<Material>>,
+}
+
+impl<'a> Importer<'a> {
+    // A data uri, a file given by the caller or the binary chunk of a .glb
+    fn load_uri(&self, uri: &str) -> Result<Vec<u8>, String> {
+        if let Some(data) = uri.strip_prefix("data:") {
+            let (_, data) = data
+                .split_once("base64,")
+                .ok_or("Only base64 data uris are supported")?;
+            base64::decode(data).map_err(|e| format!("Invalid base64 data: {e:}"))
+        } else {
+            (self.resource)(uri).ok_or_else(|| format!("Missing file {uri:?}"))
+        }
+    }
+
+    fn buffer_view(&self, index: usize) -> Result<&[u8], String> {
+        let view = self
+            .gltf
+            .buffer_views
+            .get(index)
+            .ok_or_else(|| format!("Buffer view {index:} does not exist"))?;
+        self.buffers
+            .get(view.buffer)
+            .and_then(|buffer| buffer.get(view.byte_offset..(view.byte_offset + view.byte_length)))
+            .ok_or_else(|| format!("Buffer view {index:} is out of bounds"))
+    }
+
+    // Values stay unnormalized, f64 holds every component type exactly
+    fn read_accessor(&self, index: usize) -> Result<(Vec<f64>, usize), String> {
+        let accessor = self
+            .gltf
+            .accessors
+            .get(index)
+            .ok_or_else(|| format!("Accessor {index:} does not exist"))?;
+
+        let components = match accessor.kind.as_str() {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" => 4,
+            kind => return Err(format!("Unsupported accessor type {kind:}")),
+        };
+        let (component_size, max) = match accessor.component_type {
+            5120 => (1, i8::MAX as f64),
+            5121 => (1, u8::MAX as f64),
+            5122 => (2, i16::MAX as f64),
+            5123 => (2, u16::MAX as f64),
+            5125 => (4, u32::MAX as f64),
+            5126 => (4, 1.0),
+            kind => return Err(format!("Unsupported component type {kind:}")),
+        };
+
+        let buffer_view = match accessor.buffer_view {
+            Some(view) => view,
+            None => return Ok((vec![0.0; accessor.count * components], components)),
+        };
+        let data = self.buffer_view(buffer_view)?;
+        let stride = self.gltf.buffer_views[buffer_view]
+            .byte_stride
+            .unwrap_or(component_size * components);
+
+        let mut out = Vec::with_capacity(accessor.count * components);
+        for element in 0..accessor.count {
+            for component in 0..components {
+                let offset = accessor.byte_offset + element * stride + component * component_size;
+                let bytes = data
+                    .get(offset..(offset + component_size))
+                    .ok_or_else(|| format!("Accessor {index:} is out of bounds"))?;
+
+                let value = match accessor.component_type {
+                    5120 => i8::from_le_bytes([bytes[0]]) as f64,
+                    5121 => bytes[0] as f64,
+                    5122 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    5123 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    5125 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                    _ => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                };
+                out.push(if accessor.normalized {
+                    (value / max).max(-1.0)
+                } else {
+                    value
+                });
+            }
+        }
+
+        Ok((out, components))
+    }
+
+    fn read_vec3ds(&self, index: usize) -> Result<Vec<Vec3D<f32>>, String> {
+        match self.read_accessor(index)? {
+            (values, 3) => Ok(values
+                .chunks(3)
+                .map(|v| Vec3D::new(v[0] as f32, v[1] as f32, v[2] as f32))
+                .collect()),
+            _ => Err(format!("Accessor {index:} does not hold 3D vectors")),
+        }
+    }
+
+    fn read_vec2ds(&self, index: usize) -> Result<Vec<Vec2D<f32>>, String> {
+        match self.read_accessor(index)? {
+            (values, 2) => Ok(values
+                .chunks(2)
+                .map(|v| Vec2D::new(v[0] as f32, v[1] as f32))
+                .collect()),
+            _ => Err(format!("Accessor {index:} does not hold 2D vectors")),
+        }
+    }
+
+    fn image(&mut self, index: usize) -> Result<&image::RgbaImage, String> {
+        if !self.images.contains_key(&index) {
+            let image = self
+                .gltf
+                .images
+                .get(index)
+                .ok_or_else(|| format!("Image {index:} does not exist"))?;
+
+            let data = match (&image.uri, image.buffer_view) {
+                (Some(uri), _) => self.load_uri(uri)?,
+                (None, Some(view)) => self.buffer_view(view)?.to_vec(),
+                (None, None) => return Err(format!("Image {index:} has no data")),
+            };
+            let decoded = image::load_from_memory(&data)
+                .map_err(|e| format!("Failed decoding image {index:}: {e:}"))?
+                .to_rgba8();
+
+            self.images.insert(index, decoded);
+        }
+
+        Ok(&self.images[&index])
+    }
+
+    // The color factor is baked into the pixels, the alpha is kept if any pixel isn't opaque
+    fn image_texture(
+        &mut self,
+        material_index: usize,
+        texture_index: usize,
+        factor: [f32; 4],
+    ) -> Result<Texture, String> {
+        let texture = self.gltf.textures.get(texture_index);
+        let image_index = texture
+            .and_then(|texture| texture.source)
+            .ok_or_else(|| format!("Texture {texture_index:} has no image"))?;
+        let wrap = match texture
+            .and_then(|texture| texture.sampler)
+            .and_then(|sampler| self.gltf.samplers.get(sampler))
+            .and_then(|sampler| sampler.wrap_s)
+        {
+            Some(CLAMP_TO_EDGE) => WrapMode::Clamp,
+            Some(MIRRORED_REPEAT) => WrapMode::Mirror,
+            _ => WrapMode::Repeat,
+        };
+        let image_name = self.gltf.images[image_index]
+            .name
+            .clone()
+            .unwrap_or(format!("image_{image_index:}"));
+        let tinted = factor != [1.0, 1.0, 1.0, 1.0];
+
+        let name = if tinted {
+            format!("{:}/{image_name:}_material_{material_index:}", self.name)
+        } else {
+            format!("{:}/{image_name:}", self.name)
+        };
+        let image = self.image(image_index)?;
+        let pixels = image
+            .pixels()
+            .flat_map(|pixel| {
+                (0..3).map(move |channel| (pixel[channel] as f32 * factor[channel]) as u8)
+            })
+            .collect();
+        let alpha: Vec<u8> = image
+            .pixels()
+            .map(|pixel| (pixel[3] as f32 * factor[3]) as u8)
+            .collect();
+
+        let data = ImageData::new(ImageSource::Named(name), image.width(), pixels);
+        Ok(Texture::Image {
+            data: register_image(if alpha.iter().any(|alpha| *alpha < u8::MAX) {
+                data.with_alpha(alpha)
+            } else {
+                data
+            }),
+            uv: UvTransform {
+                wrap,
+                ..UvTransform::default()
+            },
+        })
+    }
+
+    // Emissive materials become lights, transmissive / blended ones glass, mostly metallic ones
+    // metal and everything else is diffuse
+    fn material(&mut self, index: usize) -> Result<Arc<Material>, String> {
+        if let Some(material) = self.materials.get(&index) {
+            return Ok(material.clone());
+        }
+
+        let material = self
+            .gltf
+            .materials
+            .get(index)
+            .ok_or_else(|| format!("Material {index:} does not exist"))?;
+        let pbr = &material.pbr_metallic_roughness;
+
+        let base_color = pbr.base_color_factor.unwrap_or([1.0, 1.0, 1.0, 1.0]);
+        let metallic = pbr.metallic_factor.unwrap_or(1.0);
+        let roughness = pbr.roughness_factor.unwrap_or(1.0);
+        let emissive_strength = material
+            .extensions
+            .emissive_strength
+            .as_ref()
+            .and_then(|strength| strength.emissive_strength)
+            .unwrap_or(1.0);
+        let emissive = material
+            .emissive_factor
+            .map(|[r, g, b]| Vec3D::new(r, g, b) * emissive_strength)
+            .unwrap_or_default();
+        let transmission = material
+            .extensions
+            .transmission
+            .as_ref()
+            .map_or(0.0, |transmission| transmission.transmission_factor);
+        let refraction_index = material
+            .extensions
+            .ior
+            .as_ref()
+            .and_then(|ior| ior.ior)
+            .unwrap_or(DEFAULT_REFRACTION_INDEX);
+        let blended = material.alpha_mode.as_deref() == Some("BLEND") && base_color[3] < 1.0;
+        let cutoff = match material.alpha_mode.as_deref() {
+            Some("MASK") => Some(material.alpha_cutoff.unwrap_or(0.5)),
+            _ => None,
+        };
+        let base_color_texture = pbr.base_color_texture.as_ref().map(|info| info.index);
+        let normal_texture = material
+            .normal_texture
+            .as_ref()
+            .map(|info| (info.index, info.scale.unwrap_or(1.0)));
+
+        let texture = match base_color_texture {
+            Some(texture_index) => self.image_texture(index, texture_index, base_color)?,
+            None => Texture::SolidColor {
+                color: Color::from_vec3d(Vec3D::new(base_color[0], base_color[1], base_color[2])),
+            },
+        };
+        // The alpha of the base color, images without one are opaque
+        let opacity = match &texture {
+            Texture::Image { data, .. } if !data.has_alpha() => None,
+            Texture::Image { .. } => Some(texture.clone()),
+            _ => Some(Texture::SolidColor {
+                color: Color::from_vec3d(Vec3D::new(1.0, 1.0, 1.0) * base_color[3]),
+            }),
+        };
+
+        let out = if emissive.x.max(emissive.y).max(emissive.z) > 0.0 {
+            Material::light(emissive)
+        } else if transmission > 0.0 || blended {
+            Material::Dielectric { refraction_index }
+        } else if metallic >= 0.5 {
+            Material::Metal {
+                texture,
+                fuzz: roughness.clamp(0.0, 1.0),
+            }
+        } else {
+            Material::Lambertian { texture }
+        };
+
+        // Normal maps use the same convention, green along v after the uvs are flipped
+        let out = match normal_texture {
+            Some((texture_index, scale)) => Material::Bumped {
+                material: Box::new(out),
+                map: SurfaceMap::Normal {
+                    texture: self.image_texture(index, texture_index, [1.0; 4])?,
+                    strength: scale,
+                },
+            },
+            None => out,
+        };
+        let out = Arc::new(match (cutoff, opacity) {
+            (Some(cutoff), Some(opacity)) => Material::Cutout {
+                material: Box::new(out),
+                opacity,
+                cutoff,
+            },
+            _ => out,
+        });
+
+        self.materials.insert(index, out.clone());
+        Ok(out)
+    }
+
+    // Every primitive becomes its own mesh, in world space
+    fn mesh_objects(&mut self, mesh: usize, world: &Mat4) -> Result<Vec<Object3D>, String> {
+        let normal_matrix = world.inverse().unwrap_or_default().transposed();
+        let primitive_count = self
+            .gltf
+            .meshes
+            .get(mesh)
+            .ok_or_else(|| format!("Mesh {mesh:} does not exist"))?
+            .primitives
+            .len();
+
+        let mut out = vec![];
+        for primitive_index in 0..primitive_count {
+            let primitive = &self.gltf.meshes[mesh].primitives[primitive_index];
+            if primitive.mode.unwrap_or(MODE_TRIANGLES) != MODE_TRIANGLES {
+                log::warn!("Skipping a primitive of mesh {mesh:}, only triangles are supported");
+                continue;
+            }
+
+            let position_accessor = *primitive
+                .attributes
+                .get("POSITION")
+                .ok_or_else(|| format!("A primitive of mesh {mesh:} has no positions"))?;
+            let normal_accessor = primitive.attributes.get("NORMAL").copied();
+            let uv_accessor = primitive.attributes.get("TEXCOORD_0").copied();
+            let color_accessor = primitive.attributes.get("COLOR_0").copied();
+            let (index_accessor, material_index) = (primitive.indices, primitive.material);
+
+            let positions: Vec<Vec3D<f32>> = self
+                .read_vec3ds(position_accessor)?
+                .into_iter()
+                .map(|p| world.transform_point(p))
+                .collect();
+            let normals: Vec<Vec3D<f32>> = match normal_accessor {
+                Some(accessor) => self
+                    .read_vec3ds(accessor)?
+                    .into_iter()
+                    .map(|n| normal_matrix.transform_vector(n).unit_vec())
+                    .collect(),
+                None => vec![],
+            };
+            // glTF puts the uv origin in the top left corner
+            let uvs: Vec<Vec2D<f32>> = match uv_accessor {
+                Some(accessor) => self
+                    .read_vec2ds(accessor)?
+                    .into_iter()
+                    .map(|uv| Vec2D::new(uv.x, 1.0 - uv.y))
+                    .collect(),
+                None => vec![],
+            };
+            // Alpha is dropped
+            let colors: Vec<Color> = match color_accessor {
+                Some(accessor) => match self.read_accessor(accessor)? {
+                    (values, components @ (3 | 4)) => values
+                        .chunks(components)
+                        .map(|c| Color::from_vec3d(Vec3D::new(c[0] as f32, c[1] as f32, c[2] as f32)))
+                        .collect(),
+                    _ => return Err(format!("Accessor {accessor:} does not hold colors")),
+                },
+                None => vec![],
+            };
+            let indices: Vec<u32> = match index_accessor {
+                Some(accessor) => self
+                    .read_accessor(accessor)?
+                    .0
+                    .into_iter()
+                    .map(|i| i as u32)
+                    .collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            let material = match material_index {
+                Some(material) => self.material(material)?,
+                None => Arc::new(Material::Lambertian {
+                    texture: Texture::SolidColor {
+                        color: Color::from_vec3d(Vec3D::new(1.0, 1.0, 1.0)),
+                    },
+                }),
+            };
+
+            // Without normals glTF asks for flat shading
+            let smooth = !normals.is_empty();
+
+            out.push(
+                mesh_object(
+                    positions,
+                    normals,
+                    uvs,
+                    colors,
+                    indices
+                        .chunks_exact(3)
+                        .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                        .collect(),
+                    smooth,
+                    material,
+                )
+                .map_err(|e| format!("Mesh {mesh:}: {e:}"))?,
+            );
+        }
+
+        Ok(out)
+    }
+
+    fn light_object(&self, index: usize, world: &Mat4) -> Result<Object3D, String> {
+        let light = self
+            .gltf
+            .extensions
+            .lights
+            .as_ref()
+            .and_then(|lights| lights.lights.get(index))
+            .ok_or_else(|| format!("Light {index:} does not exist"))?;
+        let [r, g, b] = light.color.unwrap_or([1.0, 1.0, 1.0]);
+
+        // A sphere looks like a disk of its radius from every side, so it shines with its
+        // radiance times the disk's area
+        let (pos, radius, strength) = if light.kind == "directional" {
+            // Lights shine along their -Z axis
+            let direction = world.transform_vector(Vec3D::new(0.0, 0.0, -1.0)).unit_vec();
+            (
+                -direction * DIRECTIONAL_LIGHT_DISTANCE,
+                DIRECTIONAL_LIGHT_RADIUS,
+                light.intensity * DIRECTIONAL_LIGHT_DISTANCE.powi(2),
+            )
+        } else {
+            if light.kind == "spot" {
+                log::warn!(
+                    "Spot light {index:} is imported as a point light, shining in all directions"
+                );
+            }
+            (
+                world.transform_point(Vec3D::new(0.0, 0.0, 0.0)),
+                POINT_LIGHT_RADIUS,
+                light.intensity,
+            )
+        };
+
+        Ok(Object3D::Sphere {
+            pos,
+            radius,
+            material: Arc::new(Material::light(
+                Vec3D::new(r, g, b) * (strength.max(0.0) / (PI * radius.powi(2))),
+            )),
+        })
+    }
+
+    fn import(mut self) -> Result<Scene, String> {
+        let roots = match self.gltf.scenes.get(self.gltf.scene.unwrap_or(0)) {
+            Some(scene) => scene.nodes.clone(),
+            // Without scenes every node that isn't a child is a root
+            None => (0..self.gltf.nodes.len())
+                .filter(|node| {
+                    !self
+                        .gltf
+                        .nodes
+                        .iter()
+                        .any(|parent| parent.children.contains(node))
+                })
+                .collect(),
+        };
+
+        let mut objects = vec![];
+        let mut camera = None;
+
+        // Nodes form trees, a node reached twice means a broken file that could loop forever
+        let mut visited = vec![false; self.gltf.nodes.len()];
+        let mut stack: Vec<(usize, Mat4)> = roots
+            .into_iter()
+            .rev()
+            .map(|node| (node, Mat4::identity()))
+            .collect();
+        while let Some((node_index, parent)) = stack.pop() {
+            let node = self
+                .gltf
+                .nodes
+                .get(node_index)
+                .ok_or_else(|| format!("Node {node_index:} does not exist"))?;
+            if std::mem::replace(&mut visited[node_index], true) {
+                return Err(format!(
+                    "Node {node_index:} is reached more than once, nodes must form trees"
+                ));
+            }
+            let world = parent * node.local_matrix();
+
+            let (mesh, node_camera, light) =
+                (node.mesh, node.camera, node.extensions.light.as_ref().map(|l| l.light));
+            stack.extend(node.children.iter().rev().map(|child| (*child, world)));
+
+            if let Some(mesh) = mesh {
+                objects.extend(self.mesh_objects(mesh, &world)?);
+            }
+            if let Some(light) = light {
+                objects.push(self.light_object(light, &world)?);
+            }
+            // The first camera in the hierarchy is used
+            if let (Some(node_camera), None) = (node_camera, &camera) {
+                camera = Some((node_camera, world));
+            }
+        }
+
+        let (vfov, projection) = match camera.and_then(|(camera, _)| self.gltf.cameras.get(camera)) {
+            Some(camera) if camera.kind == "orthographic" => (
+                DEFAULT_VFOV,
+                Projection::Orthographic {
+                    view_height: camera.orthographic.as_ref().map_or(1.0, |o| o.ymag) * 2.0,
+                },
+            ),
+            Some(camera) => (
+                camera
+                    .perspective
+                    .as_ref()
+                    .map_or(DEFAULT_VFOV, |p| p.yfov.to_degrees()),
+                Projection::Perspective,
+            ),
+            None => (DEFAULT_VFOV, Projection::Perspective),
+        };
+
+        let mut scene = Scene::new(
+            Vec2D::new(1, 1),
+            vfov,
+            projection,
+            None,
+            ObjectList::new(objects),
+        );
+        // Cameras look along their -Z axis with +Y up
+        if let Some((_, world)) = camera {
+            scene.camera.pos = world.transform_point(Vec3D::new(0.0, 0.0, 0.0));
+            scene.camera.look_at(
+                scene.camera.pos + world.transform_vector(Vec3D::new(0.0, 0.0, -1.0)),
+                world.transform_vector(Vec3D::new(0.0, 1.0, 0.0)),
+            );
+        }
+
+        Ok(scene)
+    }
+}
+
+// --------------------------------------------------
+
+// Splits a .glb into its json and binary chunk
+fn parse_glb(data: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    let read_u32 = |offset: usize| -> Result<u32, String> {
+        data.get(offset..(offset + 4))
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .ok_or_else(|| "Unexpected end of .glb file".to_string())
+    };
+
+    let length = (read_u32(8)? as usize).min(data.len());
+    let (mut json, mut bin) = (None, None);
+
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let (chunk_length, chunk_type) = (read_u32(offset)? as usize, read_u32(offset + 4)?);
+        let chunk = data
+            .get((offset + 8)..(offset + 8 + chunk_length))
+            .ok_or("Unexpected end of .glb file")?;
+
+        match chunk_type {
+            GLB_CHUNK_JSON => json = json.or(Some(chunk)),
+            GLB_CHUNK_BIN => bin = bin.or(Some(chunk)),
+            _ => {}
+        }
+        offset += 8 + chunk_length;
+    }
+
+    Ok((json.ok_or(".glb file has no json chunk")?, bin))
+}
+
+// Loads a .gltf or .glb file into a new scene. Files it refers to (buffers, images) are requested
+// through `resource`, imported textures are registered under "<name>/<image name>"
+pub fn import_gltf(
+    name: &str,
+    data: &[u8],
+    resource: &dyn Fn(&str) -> Option<Vec<u8>>,
+) -> Result<Scene, String> {
+    let (json, bin) = if data.starts_with(GLB_MAGIC) {
+        parse_glb(data)?
+    } else {
+        (data, None)
+    };
+    let gltf: Gltf = serde_json::from_slice(json).map_err(|e| format!("Invalid glTF: {e:}"))?;
+
+    let mut importer = Importer {
+        name,
+        gltf,
+        buffers: vec![],
+        resource,
+        images: HashMap::new(),
+        materials: HashMap::new(),
+    };
+    importer.buffers = importer
+        .gltf
+        .buffers
+        .iter()
+        .map(|buffer| match (&buffer.uri, bin) {
+            (Some(uri), _) => importer.load_uri(uri),
+            (None, Some(bin)) => Ok(bin.to_vec()),
+            (None, None) => Err("Buffer has no data".to_string()),
+        })
+        .collect::<Result<_, _>>()?;
+
+    importer.import()
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Mesh;
+
+    // One triangle: 3 float positions followed by 3 u16 indices
+    fn triangle_buffer() -> Vec<u8> {
+        let positions = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let indices = [0u16, 1, 2];
+
+        positions
+            .iter()
+            .flat_map(|p| p.to_le_bytes())
+            .chain(indices.iter().flat_map(|i| i.to_le_bytes()))
+            .collect()
+    }
+
+    fn gltf_json(buffer: &str, nodes: &str) -> String {
+        format!(
+            r#"{{
+  "asset": {{ "version": "2.0" }},
+  "scenes": [{{ "nodes": [0, 2, 3] }}],
+  "nodes": {nodes},
+  "meshes": [
+    {{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1, "material": 0 }}] }},
+    {{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1, "material": 1 }}] }}
+  ],
+  "materials": [
+    {{ "pbrMetallicRoughness": {{ "baseColorFactor": [1.0, 0.5, 0.25, 1.0], "metallicFactor": 0.0 }} }},
+    {{ "pbrMetallicRoughness": {{ "metallicFactor": 1.0, "roughnessFactor": 0.25 }} }}
+  ],
+  "buffers": [{buffer}],
+  "bufferViews": [
+    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+    {{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }}
+  ],
+  "accessors": [
+    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }},
+    {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+  ],
+  "cameras": [{{ "type": "perspective", "perspective": {{ "yfov": 1.0, "znear": 0.1 }} }}],
+  "extensions": {{
+    "KHR_lights_punctual": {{
+      "lights": [{{ "type": "point", "color": [1.0, 1.0, 1.0], "intensity": 2.0 }}]
+    }}
+  }}
+}}"#
+        )
+    }
+
+    // A mesh moved and scaled by its parent, a metal mesh, a camera and a light
+    const NODES: &str = r#"[
+    { "mesh": 0, "translation": [0.0, 0.0, 5.0], "scale": [2.0, 2.0, 2.0], "children": [1] },
+    { "mesh": 1, "translation": [1.0, 0.0, 0.0] },
+    { "camera": 0, "translation": [0.0, 1.0, -3.0] },
+    { "extensions": { "KHR_lights_punctual": { "light": 0 } }, "translation": [0.0, 4.0, 0.0] }
+  ]"#;
+
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let pad = |data: &[u8], byte: u8| {
+            let mut data = data.to_vec();
+            data.resize(data.len().div_ceil(4) * 4, byte);
+            data
+        };
+        let (json, bin) = (pad(json.as_bytes(), b' '), pad(bin, 0));
+
+        let mut out = b"glTF".to_vec();
+        out.extend(2u32.to_le_bytes());
+        out.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        for (chunk_type, chunk) in [(GLB_CHUNK_JSON, json), (GLB_CHUNK_BIN, bin)] {
+            out.extend((chunk.len() as u32).to_le_bytes());
+            out.extend(chunk_type.to_le_bytes());
+            out.extend(chunk);
+        }
+        out
+    }
+
+    fn no_resources(_: &str) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn world_triangle(object: &Object3D) -> (Vec<Vec3D<f32>>, &Material) {
+        match object {
+            Object3D::Mesh {
+                pos,
+                mesh,
+                material,
+            } => (
+                Mesh::triangle(mesh, 0).iter().map(|p| *p + *pos).collect(),
+                &**material,
+            ),
+            object => panic!("{object:?} is not a mesh"),
+        }
+    }
+
+    fn assert_scene(scene: &Scene) {
+        let objects = &scene.object_list.objects;
+        assert_eq!(objects.len(), 3);
+
+        // Node transforms are applied, children get their parent's one too
+        let (triangle, material) = world_triangle(&objects[0]);
+        assert_eq!(
+            triangle,
+            vec![
+                Vec3D::new(0.0, 0.0, 5.0),
+                Vec3D::new(2.0, 0.0, 5.0),
+                Vec3D::new(0.0, 2.0, 5.0),
+            ]
+        );
+        assert_eq!(
+            *material,
+            Material::Lambertian {
+                texture: Texture::SolidColor {
+                    color: Color::from_vec3d(Vec3D::new(1.0, 0.5, 0.25)),
+                },
+            }
+        );
+        let (triangle, material) = world_triangle(&objects[1]);
+        assert_eq!(triangle[0], Vec3D::new(2.0, 0.0, 5.0));
+        assert!(matches!(material, Material::Metal { fuzz, .. } if *fuzz == 0.25));
+
+        // The light's intensity sets how bright its sphere is
+        match &objects[2] {
+            Object3D::Sphere {
+                pos,
+                radius,
+                material,
+            } => {
+                assert_eq!(*pos, Vec3D::new(0.0, 4.0, 0.0));
+                match &**material {
+                    Material::DiffuseLight { strength, .. } => {
+                        assert!((strength - 2.0 / (PI * radius * radius)).abs() < 1e-3)
+                    }
+                    material => panic!("{material:?} is not a light"),
+                }
+            }
+            object => panic!("{object:?} is not a sphere"),
+        }
+
+        assert_eq!(scene.camera.pos, Vec3D::new(0.0, 1.0, -3.0));
+        assert!((scene.camera.vfov - 1.0f32.to_degrees()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn gltf_with_a_data_uri_buffer() {
+        let buffer = format!(
+            r#"{{ "uri": "data:application/octet-stream;base64,{:}", "byteLength": 42 }}"#,
+            base64::encode(triangle_buffer())
+        );
+
+        assert_scene(
+            &import_gltf("test", gltf_json(&buffer, NODES).as_bytes(), &no_resources).unwrap(),
+        );
+    }
+
+    #[test]
+    fn gltf_with_an_external_buffer() {
+        let json = gltf_json(r#"{ "uri": "triangle.bin", "byteLength": 42 }"#, NODES);
+        let resource = |uri: &str| (uri == "triangle.bin").then(triangle_buffer);
+
+        assert_scene(&import_gltf("test", json.as_bytes(), &resource).unwrap());
+        assert!(import_gltf("test", json.as_bytes(), &no_resources).is_err());
+    }
+
+    #[test]
+    fn glb_with_a_binary_chunk() {
+        let json = gltf_json(r#"{ "byteLength": 42 }"#, NODES);
+
+        assert_scene(&import_gltf("test", &glb(&json, &triangle_buffer()), &no_resources).unwrap());
+        // Cut off in the middle of the binary chunk
+        let data = glb(&json, &triangle_buffer());
+        assert!(import_gltf("test", &data[..data.len() - 10], &no_resources).is_err());
+    }
+
+    #[test]
+    fn node_cycles_are_rejected() {
+        let nodes = r#"[
+    { "mesh": 0, "children": [1] },
+    { "mesh": 1, "children": [0] },
+    { "camera": 0 },
+    { "extensions": { "KHR_lights_punctual": { "light": 0 } } }
+  ]"#;
+        let json = gltf_json(r#"{ "byteLength": 42 }"#, nodes);
+
+        assert!(import_gltf("test", &glb(&json, &triangle_buffer()), &no_resources).is_err());
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use crate::draw::Color;
+use crate::graphics::{Material, Mesh, Object3D};
+use crate::vector::{Vec2D, Vec3D};
+
+mod gltf;
+mod obj;
+mod ply;
+mod stl;
+
+pub use gltf::import_gltf;
+pub use obj::import_obj;
+#[cfg(not(target_arch = "wasm32"))]
+pub use obj::obj_material_libraries;
+pub use ply::import_ply;
+pub use stl::import_stl;
+
+// --------------------------------------------------
+
+// Positions are stored relative to the minimal corner, which becomes the object's position
+fn mesh_object(
+    positions: Vec<Vec3D<f32>>,
+    normals: Vec<Vec3D<f32>>,
+    uvs: Vec<Vec2D<f32>>,
+    colors: Vec<Color>,
+    indices: Vec<[u32; 3]>,
+    smooth: bool,
+    material: Arc<Material>,
+) -> Result<Object3D, String> {
+    let pos = positions.iter().fold(
+        Vec3D::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        |min, p| Vec3D::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+    );
+
+    Ok(Object3D::Mesh {
+        pos,
+        mesh: Arc::new(Mesh::new(
+            positions.into_iter().map(|p| p - pos).collect(),
+            normals,
+            uvs,
+            colors,
+            indices,
+            smooth,
+        )?),
+        material,
+    })
+}
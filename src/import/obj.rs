@@ -0,0 +1,479 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::mesh_object;
+use crate::draw::Color;
+use crate::graphics::{Material, Object3D, ObjectList, Texture};
+use crate::vector::{Vec2D, Vec3D};
+
+// --------------------------------------------------
+
+const DEFAULT_COLOR: Vec3D<f32> = Vec3D::new(0.8, 0.8, 0.8);
+const DEFAULT_REFRACTION_INDEX: f32 = 1.5;
+
+// --------------------------------------------------
+
+// Properties of a single `newmtl`, only the ones that map onto our materials are read
+#[derive(Clone, Debug)]
+struct MtlMaterial {
+    diffuse: Vec3D<f32>,
+    specular: Vec3D<f32>,
+    emissive: Vec3D<f32>,
+    shininess: Option<f32>,
+    refraction_index: Option<f32>,
+    opacity: f32,
+    illum: Option<u32>,
+}
+
+impl MtlMaterial {
+    // Emission wins over transparency, transparency over specularity
+    fn to_material(&self) -> Material {
+        if max_component(self.emissive) > 0.0 {
+            Material::light(self.emissive)
+        } else if self.opacity < 1.0 || matches!(self.illum, Some(4 | 6 | 7 | 9)) {
+            Material::Dielectric {
+                refraction_index: self.refraction_index.unwrap_or(DEFAULT_REFRACTION_INDEX),
+            }
+        } else if max_component(self.specular) > 0.0
+            && (self.illum == Some(3) || max_component(self.specular) >= max_component(self.diffuse))
+        {
+            Material::Metal {
+                texture: solid_color(self.specular),
+                // Ns goes from 0 (rough) to 1000 (mirror)
+                fuzz: 1.0 - (self.shininess.unwrap_or(0.0) / 1000.0).clamp(0.0, 1.0).sqrt(),
+            }
+        } else {
+            Material::Lambertian {
+                texture: solid_color(self.diffuse),
+            }
+        }
+    }
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: DEFAULT_COLOR,
+            specular: Vec3D::new(0.0, 0.0, 0.0),
+            emissive: Vec3D::new(0.0, 0.0, 0.0),
+            shininess: None,
+            refraction_index: None,
+            opacity: 1.0,
+            illum: None,
+        }
+    }
+}
+
+// --------------------------------------------------
+
+// Triangles of one object / group with one material, vertices are deduplicated by their
+// position / uv / normal index triplet
+#[derive(Debug, Default)]
+struct MeshBuilder {
+    material: Option<String>,
+    smooth: bool,
+    vertices: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    positions: Vec<Vec3D<f32>>,
+    uvs: Vec<Option<Vec2D<f32>>>,
+    normals: Vec<Option<Vec3D<f32>>>,
+    indices: Vec<[u32; 3]>,
+}
+
+impl MeshBuilder {
+    fn new(material: Option<String>, smooth: bool) -> MeshBuilder {
+        MeshBuilder {
+            material,
+            smooth,
+            ..Default::default()
+        }
+    }
+
+    fn vertex(
+        &mut self,
+        key: (usize, Option<usize>, Option<usize>),
+        positions: &[Vec3D<f32>],
+        uvs: &[Vec2D<f32>],
+        normals: &[Vec3D<f32>],
+    ) -> u32 {
+        if let Some(index) = self.vertices.get(&key) {
+            return *index;
+        }
+
+        self.positions.push(positions[key.0]);
+        self.uvs.push(key.1.map(|i| uvs[i]));
+        self.normals.push(key.2.map(|i| normals[i]));
+
+        let index = (self.positions.len() - 1) as u32;
+        self.vertices.insert(key, index);
+        index
+    }
+
+    fn build(self, materials: &HashMap<String, Arc<Material>>) -> Result<Object3D, String> {
+        let material = match &self.material {
+            Some(name) => match materials.get(name) {
+                Some(material) => material.clone(),
+                None => {
+                    log::warn!("Material {name:?} is not defined in any given .mtl file");
+                    Arc::new(MtlMaterial::default().to_material())
+                }
+            },
+            None => Arc::new(MtlMaterial::default().to_material()),
+        };
+
+        // Partial normals / uvs are dropped, or filled in
+        let normals = if self.normals.iter().all(Option::is_some) {
+            self.normals.into_iter().flatten().collect()
+        } else {
+            vec![]
+        };
+        let uvs = if self.uvs.iter().any(Option::is_some) {
+            self.uvs
+                .into_iter()
+                .map(|uv| uv.unwrap_or(Vec2D::new(0.0, 0.0)))
+                .collect()
+        } else {
+            vec![]
+        };
+
+        mesh_object(
+            self.positions,
+            normals,
+            uvs,
+            vec![],
+            self.indices,
+            self.smooth,
+            material,
+        )
+    }
+}
+
+// --------------------------------------------------
+
+// Names of the material libraries an .obj file refers to, the browser gets them uploaded instead
+#[cfg(not(target_arch = "wasm32"))]
+pub fn obj_material_libraries(obj: &str) -> Vec<String> {
+    obj.lines()
+        .filter_map(|line| line.trim().strip_prefix("mtllib "))
+        .flat_map(|names| names.split_whitespace().map(str::to_string))
+        .collect()
+}
+
+// Every object / group and material combination becomes its own mesh. Materials are looked up in
+// all the given .mtl files
+pub fn import_obj(obj: &str, mtls: &[&str]) -> Result<ObjectList, String> {
+    let materials = mtls
+        .iter()
+        .map(|mtl| parse_mtl(mtl))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .map(|(name, material)| (name, Arc::new(material.to_material())))
+        .collect::<HashMap<_, _>>();
+
+    let (mut positions, mut uvs, mut normals) = (vec![], vec![], vec![]);
+    let mut meshes: Vec<MeshBuilder> = vec![];
+    let mut current = MeshBuilder::new(None, true);
+
+    for (line_index, line) in obj.lines().enumerate() {
+        let error = |e: &str| format!("Line {:}: {e:}", line_index + 1);
+
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => positions.push(parse_vec3d(&args).map_err(|e| error(&e))?),
+            "vn" => normals.push(parse_vec3d(&args).map_err(|e| error(&e))?),
+            "vt" => {
+                let uv = parse_floats(&args).map_err(|e| error(&e))?;
+                uvs.push(Vec2D::new(
+                    *uv.first().ok_or_else(|| error("Texture coordinate is missing u"))?,
+                    uv.get(1).copied().unwrap_or(0.0),
+                ))
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(error("Face has less than 3 vertices"));
+                }
+
+                let face = args
+                    .iter()
+                    .map(|vertex| {
+                        let key =
+                            parse_face_vertex(vertex, positions.len(), uvs.len(), normals.len())?;
+                        Ok(current.vertex(key, &positions, &uvs, &normals))
+                    })
+                    .collect::<Result<Vec<u32>, String>>()
+                    .map_err(|e| error(&e))?;
+
+                // Polygons are triangulated as a fan
+                for i in 1..(face.len() - 1) {
+                    current.indices.push([face[0], face[i], face[i + 1]]);
+                }
+            }
+            "o" | "g" | "usemtl" | "s" => {
+                let material = if keyword == "usemtl" {
+                    args.first().map(|name| name.to_string())
+                } else {
+                    current.material.clone()
+                };
+                let smooth = if keyword == "s" {
+                    !matches!(args.first(), Some(&"off" | &"0"))
+                } else {
+                    current.smooth
+                };
+
+                let previous = std::mem::replace(&mut current, MeshBuilder::new(material, smooth));
+                if !previous.indices.is_empty() {
+                    meshes.push(previous);
+                }
+            }
+            _ => {}
+        }
+    }
+    if !current.indices.is_empty() {
+        meshes.push(current);
+    }
+
+    if meshes.is_empty() {
+        return Err("File contains no faces".to_string());
+    }
+
+    Ok(ObjectList::new(
+        meshes
+            .into_iter()
+            .map(|mesh| mesh.build(&materials))
+            .collect::<Result<_, _>>()?,
+    ))
+}
+
+// --------------------------------------------------
+
+fn parse_mtl(mtl: &str) -> Result<Vec<(String, MtlMaterial)>, String> {
+    let mut out: Vec<(String, MtlMaterial)> = vec![];
+
+    for (line_index, line) in mtl.lines().enumerate() {
+        let error = |e: &str| format!("Material line {:}: {e:}", line_index + 1);
+
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            out.push((args.join(" "), MtlMaterial::default()));
+            continue;
+        }
+        let material = match out.last_mut() {
+            Some((_, material)) => material,
+            None => continue,
+        };
+
+        let float = || -> Result<f32, String> {
+            parse_floats(&args)
+                .map_err(|e| error(&e))?
+                .first()
+                .copied()
+                .ok_or_else(|| error("Missing value"))
+        };
+        match keyword {
+            "Kd" => material.diffuse = parse_vec3d(&args).map_err(|e| error(&e))?,
+            "Ks" => material.specular = parse_vec3d(&args).map_err(|e| error(&e))?,
+            "Ke" => material.emissive = parse_vec3d(&args).map_err(|e| error(&e))?,
+            "Ns" => material.shininess = Some(float()?),
+            "Ni" => material.refraction_index = Some(float()?),
+            "d" => material.opacity = float()?,
+            "Tr" => material.opacity = 1.0 - float()?,
+            "illum" => material.illum = Some(float()? as u32),
+            "map_Kd" => log::warn!("Image textures of .mtl files are not supported, ignoring map_Kd"),
+            _ => {}
+        }
+    }
+
+    Ok(out)
+}
+
+fn parse_floats(args: &[&str]) -> Result<Vec<f32>, String> {
+    args.iter()
+        .map(|arg| {
+            arg.parse::<f32>()
+                .map_err(|_| format!("{arg:?} is not a number"))
+        })
+        .collect()
+}
+
+fn parse_vec3d(args: &[&str]) -> Result<Vec3D<f32>, String> {
+    match parse_floats(args)?[..] {
+        [x, y, z, ..] => Ok(Vec3D::new(x, y, z)),
+        [v] => Ok(Vec3D::new(v, v, v)),
+        _ => Err("Expected 3 numbers".to_string()),
+    }
+}
+
+// "v", "v/vt", "v//vn" or "v/vt/vn", indices start at 1 and negative ones count from the end
+fn parse_face_vertex(
+    vertex: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let index = |part: Option<&str>, count: usize| -> Result<Option<usize>, String> {
+        match part {
+            None | Some("") => Ok(None),
+            Some(part) => {
+                let index = part
+                    .parse::<i64>()
+                    .map_err(|_| format!("{part:?} is not an index"))?;
+                let resolved = if index < 0 {
+                    count as i64 + index
+                } else {
+                    index - 1
+                };
+
+                if resolved >= 0 && (resolved as usize) < count {
+                    Ok(Some(resolved as usize))
+                } else {
+                    Err(format!("Index {index:} is out of range"))
+                }
+            }
+        }
+    };
+
+    let mut parts = vertex.split('/');
+    Ok((
+        index(parts.next(), position_count)?.ok_or("Face vertex is missing its position")?,
+        index(parts.next(), uv_count)?,
+        index(parts.next(), normal_count)?,
+    ))
+}
+
+fn solid_color(color: Vec3D<f32>) -> Texture {
+    Texture::SolidColor {
+        color: Color::from_vec3d(color),
+    }
+}
+
+fn max_component(v: Vec3D<f32>) -> f32 {
+    v.x.max(v.y).max(v.z)
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Mesh;
+
+    const MTL: &str = "
+newmtl matte
+Kd 0.5 0.25 1.0
+newmtl lamp
+Ke 2.0 2.0 1.0
+newmtl glass
+Ni 1.33
+d 0.2
+newmtl chrome
+Kd 0.1 0.1 0.1
+Ks 0.9 0.9 0.9
+Ns 1000
+";
+
+    fn meshes(objects: &ObjectList) -> Vec<(&Mesh, &Material)> {
+        objects
+            .objects
+            .iter()
+            .map(|object| match object {
+                Object3D::Mesh { mesh, material, .. } => (&**mesh, &**material),
+                object => panic!("{object:?} is not a mesh"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn polygons_are_triangulated_and_split_by_material() {
+        let obj = "
+mtllib scene.mtl other.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+usemtl matte
+f 1/1 2/2 3/3 4/4
+usemtl lamp
+f -1 -4 -3
+";
+        assert_eq!(obj_material_libraries(obj), vec!["scene.mtl", "other.mtl"]);
+
+        let objects = import_obj(obj, &[MTL]).unwrap();
+        let meshes = meshes(&objects);
+        assert_eq!(meshes.len(), 2);
+
+        // The quad becomes a fan of two triangles, sharing the vertices it uses
+        let (quad, matte) = meshes[0];
+        assert_eq!(quad.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(quad.uvs[2], Vec2D::new(1.0, 1.0));
+        assert_eq!(
+            *matte,
+            Material::Lambertian {
+                texture: solid_color(Vec3D::new(0.5, 0.25, 1.0))
+            }
+        );
+
+        // Negative indices count from the last vertex
+        let (triangle, lamp) = meshes[1];
+        assert_eq!(triangle.positions.len(), 3);
+        assert_eq!(*lamp, Material::light(Vec3D::new(2.0, 2.0, 1.0)));
+        assert_eq!(objects.objects[1].position(), Vec3D::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn mtl_materials_map_to_the_closest_material() {
+        let materials: HashMap<_, _> = parse_mtl(MTL)
+            .unwrap()
+            .into_iter()
+            .map(|(name, material)| (name, material.to_material()))
+            .collect();
+
+        assert_eq!(
+            materials["glass"],
+            Material::Dielectric {
+                refraction_index: 1.33
+            }
+        );
+        assert_eq!(
+            materials["chrome"],
+            Material::Metal {
+                texture: solid_color(Vec3D::new(0.9, 0.9, 0.9)),
+                fuzz: 0.0,
+            }
+        );
+        // Unknown materials get the default gray
+        let objects =
+            import_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl missing\nf 1 2 3", &[]).unwrap();
+        assert_eq!(
+            *meshes(&objects)[0].1,
+            Material::Lambertian {
+                texture: solid_color(DEFAULT_COLOR)
+            }
+        );
+    }
+
+    #[test]
+    fn broken_files_are_rejected() {
+        assert!(import_obj("v 0 0 0\nv 1 0 0", &[]).is_err());
+        assert!(import_obj("v 0 0 0\nv 1 0 0\nf 1 2 3", &[]).is_err());
+        assert!(import_obj("v 0 0 x\nf 1 1 1", &[]).is_err());
+        assert!(import_obj("v 0 0 0\nf 1 1", &[]).is_err());
+        assert!(parse_mtl("newmtl a\nKd red").is_err());
+    }
+}
@@ -0,0 +1,436 @@
+use std::sync::Arc;
+
+use super::mesh_object;
+use crate::draw::Color;
+use crate::graphics::{Material, Object3D};
+use crate::vector::{Vec2D, Vec3D};
+
+// --------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Scalar, String> {
+        Ok(match name {
+            "char" | "int8" => Scalar::Int8,
+            "uchar" | "uint8" => Scalar::UInt8,
+            "short" | "int16" => Scalar::Int16,
+            "ushort" | "uint16" => Scalar::UInt16,
+            "int" | "int32" => Scalar::Int32,
+            "uint" | "uint32" => Scalar::UInt32,
+            "float" | "float32" => Scalar::Float32,
+            "double" | "float64" => Scalar::Float64,
+            _ => return Err(format!("Unknown property type {name:?}")),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::Int8 | Scalar::UInt8 => 1,
+            Scalar::Int16 | Scalar::UInt16 => 2,
+            Scalar::Int32 | Scalar::UInt32 | Scalar::Float32 => 4,
+            Scalar::Float64 => 8,
+        }
+    }
+
+    // Integer colors go up to the maximum of their type, float ones up to 1
+    fn color_max(self) -> f64 {
+        match self {
+            Scalar::Int8 => i8::MAX as f64,
+            Scalar::UInt8 => u8::MAX as f64,
+            Scalar::Int16 => i16::MAX as f64,
+            Scalar::UInt16 => u16::MAX as f64,
+            Scalar::Int32 => i32::MAX as f64,
+            Scalar::UInt32 => u32::MAX as f64,
+            Scalar::Float32 | Scalar::Float64 => 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum PropertyKind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    // Index of the first property with one of the given names
+    fn find(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| names.contains(&property.name.as_str()))
+    }
+}
+
+// --------------------------------------------------
+
+enum Reader<'a> {
+    Ascii(std::str::SplitWhitespace<'a>),
+    Binary {
+        data: &'a [u8],
+        offset: usize,
+        big_endian: bool,
+    },
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, String> {
+        match self {
+            Reader::Ascii(tokens) => {
+                let token = tokens.next().ok_or("Unexpected end of file")?;
+                token
+                    .parse::<f64>()
+                    .map_err(|_| format!("{token:?} is not a number"))
+            }
+            Reader::Binary {
+                data,
+                offset,
+                big_endian,
+            } => {
+                let mut bytes = data
+                    .get(*offset..(*offset + scalar.size()))
+                    .ok_or("Unexpected end of file")?
+                    .to_vec();
+                *offset += scalar.size();
+                if !*big_endian {
+                    bytes.reverse();
+                }
+
+                Ok(match scalar {
+                    Scalar::Int8 => bytes[0] as i8 as f64,
+                    Scalar::UInt8 => bytes[0] as f64,
+                    Scalar::Int16 => i16::from_be_bytes([bytes[0], bytes[1]]) as f64,
+                    Scalar::UInt16 => u16::from_be_bytes([bytes[0], bytes[1]]) as f64,
+                    Scalar::Int32 => {
+                        i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+                    }
+                    Scalar::UInt32 => {
+                        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+                    }
+                    Scalar::Float32 => {
+                        f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+                    }
+                    Scalar::Float64 => f64::from_be_bytes([
+                        bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6],
+                        bytes[7],
+                    ]),
+                })
+            }
+        }
+    }
+
+    // Scalars hold one value, lists all of their items
+    fn read_property(&mut self, kind: PropertyKind) -> Result<Vec<f64>, String> {
+        match kind {
+            PropertyKind::Scalar(scalar) => Ok(vec![self.read(scalar)?]),
+            PropertyKind::List { count, item } => {
+                let count = self.read(count)? as usize;
+                (0..count).map(|_| self.read(item)).collect()
+            }
+        }
+    }
+}
+
+// --------------------------------------------------
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), String> {
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err("Not a .ply file".to_string());
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for line in lines {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        match tokens[..] {
+            ["format", name, ..] => {
+                format = Some(match name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(format!("Unknown format {name:?}")),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| format!("{count:?} is not an element count"))?,
+                properties: vec![],
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or("Property outside of an element")?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::List {
+                        count: Scalar::parse(count)?,
+                        item: Scalar::parse(item)?,
+                    },
+                }),
+            ["property", scalar, name] => elements
+                .last_mut()
+                .ok_or("Property outside of an element")?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::Scalar(Scalar::parse(scalar)?),
+                }),
+            _ => {}
+        }
+    }
+
+    Ok((format.ok_or("Missing format")?, elements))
+}
+
+// Creates one mesh from the vertex and face elements, other elements are skipped. Vertex colors
+// are kept, use `Texture::VertexColor` in the material to show them
+pub fn import_ply(data: &[u8], material: Material) -> Result<Object3D, String> {
+    const END_HEADER: &[u8] = b"end_header";
+
+    let header_end = data
+        .windows(END_HEADER.len())
+        .position(|window| window == END_HEADER)
+        .ok_or("Missing end_header")?;
+    let body_start = data[header_end..]
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .map_or(data.len(), |i| header_end + i + 1);
+
+    let (format, elements) = parse_header(&String::from_utf8_lossy(&data[..header_end]))?;
+    let mut reader = match format {
+        Format::Ascii => Reader::Ascii(
+            std::str::from_utf8(&data[body_start..])
+                .map_err(|_| "Ascii body is not valid text")?
+                .split_whitespace(),
+        ),
+        Format::BinaryLittleEndian | Format::BinaryBigEndian => Reader::Binary {
+            data: &data[body_start..],
+            offset: 0,
+            big_endian: format == Format::BinaryBigEndian,
+        },
+    };
+
+    let (mut positions, mut normals, mut uvs, mut colors) = (vec![], vec![], vec![], vec![]);
+    let mut indices: Vec<[u32; 3]> = vec![];
+
+    for element in &elements {
+        let (position, normal, uv, color) = (
+            [
+                element.find(&["x"]),
+                element.find(&["y"]),
+                element.find(&["z"]),
+            ],
+            [
+                element.find(&["nx"]),
+                element.find(&["ny"]),
+                element.find(&["nz"]),
+            ],
+            [
+                element.find(&["u", "s", "texture_u", "texture_s"]),
+                element.find(&["v", "t", "texture_v", "texture_t"]),
+            ],
+            [
+                element.find(&["red", "diffuse_red", "r"]),
+                element.find(&["green", "diffuse_green", "g"]),
+                element.find(&["blue", "diffuse_blue", "b"]),
+            ],
+        );
+        let face = element.find(&["vertex_indices", "vertex_index"]);
+
+        for _ in 0..element.count {
+            let values = element
+                .properties
+                .iter()
+                .map(|property| reader.read_property(property.kind))
+                .collect::<Result<Vec<_>, _>>()?;
+            let vec3d = |[x, y, z]: [Option<usize>; 3]| -> Option<Vec3D<f32>> {
+                Some(Vec3D::new(
+                    values[x?][0] as f32,
+                    values[y?][0] as f32,
+                    values[z?][0] as f32,
+                ))
+            };
+
+            match element.name.as_str() {
+                "vertex" => {
+                    positions.push(vec3d(position).ok_or("Vertices are missing x / y / z")?);
+                    normals.extend(vec3d(normal));
+                    if let [Some(u), Some(v)] = uv {
+                        uvs.push(Vec2D::new(values[u][0] as f32, values[v][0] as f32));
+                    }
+                    if let Some(rgb) = vec3d(color) {
+                        let max = match element.properties[color[0].unwrap()].kind {
+                            PropertyKind::Scalar(scalar) => scalar.color_max(),
+                            PropertyKind::List { .. } => 1.0,
+                        } as f32;
+                        colors.push(Color::from_vec3d(rgb / max));
+                    }
+                }
+                "face" => {
+                    let face = &values[face.ok_or("Faces are missing vertex_indices")?];
+                    if face.len() < 3 {
+                        return Err("Face has less than 3 vertices".to_string());
+                    }
+
+                    // Polygons are triangulated as a fan
+                    for i in 1..(face.len() - 1) {
+                        indices.push([face[0] as u32, face[i] as u32, face[i + 1] as u32]);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if indices.is_empty() {
+        return Err("File contains no faces".to_string());
+    }
+
+    mesh_object(
+        positions,
+        normals,
+        uvs,
+        colors,
+        indices,
+        true,
+        Arc::new(material),
+    )
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Mesh;
+
+    const HEADER: &str = "element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    // A unit quad at z = 1, with one red corner
+    const VERTICES: [([f32; 3], [u8; 3]); 4] = [
+        ([1.0, 1.0, 1.0], [255, 0, 0]),
+        ([2.0, 1.0, 1.0], [0, 0, 0]),
+        ([2.0, 2.0, 1.0], [0, 0, 0]),
+        ([1.0, 2.0, 1.0], [0, 0, 0]),
+    ];
+
+    fn binary(format: &str, big_endian: bool) -> Vec<u8> {
+        let float = |f: f32| {
+            if big_endian {
+                f.to_be_bytes()
+            } else {
+                f.to_le_bytes()
+            }
+        };
+        let int = |i: i32| {
+            if big_endian {
+                i.to_be_bytes()
+            } else {
+                i.to_le_bytes()
+            }
+        };
+
+        let mut data = format!("ply\nformat {format} 1.0\n{HEADER}").into_bytes();
+        for (position, color) in VERTICES {
+            data.extend(position.into_iter().flat_map(float));
+            data.extend(color);
+        }
+        data.push(4);
+        data.extend([0, 1, 2, 3].into_iter().flat_map(int));
+        data
+    }
+
+    fn ascii() -> Vec<u8> {
+        let mut text = format!("ply\nformat ascii 1.0\ncomment a quad\n{HEADER}");
+        for ([x, y, z], [r, g, b]) in VERTICES {
+            text += &format!("{x} {y} {z} {r} {g} {b}\n");
+        }
+        text += "4 0 1 2 3\n";
+        text.into_bytes()
+    }
+
+    fn assert_quad(object: &Object3D) {
+        let (pos, mesh): (_, &Mesh) = match object {
+            Object3D::Mesh { pos, mesh, .. } => (*pos, mesh),
+            object => panic!("{object:?} is not a mesh"),
+        };
+
+        // Positions are relative to the lowest corner
+        assert_eq!(pos, Vec3D::new(1.0, 1.0, 1.0));
+        assert_eq!(mesh.positions[2], Vec3D::new(1.0, 1.0, 0.0));
+        // The quad is triangulated as a fan
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.colors[0], Color::from_vec3d(Vec3D::new(1.0, 0.0, 0.0)));
+        assert_eq!(mesh.colors[1], Color::from_vec3d(Vec3D::new(0.0, 0.0, 0.0)));
+        // Smooth normals are computed as the file has none
+        assert_eq!(mesh.normals.len(), 4);
+        assert!(mesh.smooth && mesh.uvs.is_empty());
+    }
+
+    #[test]
+    fn every_format_reads_the_same_mesh() {
+        assert_quad(&import_ply(&ascii(), Material::default()).unwrap());
+        assert_quad(
+            &import_ply(&binary("binary_little_endian", false), Material::default()).unwrap(),
+        );
+        assert_quad(&import_ply(&binary("binary_big_endian", true), Material::default()).unwrap());
+    }
+
+    #[test]
+    fn broken_files_are_rejected() {
+        let data = binary("binary_little_endian", false);
+
+        // Cut off in the middle of the face
+        assert!(import_ply(&data[..data.len() - 3], Material::default()).is_err());
+        assert!(import_ply(&binary("binary_middle_endian", false), Material::default()).is_err());
+        assert!(import_ply(b"ply\nformat ascii 1.0\n", Material::default()).is_err());
+        assert!(import_ply(&ascii()[1..], Material::default()).is_err());
+        // Vertices only
+        let text = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\nend_header\n0 0 0\n";
+        assert!(import_ply(text.as_bytes(), Material::default()).is_err());
+    }
+}
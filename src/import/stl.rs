@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::mesh_object;
+use crate::graphics::{Material, Object3D};
+use crate::vector::Vec3D;
+
+// --------------------------------------------------
+
+const BINARY_HEADER_SIZE: usize = 84;
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+// --------------------------------------------------
+
+// Binary files are recognized by their size, as their header may start with "solid" too
+fn is_binary(data: &[u8]) -> bool {
+    data.len() >= BINARY_HEADER_SIZE && {
+        let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+        data.len() == BINARY_HEADER_SIZE + count * BINARY_TRIANGLE_SIZE
+    }
+}
+
+fn parse_binary(data: &[u8]) -> Vec<[Vec3D<f32>; 3]> {
+    let float = |offset: usize| {
+        f32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    };
+
+    // Every triangle is a normal, 3 vertices and 2 attribute bytes
+    (BINARY_HEADER_SIZE..data.len())
+        .step_by(BINARY_TRIANGLE_SIZE)
+        .map(|start| {
+            [0, 1, 2].map(|vertex| {
+                let offset = start + 12 + vertex * 12;
+                Vec3D::new(float(offset), float(offset + 4), float(offset + 8))
+            })
+        })
+        .collect()
+}
+
+fn parse_ascii(text: &str) -> Result<Vec<[Vec3D<f32>; 3]>, String> {
+    let mut vertices = vec![];
+
+    for (line_index, line) in text.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("vertex") {
+            continue;
+        }
+
+        let coordinates = tokens
+            .map(|token| token.parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("Line {:}: Invalid vertex", line_index + 1))?;
+        match coordinates[..] {
+            [x, y, z] => vertices.push(Vec3D::new(x, y, z)),
+            _ => return Err(format!("Line {:}: Expected 3 numbers", line_index + 1)),
+        }
+    }
+
+    if vertices.len() % 3 != 0 {
+        return Err("Facets have to have 3 vertices".to_string());
+    }
+    Ok(vertices
+        .chunks_exact(3)
+        .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+        .collect())
+}
+
+// Creates one flat shaded mesh from a binary or ascii .stl file. Vertices with the same position
+// are merged
+pub fn import_stl(data: &[u8], material: Material) -> Result<Object3D, String> {
+    let triangles = if is_binary(data) {
+        parse_binary(data)
+    } else {
+        parse_ascii(&String::from_utf8_lossy(data))?
+    };
+    if triangles.is_empty() {
+        return Err("File contains no facets".to_string());
+    }
+
+    let mut vertices: HashMap<[u32; 3], u32> = HashMap::new();
+    let mut positions = vec![];
+    let indices = triangles
+        .iter()
+        .map(|triangle| {
+            triangle.map(|vertex| {
+                *vertices
+                    .entry([vertex.x.to_bits(), vertex.y.to_bits(), vertex.z.to_bits()])
+                    .or_insert_with(|| {
+                        positions.push(vertex);
+                        (positions.len() - 1) as u32
+                    })
+            })
+        })
+        .collect();
+
+    mesh_object(
+        positions,
+        vec![],
+        vec![],
+        vec![],
+        indices,
+        false,
+        Arc::new(material),
+    )
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Mesh;
+
+    // Two triangles of a square, sharing an edge
+    const TRIANGLES: [[[f32; 3]; 3]; 2] = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+    ];
+
+    fn ascii() -> String {
+        let mut text = "solid square\n".to_string();
+        for triangle in TRIANGLES {
+            text += "facet normal 0 0 1\nouter loop\n";
+            for [x, y, z] in triangle {
+                text += &format!("vertex {x} {y} {z}\n");
+            }
+            text += "endloop\nendfacet\n";
+        }
+        text + "endsolid square\n"
+    }
+
+    // The header starts with "solid" too, like the ones some exporters write
+    fn binary() -> Vec<u8> {
+        let mut data = b"solid binary".to_vec();
+        data.resize(80, 0);
+        data.extend((TRIANGLES.len() as u32).to_le_bytes());
+        for triangle in TRIANGLES {
+            data.extend([0.0f32, 0.0, 1.0].into_iter().flat_map(f32::to_le_bytes));
+            data.extend(triangle.into_iter().flatten().flat_map(f32::to_le_bytes));
+            data.extend([0, 0]);
+        }
+        data
+    }
+
+    fn assert_square(object: &Object3D) {
+        let mesh: &Mesh = match object {
+            Object3D::Mesh { mesh, .. } => mesh,
+            object => panic!("{object:?} is not a mesh"),
+        };
+
+        // The shared vertices are merged
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.positions[3], Vec3D::new(0.0, 1.0, 0.0));
+        assert!(!mesh.smooth);
+    }
+
+    #[test]
+    fn ascii_and_binary_files_read_the_same_mesh() {
+        assert!(!is_binary(ascii().as_bytes()));
+        assert!(is_binary(&binary()));
+
+        assert_square(&import_stl(ascii().as_bytes(), Material::default()).unwrap());
+        assert_square(&import_stl(&binary(), Material::default()).unwrap());
+    }
+
+    #[test]
+    fn broken_files_are_rejected() {
+        assert!(import_stl(b"solid empty\nendsolid empty\n", Material::default()).is_err());
+        assert!(import_stl(b"vertex 0 0 0\nvertex 1 0 0\n", Material::default()).is_err());
+        assert!(import_stl(b"vertex 0 0\n", Material::default()).is_err());
+        assert!(import_stl(b"vertex 0 0 x\n", Material::default()).is_err());
+    }
+}
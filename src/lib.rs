@@ -4,7 +4,10 @@ mod draw;
 mod event;
 mod graphics;
 mod image;
+mod import;
 mod misc;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod native;
 mod predefined_scenes;
 mod ui;
 mod vector;
@@ -47,6 +50,7 @@ fn main() {
         predefined_scenes(),
         *view_size.lock().unwrap(),
         settings.lock().unwrap().vfov(),
+        settings.lock().unwrap().projection(),
     )));
 
     event::register_ui_events(
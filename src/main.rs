@@ -0,0 +1,110 @@
+// Command line front end of the native entry points, the browser build only uses the library
+
+#[cfg(not(target_arch = "wasm32"))]
+mod cli {
+    use std::env;
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+    use std::process;
+
+    use webtracer::native;
+
+    const USAGE: &str = "Usage:
+    webtracer-cli render <scene> <out_dir> <width> <height> <rays_per_pixel> <bounce_depth>
+    webtracer-cli import-obj <obj> <scene> <out_scene>
+    webtracer-cli import-mesh <ply_or_stl> <scene> <out_scene> [<material_yaml>]
+    webtracer-cli embed <scene> <out_scene>";
+
+    // Meshes show their vertex colors unless a material file is given, like uploaded ones
+    const VERTEX_COLOR_MATERIAL: &str = "Lambertian: { texture: VertexColor }";
+
+    // --------------------------------------------------
+
+    // Warnings and errors of the renderer go to stderr, progress messages are left out
+    struct StderrLogger;
+
+    impl log::Log for StderrLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                eprintln!("{:} - {:}", record.level(), record.args());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: StderrLogger = StderrLogger;
+
+    fn number<T: std::str::FromStr>(arg: &str) -> io::Result<T> {
+        arg.parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{arg:?} is not a valid number"),
+            )
+        })
+    }
+
+    fn run(args: &[&str]) -> io::Result<()> {
+        match args {
+            ["render", scene, out_dir, width, height, rays_per_pixel, bounce_depth] => {
+                native::render_sequence_to_dir(
+                    Path::new(scene),
+                    Path::new(out_dir),
+                    (number(width)?, number(height)?),
+                    number(rays_per_pixel)?,
+                    number(bounce_depth)?,
+                )
+            }
+            ["import-obj", obj, scene, out_scene] => native::import_obj_into_scene(
+                Path::new(obj),
+                Path::new(scene),
+                Path::new(out_scene),
+            ),
+            ["import-mesh", mesh, scene, out_scene, material @ ..] if material.len() <= 1 => {
+                let material = match material.first() {
+                    Some(path) => fs::read_to_string(path)?,
+                    None => VERTEX_COLOR_MATERIAL.to_string(),
+                };
+
+                native::import_mesh_into_scene(
+                    Path::new(mesh),
+                    serde_yaml::from_str::<native::Material>(&material)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+                    Path::new(scene),
+                    Path::new(out_scene),
+                )
+            }
+            ["embed", scene, out_scene] => {
+                native::write_self_contained_scene(Path::new(scene), Path::new(out_scene))
+            }
+            _ => {
+                eprintln!("{USAGE:}");
+                process::exit(2)
+            }
+        }
+    }
+
+    pub fn main() {
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Warn);
+
+        let args: Vec<String> = env::args().skip(1).collect();
+        if let Err(e) = run(&args.iter().map(String::as_str).collect::<Vec<_>>()) {
+            eprintln!("Error - {e:}");
+            process::exit(1)
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    cli::main()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
@@ -1,10 +1,14 @@
+use std::io::{Cursor, Write};
 use std::sync::Arc;
 
+use crate::image::with_embedded_images;
 use crate::ui::Settings;
 use crate::vector::Vec2D;
 use serde::Serialize;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
 
 // --------------------------------------------------
 
@@ -18,6 +22,7 @@ pub enum RenderState {
     StandByMode,
     PreviewRender,
     FullRender,
+    SequenceRender,
     RenderingFreeze,
     FinishedFullRender,
 }
@@ -30,6 +35,7 @@ extern "C" {
     pub fn cpu_cores() -> u32;
     pub fn download_blob(blob: web_sys::Blob, filename: String);
     pub fn blob_from_str(data: String) -> web_sys::Blob;
+    pub fn blob_from_bytes(data: Vec<u8>) -> web_sys::Blob;
     pub fn event_to_file(event: web_sys::Event) -> web_sys::Blob;
 }
 
@@ -64,11 +70,30 @@ pub fn download_struct(object: impl Serialize, filename: String) {
     let filename_arc = Arc::new(filename);
 
     download_blob(
-        blob_from_str(serde_yaml::to_string(&object).unwrap()),
+        blob_from_str(with_embedded_images(|| serde_yaml::to_string(&object)).unwrap()),
         format!("{:?}.yaml", *filename_arc),
     );
 }
 
+// Files are stored uncompressed, pngs don't get any smaller anyway
+pub fn download_zip(files: &[(String, Vec<u8>)], filename: String) {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+
+    for (name, data) in files {
+        zip.start_file(
+            name,
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )
+        .unwrap();
+        zip.write_all(data).unwrap();
+    }
+
+    download_blob(
+        blob_from_bytes(zip.finish().unwrap().into_inner()),
+        format!("{filename}.zip"),
+    );
+}
+
 pub fn download_canvas_content(filename: String) {
     let filename_arc = Arc::new(filename);
 
@@ -0,0 +1,172 @@
+// Entry points for using the renderer outside of the browser
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::graphics::{self, Scene, Stereo, StereoMode};
+use crate::image::{register_image, with_embedded_images, ImageData, ImageSource};
+use crate::import;
+use crate::vector::Vec2D;
+
+// Materials of imported meshes are usually read from yaml, like in scene files
+pub use crate::graphics::Material;
+
+// --------------------------------------------------
+
+// Makes an image file available to scenes as a texture named like the file
+pub fn register_texture_file(path: &Path) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().to_string());
+    let image = ImageData::decode(ImageSource::Named(name), &fs::read(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    register_image(image);
+
+    Ok(())
+}
+
+// .gltf / .glb files are imported, with the files they refer to read from the same directory. The
+// same goes for the .raw volume files and the textures of .yaml scenes - files that can't be read
+// are skipped with a warning, scenes using them fail to load with the name of the missing file
+fn read_scene(scene_path: &Path) -> io::Result<Scene> {
+    let extension = scene_path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+
+    if matches!(extension.as_deref(), Some("gltf" | "glb")) {
+        let name = scene_path
+            .file_stem()
+            .map_or(String::new(), |name| name.to_string_lossy().to_string());
+        let resource = |uri: &str| fs::read(scene_path.with_file_name(uri)).ok();
+
+        import::import_gltf(&name, &fs::read(scene_path)?, &resource)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    } else {
+        // Volumes and textures refer to files next to the scene by their file name
+        let dir = match scene_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            match path
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .as_deref()
+            {
+                Some("raw") => match fs::read(&path) {
+                    Ok(data) => graphics::register_volume_file(
+                        &path.file_name().unwrap().to_string_lossy(),
+                        data,
+                    ),
+                    Err(e) => log::warn!("Skipped volume file {path:?}: {e:}"),
+                },
+                Some("png" | "jpg" | "jpeg") => {
+                    if let Err(e) = register_texture_file(&path) {
+                        log::warn!("Skipped image {path:?}: {e:}")
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        serde_yaml::from_str::<Scene>(&fs::read_to_string(scene_path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+fn load_scene(scene_path: &Path, size: Vec2D<u32>) -> io::Result<Scene> {
+    let mut scene = read_scene(scene_path)?;
+    scene.change_size(size);
+
+    Ok(scene)
+}
+
+// Writes the scene to out_path with the textures it uses from files embedded, so it loads without
+// them
+pub fn write_self_contained_scene(scene_path: &Path, out_path: &Path) -> io::Result<()> {
+    let scene = read_scene(scene_path)?;
+
+    fs::write(
+        out_path,
+        with_embedded_images(|| serde_yaml::to_string(&scene))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+    )
+}
+
+// Renders every frame of the scene's animation and writes them into out_dir as numbered pngs
+pub fn render_sequence_to_dir(
+    scene_path: &Path,
+    out_dir: &Path,
+    size: (u32, u32),
+    rays_per_pixel: u32,
+    diffuse_bounce_depth: i32,
+) -> io::Result<()> {
+    let scene = load_scene(scene_path, Vec2D::new(size.0, size.1))?;
+    let stereo = Stereo::new(StereoMode::Off, 0.0, 1.0);
+
+    fs::create_dir_all(out_dir)?;
+    for frame in 0..scene.animation.frame_count() {
+        for (frame, frame_buffer) in
+            scene.render_frames(frame..(frame + 1), rays_per_pixel, diffuse_bounce_depth, &stereo)
+        {
+            fs::write(
+                out_dir.join(Scene::frame_file_name(frame)),
+                frame_buffer.to_png(),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+// Adds the meshes of an .obj file to a scene and writes the result to out_path. The .mtl files
+// named by the .obj are looked up next to it
+pub fn import_obj_into_scene(obj_path: &Path, scene_path: &Path, out_path: &Path) -> io::Result<()> {
+    let obj = fs::read_to_string(obj_path)?;
+    let mtls = import::obj_material_libraries(&obj)
+        .into_iter()
+        .map(|name| fs::read_to_string(obj_path.with_file_name(name)))
+        .collect::<io::Result<Vec<String>>>()?;
+
+    let objects = import::import_obj(&obj, &mtls.iter().map(String::as_str).collect::<Vec<_>>())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut scene = read_scene(scene_path)?;
+    scene.object_list.objects.extend(objects.objects);
+
+    fs::write(
+        out_path,
+        serde_yaml::to_string(&scene).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+    )
+}
+
+// Adds the mesh of a .ply or .stl file with the given material to a scene and writes the result to
+// out_path
+pub fn import_mesh_into_scene(
+    mesh_path: &Path,
+    material: Material,
+    scene_path: &Path,
+    out_path: &Path,
+) -> io::Result<()> {
+    let data = fs::read(mesh_path)?;
+    let object = match mesh_path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .as_deref()
+    {
+        Some("ply") => import::import_ply(&data, material),
+        Some("stl") => import::import_stl(&data, material),
+        _ => Err("Only .ply and .stl files are supported".to_string()),
+    }
+    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut scene = read_scene(scene_path)?;
+    scene.object_list.objects.push(object);
+
+    fs::write(
+        out_path,
+        serde_yaml::to_string(&scene).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+    )
+}
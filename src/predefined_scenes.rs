@@ -3,7 +3,7 @@ use std::sync::Arc;
 use rand::{thread_rng, Rng};
 
 use crate::draw::Color;
-use crate::graphics::{Material, Object3D, ObjectList, Texture};
+use crate::graphics::{Material, Object3D, ObjectList, Texture, UvTransform};
 use crate::image::{get_const_image, ImageID};
 use crate::vector::Vec3D;
 
@@ -21,22 +21,28 @@ pub fn predefined_scenes() -> Vec<(ObjectList, Option<Color>)> {
                 fuzz: 0.1,
                 texture: Texture::Image {
                     data: get_const_image(ImageID::Earth),
+                    uv: UvTransform::default(),
                 },
             });
             let material_right = Arc::new(Material::Lambertian {
                 texture: Texture::Image {
                     data: get_const_image(ImageID::Mars),
+                    uv: UvTransform::default(),
                 },
             });
             let material_left = Arc::new(Material::Lambertian {
                 texture: Texture::Image {
                     data: get_const_image(ImageID::Jupiter),
+                    uv: UvTransform::default(),
                 },
             });
             let material_light = Arc::new(Material::DiffuseLight {
                 texture: Texture::Image {
                     data: get_const_image(ImageID::Sun),
+                    uv: UvTransform::default(),
                 },
+                strength: 1.0,
+                temperature: None,
             });
 
             (
@@ -74,12 +80,15 @@ pub fn predefined_scenes() -> Vec<(ObjectList, Option<Color>)> {
             let material_center = Arc::new(Material::Lambertian {
                 texture: Texture::Image {
                     data: get_const_image(ImageID::Brick),
+                    uv: UvTransform::default(),
                 },
             });
             let material_light = Arc::new(Material::DiffuseLight {
                 texture: Texture::SolidColor {
                     color: Color::new(255, 255, 255),
                 },
+                strength: 1.0,
+                temperature: None,
             });
             let material_ground = Arc::new(Material::Lambertian {
                 texture: Texture::Checkered {
@@ -135,6 +144,8 @@ pub fn predefined_scenes() -> Vec<(ObjectList, Option<Color>)> {
                 texture: Texture::SolidColor {
                     color: Color::new(255, 255, 255),
                 },
+                strength: 1.0,
+                temperature: None,
             });
 
             (
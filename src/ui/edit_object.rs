@@ -2,13 +2,15 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::draw::Color;
-use crate::image::{get_const_image, ImageID};
+use crate::image::{get_const_image, get_image, ImageID, ImageSource};
 use wasm_bindgen::JsCast;
 use web_sys::{HtmlElement, HtmlInputElement, HtmlSelectElement};
 
-use crate::graphics::{Material, Object3D, Texture};
-use crate::misc::get_element_by_id;
-use crate::vector::Vec3D;
+use crate::graphics::{
+    CsgOperation, Material, Object3D, Texture, Transform, UvTransform, WrapMode,
+};
+use crate::misc::{document, get_element_by_id};
+use crate::vector::{Vec2D, Vec3D, VectorOperation};
 
 // --------------------------------------------------
 
@@ -17,7 +19,40 @@ const SIZE: f64 = 1.0;
 const RADIUS: f64 = 1.0;
 const FUZZ: f64 = 0.0;
 const REFRACTION_INDEX: f64 = 0.0;
+const STRENGTH: f64 = 1.0;
+const TEMPERATURE: f64 = 0.0;
 const CHECK_SIZE: f64 = 10.0;
+const ROTATION: f64 = 0.0;
+const HEIGHT: f64 = 1.0;
+const MINOR_RADIUS: f64 = 0.25;
+const NOISE_SCALE: f64 = 4.0;
+const OCTAVES: f64 = 5.0;
+const LINE_WIDTH: f64 = 0.1;
+const UV_SCALE: f64 = 1.0;
+const UV_OFFSET: f64 = 0.0;
+
+// --------------------------------------------------
+
+// Csg objects made in the panel are a brick with a sphere in its center, their position, size and
+// radius are returned. Other csg objects can only be moved
+fn csg_brick_sphere(object: &Object3D) -> Option<(Vec3D<f32>, Vec3D<f32>, f32)> {
+    match object {
+        Object3D::Csg { left, right, .. } => match (&**left, &**right) {
+            (
+                Object3D::Brick { pos, corner, .. },
+                Object3D::Sphere {
+                    pos: center,
+                    radius,
+                    ..
+                },
+            ) if ((*pos + *corner) / 2.0 - *center).length() < 0.0001 => {
+                Some((*pos, *corner - *pos, *radius))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
 
 // --------------------------------------------------
 
@@ -27,9 +62,27 @@ pub struct EditObject {
     pub subsection_elements: HashMap<String, HtmlElement>,
     pub select_elements: HashMap<String, HtmlSelectElement>,
     pub input_elements: HashMap<String, HtmlInputElement>,
+    // Objects that can only be moved (meshes, media, instances, ...) keep their shape from here
+    pub source: Option<Object3D>,
+    // What the material inputs showed for the source, to tell whether they were changed
+    source_material: Option<Material>,
 }
 
 impl EditObject {
+    // Offers a registered image in the texture image select, unless it already is
+    pub fn add_image_option(name: &str) {
+        let select = get_element_by_id("texture_image_select");
+        let selector = format!("option[value=\"{:}\"]", name.replace('"', "\\\""));
+        if select.query_selector(&selector).ok().flatten().is_some() {
+            return;
+        }
+
+        let option = document().create_element("option").unwrap();
+        option.set_attribute("value", name).unwrap();
+        option.set_text_content(Some(name));
+        select.append_child(&option).unwrap();
+    }
+
     pub fn hide(&self) {
         self.panel_element
             .style()
@@ -72,6 +125,104 @@ impl EditObject {
                     .style()
                     .set_property("display", "flex")
                     .unwrap();
+                self.input_elements["size_z"]
+                    .style()
+                    .remove_property("display")
+                    .unwrap();
+            }
+            "quad" | "oriented_brick" => {
+                self.subsection_elements["object_multi_box"]
+                    .style()
+                    .set_property("height", "22%")
+                    .unwrap();
+
+                self.select_elements["object_type_select"].set_value(&object);
+                self.subsection_elements["line_object_size"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.subsection_elements["line_object_rotation"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+
+                // Quads only have a width and a height
+                if object == "quad" {
+                    self.input_elements["size_z"]
+                        .style()
+                        .set_property("display", "none")
+                        .unwrap();
+                } else {
+                    self.input_elements["size_z"]
+                        .style()
+                        .remove_property("display")
+                        .unwrap();
+                }
+            }
+            "plane" | "disk" | "cylinder" | "cone" | "torus" => {
+                let lines: &[&str] = match object.as_str() {
+                    "plane" => &[],
+                    "disk" => &["line_object_radius"],
+                    "cylinder" | "cone" => &["line_object_radius", "line_object_height"],
+                    _ => &["line_object_radius", "line_object_minor_radius"],
+                };
+
+                self.subsection_elements["object_multi_box"]
+                    .style()
+                    .set_property("height", &format!("{:}%", 16 + lines.len() * 6))
+                    .unwrap();
+
+                self.select_elements["object_type_select"].set_value(&object);
+                for line in lines.iter().chain(&["line_object_rotation"]) {
+                    self.subsection_elements[*line]
+                        .style()
+                        .set_property("display", "flex")
+                        .unwrap();
+                }
+            }
+            "csg" => {
+                // Sources that aren't a brick and a sphere keep their shape
+                let editable = self.source.as_ref().is_none_or(|source| {
+                    !matches!(source, Object3D::Csg { .. }) || csg_brick_sphere(source).is_some()
+                });
+
+                self.subsection_elements["object_multi_box"]
+                    .style()
+                    .set_property("height", if editable { "28%" } else { "16%" })
+                    .unwrap();
+
+                self.select_elements["object_type_select"].set_value("csg");
+                self.subsection_elements["line_object_csg"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                if editable {
+                    self.subsection_elements["line_object_size"]
+                        .style()
+                        .set_property("display", "flex")
+                        .unwrap();
+                    self.input_elements["size_z"]
+                        .style()
+                        .remove_property("display")
+                        .unwrap();
+                    self.subsection_elements["line_object_radius"]
+                        .style()
+                        .set_property("display", "flex")
+                        .unwrap();
+                }
+            }
+            "triangle"
+            | "mesh"
+            | "constant_medium"
+            | "heterogeneous_medium"
+            | "sdf"
+            | "instance" => {
+                self.subsection_elements["object_multi_box"]
+                    .style()
+                    .set_property("height", "10%")
+                    .unwrap();
+
+                self.select_elements["object_type_select"].set_value(&object);
             }
             _ => unreachable!(),
         }
@@ -120,7 +271,7 @@ impl EditObject {
             "diffuse_light" => {
                 self.subsection_elements["material_multi_box"]
                     .style()
-                    .set_property("height", "10%")
+                    .set_property("height", "22%")
                     .unwrap();
 
                 self.select_elements["material_select"].set_value("diffuse_light");
@@ -128,6 +279,26 @@ impl EditObject {
                     .style()
                     .set_property("display", "flex")
                     .unwrap();
+                self.subsection_elements["line_material_strength"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.subsection_elements["line_material_temperature"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+            }
+            "isotropic" => {
+                self.subsection_elements["material_multi_box"]
+                    .style()
+                    .set_property("height", "10%")
+                    .unwrap();
+
+                self.select_elements["material_select"].set_value("isotropic");
+                self.subsection_elements["texture_multi_box"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
             }
             _ => unreachable!(),
         }
@@ -162,10 +333,22 @@ impl EditObject {
                     Ok(v) => elem.1.set_value_as_number(v.clamp(0.001, 10000.0)),
                     Err(_) => elem.1.set_value_as_number(SIZE),
                 },
+                "rotation_x" | "rotation_y" | "rotation_z" => match elem.1.value().parse::<f64>() {
+                    Ok(v) => elem.1.set_value_as_number(v.clamp(-360.0, 360.0)),
+                    Err(_) => elem.1.set_value_as_number(ROTATION),
+                },
                 "object_radius" => match elem.1.value().parse::<f64>() {
                     Ok(v) => elem.1.set_value_as_number(v.clamp(0.001, 1000000.0)),
                     Err(_) => elem.1.set_value_as_number(RADIUS),
                 },
+                "object_minor_radius" => match elem.1.value().parse::<f64>() {
+                    Ok(v) => elem.1.set_value_as_number(v.clamp(0.001, 1000000.0)),
+                    Err(_) => elem.1.set_value_as_number(MINOR_RADIUS),
+                },
+                "object_height" => match elem.1.value().parse::<f64>() {
+                    Ok(v) => elem.1.set_value_as_number(v.clamp(0.001, 1000000.0)),
+                    Err(_) => elem.1.set_value_as_number(HEIGHT),
+                },
                 "material_fuzz" => match elem.1.value().parse::<f64>() {
                     Ok(v) => elem.1.set_value_as_number(v.clamp(0.0, 1.0)),
                     Err(_) => elem.1.set_value_as_number(FUZZ),
@@ -174,6 +357,16 @@ impl EditObject {
                     Ok(v) => elem.1.set_value_as_number(v.clamp(-1.0, 1.0)),
                     Err(_) => elem.1.set_value_as_number(REFRACTION_INDEX),
                 },
+                "material_strength" => match elem.1.value().parse::<f64>() {
+                    Ok(v) => elem.1.set_value_as_number(v.clamp(0.0, 1000000.0)),
+                    Err(_) => elem.1.set_value_as_number(STRENGTH),
+                },
+                // 0 turns the tint off, anything else is kept in the range blackbodies are known
+                "material_temperature" => match elem.1.value().parse::<f64>() {
+                    Ok(v) if v <= 0.0 => elem.1.set_value_as_number(0.0),
+                    Ok(v) => elem.1.set_value_as_number(v.clamp(1000.0, 40000.0)),
+                    Err(_) => elem.1.set_value_as_number(TEMPERATURE),
+                },
                 "texture_color" => {}
                 "texture_color_odd" => {}
                 "texture_color_even" => {}
@@ -181,6 +374,36 @@ impl EditObject {
                     Ok(v) => elem.1.set_value_as_number(v.clamp(0.5, 100.0)),
                     Err(_) => elem.1.set_value_as_number(CHECK_SIZE),
                 },
+                "texture_color_low" => {}
+                "texture_color_high" => {}
+                "texture_noise_scale" => match elem.1.value().parse::<f64>() {
+                    Ok(v) => elem.1.set_value_as_number(v.clamp(0.01, 1000.0)),
+                    Err(_) => elem.1.set_value_as_number(NOISE_SCALE),
+                },
+                "texture_octaves" => match elem.1.value().parse::<f64>() {
+                    Ok(v) => elem.1.set_value_as_number(v.round().clamp(1.0, 10.0)),
+                    Err(_) => elem.1.set_value_as_number(OCTAVES),
+                },
+                "texture_line_width" => match elem.1.value().parse::<f64>() {
+                    Ok(v) => elem.1.set_value_as_number(v.clamp(0.0, 1.0)),
+                    Err(_) => elem.1.set_value_as_number(LINE_WIDTH),
+                },
+                "texture_uv_scale_x" | "texture_uv_scale_y" => {
+                    match elem.1.value().parse::<f64>() {
+                        Ok(v) => elem.1.set_value_as_number(v.clamp(-10000.0, 10000.0)),
+                        Err(_) => elem.1.set_value_as_number(UV_SCALE),
+                    }
+                }
+                "texture_uv_offset_x" | "texture_uv_offset_y" => {
+                    match elem.1.value().parse::<f64>() {
+                        Ok(v) => elem.1.set_value_as_number(v.clamp(-10000.0, 10000.0)),
+                        Err(_) => elem.1.set_value_as_number(UV_OFFSET),
+                    }
+                }
+                "texture_uv_rotation" => match elem.1.value().parse::<f64>() {
+                    Ok(v) => elem.1.set_value_as_number(v.clamp(-360.0, 360.0)),
+                    Err(_) => elem.1.set_value_as_number(ROTATION),
+                },
                 _ => unreachable!(),
             }
         }
@@ -244,10 +467,240 @@ impl EditObject {
                 ),
                 Arc::new(self.get_material()),
             ),
+            // The quad lies in its local xy plane before being rotated
+            "quad" => {
+                let size = self.input_vec3d("size", SIZE);
+                let orientation = Transform::new(
+                    Vec3D::new(0.0, 0.0, 0.0),
+                    self.input_vec3d("rotation", ROTATION),
+                    Vec3D::new(1.0, 1.0, 1.0),
+                );
+
+                Object3D::Quad {
+                    pos: self.input_vec3d("pos", POS.x),
+                    u: orientation.to_world_vector(Vec3D::new(size.x, 0.0, 0.0)),
+                    v: orientation.to_world_vector(Vec3D::new(0.0, size.y, 0.0)),
+                    material: Arc::new(self.get_material()),
+                }
+            }
+            "oriented_brick" => Object3D::oriented_brick(
+                self.input_vec3d("pos", POS.x),
+                self.input_vec3d("size", SIZE),
+                self.input_vec3d("rotation", ROTATION),
+                Arc::new(self.get_material()),
+            ),
+            "plane" | "disk" | "cylinder" | "cone" | "torus" => {
+                let transform = Transform::new(
+                    self.input_vec3d("pos", POS.x),
+                    self.input_vec3d("rotation", ROTATION),
+                    self.source_scale(),
+                );
+                let material = Arc::new(self.get_material());
+                let input = |name: &str, default: f64| {
+                    self.input_elements[name]
+                        .value()
+                        .parse::<f32>()
+                        .unwrap_or(default as f32)
+                };
+
+                match self.select_elements["object_type_select"].value().as_str() {
+                    "plane" => Object3D::Plane {
+                        transform,
+                        material,
+                    },
+                    "disk" => Object3D::Disk {
+                        radius: input("object_radius", RADIUS),
+                        transform,
+                        material,
+                    },
+                    "cylinder" => Object3D::Cylinder {
+                        radius: input("object_radius", RADIUS),
+                        height: input("object_height", HEIGHT),
+                        transform,
+                        material,
+                    },
+                    "cone" => Object3D::Cone {
+                        radius: input("object_radius", RADIUS),
+                        height: input("object_height", HEIGHT),
+                        transform,
+                        material,
+                    },
+                    _ => Object3D::Torus {
+                        major_radius: input("object_radius", RADIUS),
+                        minor_radius: input("object_minor_radius", MINOR_RADIUS),
+                        transform,
+                        material,
+                    },
+                }
+            }
+            "csg" => {
+                let operation = self.get_csg_operation();
+                let pos = self.input_vec3d("pos", POS.x);
+
+                match self.source.as_ref() {
+                    Some(source @ Object3D::Csg { .. }) if csg_brick_sphere(source).is_none() => {
+                        match self.moved_source() {
+                            Object3D::Csg { left, right, .. } => Object3D::Csg {
+                                operation,
+                                left,
+                                right,
+                            },
+                            _ => unreachable!(),
+                        }
+                    }
+                    _ => {
+                        let size = self.input_vec3d("size", SIZE);
+                        // The children keep their own materials unless the material is changed
+                        let (left_material, right_material) =
+                            match (self.changed_material(), self.source.as_ref()) {
+                                (None, Some(Object3D::Csg { left, right, .. })) => {
+                                    (left.material().clone(), right.material().clone())
+                                }
+                                (material, _) => {
+                                    let material =
+                                        Arc::new(material.unwrap_or_else(|| self.get_material()));
+                                    (material.clone(), material)
+                                }
+                            };
+
+                        Object3D::Csg {
+                            operation,
+                            left: Box::new(Object3D::brick(pos, size, left_material)),
+                            right: Box::new(Object3D::Sphere {
+                                pos: pos + size / 2.0,
+                                radius: self.input_elements["object_radius"]
+                                    .value()
+                                    .parse::<f32>()
+                                    .unwrap_or(RADIUS as f32),
+                                material: right_material,
+                            }),
+                        }
+                    }
+                }
+            }
+            "triangle"
+            | "mesh"
+            | "constant_medium"
+            | "heterogeneous_medium"
+            | "sdf"
+            | "instance" => self.moved_source(),
             _ => unreachable!(),
         }
     }
 
+    // Objects whose shape can't be edited here are only moved. Their materials (like the ones of
+    // an instance's shared group or of csg children) are kept unless the material is changed
+    fn moved_source(&self) -> Object3D {
+        let source = self
+            .source
+            .as_ref()
+            .expect("Objects that can only be moved are edited with their source set");
+        let moved = source.translated(self.input_vec3d("pos", POS.x) - source.position());
+
+        match self.changed_material() {
+            Some(material) => moved.with_material(material),
+            None => moved,
+        }
+    }
+
+    // The material of the inputs, if it differs from the one they showed for the source
+    fn changed_material(&self) -> Option<Material> {
+        Some(self.get_material()).filter(|material| self.source_material.as_ref() != Some(material))
+    }
+
+    // Scales can't be edited, the one of the edited primitive is kept
+    fn source_scale(&self) -> Vec3D<f32> {
+        match &self.source {
+            Some(
+                Object3D::Plane { transform, .. }
+                | Object3D::Disk { transform, .. }
+                | Object3D::Cylinder { transform, .. }
+                | Object3D::Cone { transform, .. }
+                | Object3D::Torus { transform, .. },
+            ) => transform.scale,
+            _ => Vec3D::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    fn get_csg_operation(&self) -> CsgOperation {
+        match self.select_elements["csg_operation_select"].value().as_str() {
+            "union" => CsgOperation::Union,
+            "intersection" => CsgOperation::Intersection,
+            "difference" => CsgOperation::Difference,
+            _ => unreachable!(),
+        }
+    }
+
+    fn get_uv_transform(&self) -> UvTransform {
+        let value = |name: &str, default: f64| {
+            self.input_elements[name]
+                .value()
+                .parse::<f32>()
+                .unwrap_or(default as f32)
+        };
+
+        UvTransform {
+            scale: Vec2D::new(
+                value("texture_uv_scale_x", UV_SCALE),
+                value("texture_uv_scale_y", UV_SCALE),
+            ),
+            offset: Vec2D::new(
+                value("texture_uv_offset_x", UV_OFFSET),
+                value("texture_uv_offset_y", UV_OFFSET),
+            ),
+            rotation: value("texture_uv_rotation", ROTATION),
+            wrap: match self.select_elements["texture_wrap_select"].value().as_str() {
+                "clamp" => WrapMode::Clamp,
+                "repeat" => WrapMode::Repeat,
+                "mirror" => WrapMode::Mirror,
+                _ => unreachable!(),
+            },
+        }
+    }
+
+    fn set_uv_transform(&self, uv: &UvTransform) {
+        for line in [
+            "line_texture_uv_scale",
+            "line_texture_uv_offset",
+            "line_texture_uv_rotation",
+            "line_texture_wrap",
+        ] {
+            self.subsection_elements[line]
+                .style()
+                .set_property("display", "flex")
+                .unwrap();
+        }
+
+        self.input_elements["texture_uv_scale_x"].set_value_as_number(uv.scale.x.into());
+        self.input_elements["texture_uv_scale_y"].set_value_as_number(uv.scale.y.into());
+        self.input_elements["texture_uv_offset_x"].set_value_as_number(uv.offset.x.into());
+        self.input_elements["texture_uv_offset_y"].set_value_as_number(uv.offset.y.into());
+        self.input_elements["texture_uv_rotation"].set_value_as_number(uv.rotation.into());
+        self.select_elements["texture_wrap_select"].set_value(match uv.wrap {
+            WrapMode::Clamp => "clamp",
+            WrapMode::Repeat => "repeat",
+            WrapMode::Mirror => "mirror",
+        });
+    }
+
+    // Reads the "<prefix>_x", "<prefix>_y" and "<prefix>_z" inputs
+    fn input_vec3d(&self, prefix: &str, default: f64) -> Vec3D<f32> {
+        let value = |axis: &str| {
+            self.input_elements[&format!("{prefix:}_{axis:}")]
+                .value()
+                .parse::<f32>()
+                .unwrap_or(default as f32)
+        };
+
+        Vec3D::new(value("x"), value("y"), value("z"))
+    }
+
+    fn set_input_vec3d(&self, prefix: &str, value: Vec3D<f32>) {
+        self.input_elements[&format!("{prefix:}_x")].set_value_as_number(value.x.into());
+        self.input_elements[&format!("{prefix:}_y")].set_value_as_number(value.y.into());
+        self.input_elements[&format!("{prefix:}_z")].set_value_as_number(value.z.into());
+    }
+
     fn set_object(&self, object: &Object3D) {
         self.input_elements["pos_x"].set_value_as_number(POS.x);
         self.input_elements["pos_y"].set_value_as_number(POS.y);
@@ -258,6 +711,12 @@ impl EditObject {
         self.input_elements["size_z"].set_value_as_number(SIZE);
 
         self.input_elements["object_radius"].set_value_as_number(RADIUS);
+        self.input_elements["object_minor_radius"].set_value_as_number(MINOR_RADIUS);
+        self.input_elements["object_height"].set_value_as_number(HEIGHT);
+
+        self.input_elements["rotation_x"].set_value_as_number(ROTATION);
+        self.input_elements["rotation_y"].set_value_as_number(ROTATION);
+        self.input_elements["rotation_z"].set_value_as_number(ROTATION);
 
         match object {
             Object3D::Sphere {
@@ -293,6 +752,114 @@ impl EditObject {
 
                 self.set_material(&*material)
             }
+            Object3D::Quad {
+                pos,
+                u,
+                v,
+                material,
+            } => {
+                self.select_elements["object_type_select"].set_value("quad");
+
+                self.set_input_vec3d("pos", *pos);
+                self.set_input_vec3d("size", Vec3D::new(u.length(), v.length(), SIZE as f32));
+                self.set_input_vec3d("rotation", Transform::rotation_from_axes(*u, *v));
+
+                self.set_material(material)
+            }
+            Object3D::OrientedBrick {
+                pos,
+                size,
+                rotation,
+                material,
+                ..
+            } => {
+                self.select_elements["object_type_select"].set_value("oriented_brick");
+
+                self.set_input_vec3d("pos", *pos);
+                self.set_input_vec3d("size", *size);
+                self.set_input_vec3d("rotation", *rotation);
+
+                self.set_material(material)
+            }
+            Object3D::Plane { transform, .. }
+            | Object3D::Disk { transform, .. }
+            | Object3D::Cylinder { transform, .. }
+            | Object3D::Cone { transform, .. }
+            | Object3D::Torus { transform, .. } => {
+                self.select_elements["object_type_select"].set_value(match object {
+                    Object3D::Plane { .. } => "plane",
+                    Object3D::Disk { .. } => "disk",
+                    Object3D::Cylinder { .. } => "cylinder",
+                    Object3D::Cone { .. } => "cone",
+                    _ => "torus",
+                });
+
+                self.set_input_vec3d("pos", transform.translation);
+                self.set_input_vec3d("rotation", transform.rotation);
+                match object {
+                    Object3D::Disk { radius, .. } => {
+                        self.input_elements["object_radius"].set_value_as_number(*radius as f64);
+                    }
+                    Object3D::Cylinder { radius, height, .. }
+                    | Object3D::Cone { radius, height, .. } => {
+                        self.input_elements["object_radius"].set_value_as_number(*radius as f64);
+                        self.input_elements["object_height"].set_value_as_number(*height as f64);
+                    }
+                    Object3D::Torus {
+                        major_radius,
+                        minor_radius,
+                        ..
+                    } => {
+                        self.input_elements["object_radius"]
+                            .set_value_as_number(*major_radius as f64);
+                        self.input_elements["object_minor_radius"]
+                            .set_value_as_number(*minor_radius as f64);
+                    }
+                    _ => {}
+                }
+
+                self.set_material(object.material())
+            }
+            Object3D::Csg { operation, .. } => {
+                self.select_elements["object_type_select"].set_value("csg");
+                self.select_elements["csg_operation_select"].set_value(match operation {
+                    CsgOperation::Union => "union",
+                    CsgOperation::Intersection => "intersection",
+                    CsgOperation::Difference => "difference",
+                });
+
+                if let Some((pos, size, radius)) = csg_brick_sphere(object) {
+                    self.set_input_vec3d("pos", pos);
+                    self.set_input_vec3d("size", size);
+                    self.input_elements["object_radius"].set_value_as_number(radius as f64);
+                } else {
+                    self.set_input_vec3d("pos", object.position());
+                }
+
+                self.set_material(object.material())
+            }
+            Object3D::Triangle { .. }
+            | Object3D::Mesh { .. }
+            | Object3D::ConstantMedium { .. }
+            | Object3D::HeterogeneousMedium { .. }
+            | Object3D::Sdf { .. }
+            | Object3D::Instance { .. } => {
+                self.select_elements["object_type_select"].set_value(match object {
+                    Object3D::Triangle { .. } => "triangle",
+                    Object3D::Mesh { .. } => "mesh",
+                    Object3D::ConstantMedium { .. } => "constant_medium",
+                    Object3D::HeterogeneousMedium { .. } => "heterogeneous_medium",
+                    Object3D::Sdf { .. } => "sdf",
+                    _ => "instance",
+                });
+
+                let pos = object.position();
+                self.input_elements["pos_x"].set_value_as_number(pos.x.into());
+                self.input_elements["pos_y"].set_value_as_number(pos.y.into());
+                self.input_elements["pos_z"].set_value_as_number(pos.z.into());
+
+                self.set_material(object.material())
+            }
             Object3D::XYRect { .. } | Object3D::XZRect { .. } | Object3D::YZRect { .. } => {
                 unimplemented!()
             }
@@ -345,10 +912,10 @@ impl EditObject {
                     .unwrap();
                 self.input_elements["texture_check_size"].set_value_as_number(*check_size as f64);
             }
-            Texture::Image { data } => {
+            Texture::Image { data, uv } => {
                 self.subsection_elements["texture_multi_box"]
                     .style()
-                    .set_property("height", "16%")
+                    .set_property("height", "40%")
                     .unwrap();
 
                 self.select_elements["texture_select"].set_value("image");
@@ -357,8 +924,201 @@ impl EditObject {
                     .style()
                     .set_property("display", "flex")
                     .unwrap();
-                self.select_elements["texture_image_select"]
-                    .set_value(&data.data.1.to_string().to_lowercase())
+                // Built-in images have lowercase option values, registered ones keep their name
+                match &data.data.1 {
+                    ImageSource::BuiltIn(id) => self.select_elements["texture_image_select"]
+                        .set_value(&id.to_string().to_lowercase()),
+                    ImageSource::Named(name) => {
+                        EditObject::add_image_option(name);
+                        self.select_elements["texture_image_select"].set_value(name);
+                    }
+                }
+
+                self.set_uv_transform(uv);
+            }
+            Texture::VertexColor => {
+                self.subsection_elements["texture_multi_box"]
+                    .style()
+                    .set_property("height", "10%")
+                    .unwrap();
+
+                self.select_elements["texture_select"].set_value("vertex_color");
+            }
+            Texture::Noise {
+                low_color,
+                high_color,
+                scale,
+                octaves,
+            }
+            | Texture::Turbulence {
+                low_color,
+                high_color,
+                scale,
+                octaves,
+            }
+            | Texture::Marble {
+                low_color,
+                high_color,
+                scale,
+                octaves,
+            }
+            | Texture::Wood {
+                low_color,
+                high_color,
+                scale,
+                octaves,
+            } => {
+                self.subsection_elements["texture_multi_box"]
+                    .style()
+                    .set_property("height", "34%")
+                    .unwrap();
+
+                self.select_elements["texture_select"].set_value(match texture {
+                    Texture::Noise { .. } => "noise",
+                    Texture::Turbulence { .. } => "turbulence",
+                    Texture::Marble { .. } => "marble",
+                    _ => "wood",
+                });
+
+                self.subsection_elements["line_texture_color_low"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.input_elements["texture_color_low"].set_value(&low_color.to_string());
+
+                self.subsection_elements["line_texture_color_high"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.input_elements["texture_color_high"].set_value(&high_color.to_string());
+
+                self.subsection_elements["line_texture_noise_scale"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.input_elements["texture_noise_scale"].set_value_as_number(*scale as f64);
+
+                self.subsection_elements["line_texture_octaves"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.input_elements["texture_octaves"].set_value_as_number(*octaves as f64);
+            }
+            Texture::Voronoi {
+                low_color,
+                high_color,
+                scale,
+            } => {
+                self.subsection_elements["texture_multi_box"]
+                    .style()
+                    .set_property("height", "28%")
+                    .unwrap();
+
+                self.select_elements["texture_select"].set_value("voronoi");
+
+                self.subsection_elements["line_texture_color_low"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.input_elements["texture_color_low"].set_value(&low_color.to_string());
+
+                self.subsection_elements["line_texture_color_high"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.input_elements["texture_color_high"].set_value(&high_color.to_string());
+
+                self.subsection_elements["line_texture_noise_scale"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.input_elements["texture_noise_scale"].set_value_as_number(*scale as f64);
+            }
+            // Only the colors of gradients can be edited, the rest is kept from the source
+            Texture::LinearGradient {
+                low_color,
+                high_color,
+                ..
+            }
+            | Texture::RadialGradient {
+                low_color,
+                high_color,
+                ..
+            } => {
+                self.subsection_elements["texture_multi_box"]
+                    .style()
+                    .set_property("height", "22%")
+                    .unwrap();
+
+                self.select_elements["texture_select"].set_value(match texture {
+                    Texture::LinearGradient { .. } => "linear_gradient",
+                    _ => "radial_gradient",
+                });
+
+                self.subsection_elements["line_texture_color_low"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.input_elements["texture_color_low"].set_value(&low_color.to_string());
+
+                self.subsection_elements["line_texture_color_high"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.input_elements["texture_color_high"].set_value(&high_color.to_string());
+            }
+            Texture::Stripes {
+                odd_color,
+                even_color,
+                count,
+            }
+            | Texture::Grid {
+                line_color: odd_color,
+                fill_color: even_color,
+                count,
+                ..
+            } => {
+                self.subsection_elements["texture_multi_box"]
+                    .style()
+                    .set_property("height", "28%")
+                    .unwrap();
+
+                self.select_elements["texture_select"].set_value(match texture {
+                    Texture::Stripes { .. } => "stripes",
+                    _ => "grid",
+                });
+
+                self.subsection_elements["line_texture_color_odd"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.input_elements["texture_color_odd"].set_value(&odd_color.to_string());
+
+                self.subsection_elements["line_texture_color_even"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.input_elements["texture_color_even"].set_value(&even_color.to_string());
+
+                self.subsection_elements["line_texture_noise_scale"]
+                    .style()
+                    .set_property("display", "flex")
+                    .unwrap();
+                self.input_elements["texture_noise_scale"].set_value_as_number(*count as f64);
+
+                if let Texture::Grid { line_width, .. } = texture {
+                    self.subsection_elements["texture_multi_box"]
+                        .style()
+                        .set_property("height", "34%")
+                        .unwrap();
+
+                    self.subsection_elements["line_texture_line_width"]
+                        .style()
+                        .set_property("display", "flex")
+                        .unwrap();
+                    self.input_elements["texture_line_width"]
+                        .set_value_as_number(*line_width as f64);
+                }
             }
         }
     }
@@ -376,35 +1136,138 @@ impl EditObject {
                     .parse::<f32>()
                     .unwrap_or(CHECK_SIZE as f32),
             },
-            "image" => match self.select_elements["texture_image_select"]
-                .value()
-                .to_lowercase()
-                .as_str()
-            {
-                "brick" => Texture::Image {
-                    data: get_const_image(ImageID::Brick),
-                },
-                "earth" => Texture::Image {
-                    data: get_const_image(ImageID::Earth),
-                },
-                "jupiter" => Texture::Image {
-                    data: get_const_image(ImageID::Jupiter),
+            "image" => Texture::Image {
+                data: match self.select_elements["texture_image_select"].value().as_str() {
+                    "brick" => get_const_image(ImageID::Brick),
+                    "earth" => get_const_image(ImageID::Earth),
+                    "jupiter" => get_const_image(ImageID::Jupiter),
+                    "mars" => get_const_image(ImageID::Mars),
+                    "sun" => get_const_image(ImageID::Sun),
+                    "rust" => get_const_image(ImageID::Rust),
+                    name => match get_image(&ImageSource::Named(name.to_string())) {
+                        Some(image) => image,
+                        None => {
+                            log::error!("Image {name:?} is not loaded");
+                            return self.current_texture();
+                        }
+                    },
                 },
-                "mars" => Texture::Image {
-                    data: get_const_image(ImageID::Mars),
-                },
-                "sun" => Texture::Image {
-                    data: get_const_image(ImageID::Sun),
-                },
-                "rust" => Texture::Image {
-                    data: get_const_image(ImageID::Rust),
-                },
-                _ => unreachable!(),
+                uv: self.get_uv_transform(),
             },
-            _ => unreachable!(),
+            "vertex_color" => Texture::VertexColor,
+            "noise" | "turbulence" | "marble" | "wood" | "voronoi" | "linear_gradient"
+            | "radial_gradient" | "stripes" | "grid" => {
+                let low_color = Color::from_hex_str(self.input_elements["texture_color_low"].value());
+                let high_color =
+                    Color::from_hex_str(self.input_elements["texture_color_high"].value());
+                let odd_color = Color::from_hex_str(self.input_elements["texture_color_odd"].value());
+                let even_color =
+                    Color::from_hex_str(self.input_elements["texture_color_even"].value());
+                let scale = self.input_elements["texture_noise_scale"]
+                    .value()
+                    .parse::<f32>()
+                    .unwrap_or(NOISE_SCALE as f32);
+                let octaves = self.input_elements["texture_octaves"]
+                    .value()
+                    .parse::<u32>()
+                    .unwrap_or(OCTAVES as u32);
+                let current_texture = self.current_texture();
+
+                match self.select_elements["texture_select"].value().as_str() {
+                    "noise" => Texture::Noise {
+                        low_color,
+                        high_color,
+                        scale,
+                        octaves,
+                    },
+                    "turbulence" => Texture::Turbulence {
+                        low_color,
+                        high_color,
+                        scale,
+                        octaves,
+                    },
+                    "marble" => Texture::Marble {
+                        low_color,
+                        high_color,
+                        scale,
+                        octaves,
+                    },
+                    "wood" => Texture::Wood {
+                        low_color,
+                        high_color,
+                        scale,
+                        octaves,
+                    },
+                    "voronoi" => Texture::Voronoi {
+                        low_color,
+                        high_color,
+                        scale,
+                    },
+                    // Only the colors of gradients are edited, the rest comes from the source
+                    "linear_gradient" => match current_texture {
+                        Texture::LinearGradient {
+                            space, start, end, ..
+                        } => Texture::LinearGradient {
+                            low_color,
+                            high_color,
+                            space,
+                            start,
+                            end,
+                        },
+                        texture => {
+                            log::error!("Only objects with a linear gradient can keep one");
+                            texture
+                        }
+                    },
+                    "radial_gradient" => match current_texture {
+                        Texture::RadialGradient {
+                            space,
+                            center,
+                            radius,
+                            ..
+                        } => Texture::RadialGradient {
+                            low_color,
+                            high_color,
+                            space,
+                            center,
+                            radius,
+                        },
+                        texture => {
+                            log::error!("Only objects with a radial gradient can keep one");
+                            texture
+                        }
+                    },
+                    "stripes" => Texture::Stripes {
+                        odd_color,
+                        even_color,
+                        count: scale,
+                    },
+                    _ => Texture::Grid {
+                        line_color: odd_color,
+                        fill_color: even_color,
+                        count: scale,
+                        line_width: self.input_elements["texture_line_width"]
+                            .value()
+                            .parse::<f32>()
+                            .unwrap_or(LINE_WIDTH as f32),
+                    },
+                }
+            }
+            texture => {
+                log::error!("Unknown texture {texture:?}");
+                self.current_texture()
+            }
         }
     }
 
+    // What the edited object shows, kept when the inputs don't describe a texture
+    fn current_texture(&self) -> Texture {
+        self.source
+            .as_ref()
+            .and_then(|source| source.material().texture().cloned())
+            .unwrap_or_default()
+    }
+
     fn set_material(&self, material: &Material) {
         match material {
             Material::Lambertian { texture } => {
@@ -430,18 +1293,36 @@ impl EditObject {
                     String::from("dielectric"),
                 );
             }
-            Material::DiffuseLight { texture } => {
+            Material::DiffuseLight {
+                texture,
+                strength,
+                temperature,
+            } => {
                 self.set_texture(texture);
+                self.input_elements["material_strength"].set_value_as_number(*strength as f64);
+                self.input_elements["material_temperature"]
+                    .set_value_as_number(temperature.unwrap_or(0.0) as f64);
                 self.update_sub_visibility(
                     self.select_elements["object_type_select"].value(),
                     String::from("diffuse_light"),
                 );
             }
+            Material::Isotropic { texture } => {
+                self.set_texture(texture);
+                self.update_sub_visibility(
+                    self.select_elements["object_type_select"].value(),
+                    String::from("isotropic"),
+                );
+            }
+            // Maps and cutouts are kept by get_material
+            Material::Bumped { .. } | Material::Cutout { .. } => {
+                self.set_material(material.base())
+            }
         }
     }
 
     fn get_material(&self) -> Material {
-        match self.select_elements["material_select"].value().as_str() {
+        let material = match self.select_elements["material_select"].value().as_str() {
             "lambertian" => Material::Lambertian {
                 texture: self.get_texture(),
             },
@@ -455,8 +1336,22 @@ impl EditObject {
             },
             "diffuse_light" => Material::DiffuseLight {
                 texture: self.get_texture(),
+                strength: self.input_elements["material_strength"].value_as_number() as f32,
+                temperature: Some(
+                    self.input_elements["material_temperature"].value_as_number() as f32,
+                )
+                .filter(|temperature| *temperature > 0.0),
+            },
+            "isotropic" => Material::Isotropic {
+                texture: self.get_texture(),
             },
             _ => unreachable!(),
+        };
+
+        // Surface maps and cutouts can't be edited here, the source's ones are put back
+        match self.source.as_ref() {
+            Some(source) => source.material().rewrapped(material),
+            None => material,
         }
     }
 }
@@ -506,12 +1401,42 @@ impl Default for EditObject {
                     .dyn_into::<HtmlInputElement>()
                     .unwrap(),
             ),
+            (
+                "rotation_x".to_string(),
+                get_element_by_id("rotation_x")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "rotation_y".to_string(),
+                get_element_by_id("rotation_y")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "rotation_z".to_string(),
+                get_element_by_id("rotation_z")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
             (
                 "object_radius".to_string(),
                 get_element_by_id("object_radius")
                     .dyn_into::<HtmlInputElement>()
                     .unwrap(),
             ),
+            (
+                "object_minor_radius".to_string(),
+                get_element_by_id("object_minor_radius")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "object_height".to_string(),
+                get_element_by_id("object_height")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
             (
                 "material_fuzz".to_string(),
                 get_element_by_id("material_fuzz")
@@ -530,6 +1455,18 @@ impl Default for EditObject {
                     .dyn_into::<HtmlInputElement>()
                     .unwrap(),
             ),
+            (
+                "material_strength".to_string(),
+                get_element_by_id("material_strength")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "material_temperature".to_string(),
+                get_element_by_id("material_temperature")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
             (
                 "texture_color".to_string(),
                 get_element_by_id("texture_color")
@@ -554,6 +1491,66 @@ impl Default for EditObject {
                     .dyn_into::<HtmlInputElement>()
                     .unwrap(),
             ),
+            (
+                "texture_color_low".to_string(),
+                get_element_by_id("texture_color_low")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "texture_color_high".to_string(),
+                get_element_by_id("texture_color_high")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "texture_noise_scale".to_string(),
+                get_element_by_id("texture_noise_scale")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "texture_octaves".to_string(),
+                get_element_by_id("texture_octaves")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "texture_line_width".to_string(),
+                get_element_by_id("texture_line_width")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "texture_uv_scale_x".to_string(),
+                get_element_by_id("texture_uv_scale_x")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "texture_uv_scale_y".to_string(),
+                get_element_by_id("texture_uv_scale_y")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "texture_uv_offset_x".to_string(),
+                get_element_by_id("texture_uv_offset_x")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "texture_uv_offset_y".to_string(),
+                get_element_by_id("texture_uv_offset_y")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "texture_uv_rotation".to_string(),
+                get_element_by_id("texture_uv_rotation")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
         ]);
 
         let subsection_elements = HashMap::from([
@@ -561,10 +1558,26 @@ impl Default for EditObject {
                 "line_object_radius".to_string(),
                 get_element_by_id("line_object_radius"),
             ),
+            (
+                "line_object_minor_radius".to_string(),
+                get_element_by_id("line_object_minor_radius"),
+            ),
+            (
+                "line_object_height".to_string(),
+                get_element_by_id("line_object_height"),
+            ),
             (
                 "line_object_size".to_string(),
                 get_element_by_id("line_object_size"),
             ),
+            (
+                "line_object_rotation".to_string(),
+                get_element_by_id("line_object_rotation"),
+            ),
+            (
+                "line_object_csg".to_string(),
+                get_element_by_id("line_object_csg"),
+            ),
             (
                 "line_material_fuzz".to_string(),
                 get_element_by_id("line_material_fuzz"),
@@ -573,6 +1586,14 @@ impl Default for EditObject {
                 "line_material_refraction_index".to_string(),
                 get_element_by_id("line_material_refraction_index"),
             ),
+            (
+                "line_material_strength".to_string(),
+                get_element_by_id("line_material_strength"),
+            ),
+            (
+                "line_material_temperature".to_string(),
+                get_element_by_id("line_material_temperature"),
+            ),
             (
                 "line_texture_color".to_string(),
                 get_element_by_id("line_texture_color"),
@@ -593,6 +1614,42 @@ impl Default for EditObject {
                 "line_texture_image".to_string(),
                 get_element_by_id("line_texture_image"),
             ),
+            (
+                "line_texture_color_low".to_string(),
+                get_element_by_id("line_texture_color_low"),
+            ),
+            (
+                "line_texture_color_high".to_string(),
+                get_element_by_id("line_texture_color_high"),
+            ),
+            (
+                "line_texture_noise_scale".to_string(),
+                get_element_by_id("line_texture_noise_scale"),
+            ),
+            (
+                "line_texture_octaves".to_string(),
+                get_element_by_id("line_texture_octaves"),
+            ),
+            (
+                "line_texture_line_width".to_string(),
+                get_element_by_id("line_texture_line_width"),
+            ),
+            (
+                "line_texture_uv_scale".to_string(),
+                get_element_by_id("line_texture_uv_scale"),
+            ),
+            (
+                "line_texture_uv_offset".to_string(),
+                get_element_by_id("line_texture_uv_offset"),
+            ),
+            (
+                "line_texture_uv_rotation".to_string(),
+                get_element_by_id("line_texture_uv_rotation"),
+            ),
+            (
+                "line_texture_wrap".to_string(),
+                get_element_by_id("line_texture_wrap"),
+            ),
             (
                 "object_multi_box".to_string(),
                 get_element_by_id("object_multi_box"),
@@ -618,6 +1675,12 @@ impl Default for EditObject {
                     .dyn_into::<HtmlSelectElement>()
                     .unwrap(),
             ),
+            (
+                "csg_operation_select".to_string(),
+                get_element_by_id("csg_operation_select")
+                    .dyn_into::<HtmlSelectElement>()
+                    .unwrap(),
+            ),
             (
                 "material_select".to_string(),
                 get_element_by_id("material_select")
@@ -636,6 +1699,12 @@ impl Default for EditObject {
                     .dyn_into::<HtmlSelectElement>()
                     .unwrap(),
             ),
+            (
+                "texture_wrap_select".to_string(),
+                get_element_by_id("texture_wrap_select")
+                    .dyn_into::<HtmlSelectElement>()
+                    .unwrap(),
+            ),
         ]);
 
         let out = EditObject {
@@ -643,6 +1712,8 @@ impl Default for EditObject {
             subsection_elements,
             select_elements,
             input_elements,
+            source: None,
+            source_material: None,
         };
         out.hide_sub_lines();
 
@@ -652,9 +1723,13 @@ impl Default for EditObject {
 
 impl From<Object3D> for EditObject {
     fn from(item: Object3D) -> EditObject {
-        let edit_object = EditObject::default();
-
+        // The source is needed to tell which inputs to show
+        let mut edit_object = EditObject {
+            source: Some(item.clone()),
+            ..EditObject::default()
+        };
         edit_object.set_object(&item);
+        edit_object.source_material = Some(edit_object.get_material());
 
         edit_object
     }
@@ -1,8 +1,9 @@
+use crate::graphics::{Projection, Stereo, StereoMode};
 use crate::misc::get_element_by_id;
 use crate::vector::Vec2D;
 use std::collections::HashMap;
 use wasm_bindgen::JsCast;
-use web_sys::{HtmlButtonElement, HtmlElement, HtmlInputElement};
+use web_sys::{HtmlButtonElement, HtmlElement, HtmlInputElement, HtmlSelectElement};
 
 // --------------------------------------------------
 
@@ -19,6 +20,17 @@ const RAY_BOUNCE_PER_RECURSION_LIMIT_MIN_MAX: (f64, f64) = (5.0, 500.0);
 
 const VFOV_DEFAULT: f64 = 90.0;
 
+const ORTHOGRAPHIC_HEIGHT_DEFAULT: f64 = 4.0;
+const ORTHOGRAPHIC_HEIGHT_MIN_MAX: (f64, f64) = (0.01, 100000.0);
+
+const INTEROCULAR_DISTANCE_DEFAULT: f64 = 0.065;
+const INTEROCULAR_DISTANCE_MIN_MAX: (f64, f64) = (0.0, 100.0);
+
+const CONVERGENCE_DISTANCE_DEFAULT: f64 = 2.0;
+const CONVERGENCE_DISTANCE_MIN_MAX: (f64, f64) = (0.01, 100000.0);
+
+const ANIMATION_TIME_DEFAULT: f64 = 0.0;
+
 const AUTO_RESIZE_DEFAULT: bool = true;
 const RESOLUTION_X_DEFAULT: f64 = 1920.0;
 const RESOLUTION_Y_DEFAULT: f64 = 1080.0;
@@ -30,6 +42,7 @@ pub struct Settings {
     pub show: bool,
     pub panel_element: HtmlElement,
     pub input_elements: HashMap<String, HtmlInputElement>,
+    pub select_elements: HashMap<String, HtmlSelectElement>,
     pub button_elements: HashMap<String, HtmlButtonElement>,
     pub file_input_element: HtmlInputElement,
     pub labels: HashMap<String, HtmlElement>,
@@ -89,9 +102,41 @@ impl Settings {
                     Ok(v) => elem.1.set_value_as_number(v.clamp(20.0, 100000.0)),
                     Err(_) => elem.1.set_value_as_number(RESOLUTION_Y_DEFAULT),
                 },
+                "orthographic_height" => match elem.1.value().parse::<f64>() {
+                    Ok(v) => elem.1.set_value_as_number(v.clamp(
+                        ORTHOGRAPHIC_HEIGHT_MIN_MAX.0,
+                        ORTHOGRAPHIC_HEIGHT_MIN_MAX.1,
+                    )),
+                    Err(_) => elem.1.set_value_as_number(ORTHOGRAPHIC_HEIGHT_DEFAULT),
+                },
+                "interocular_distance" => match elem.1.value().parse::<f64>() {
+                    Ok(v) => elem.1.set_value_as_number(v.clamp(
+                        INTEROCULAR_DISTANCE_MIN_MAX.0,
+                        INTEROCULAR_DISTANCE_MIN_MAX.1,
+                    )),
+                    Err(_) => elem.1.set_value_as_number(INTEROCULAR_DISTANCE_DEFAULT),
+                },
+                "convergence_distance" => match elem.1.value().parse::<f64>() {
+                    Ok(v) => elem.1.set_value_as_number(v.clamp(
+                        CONVERGENCE_DISTANCE_MIN_MAX.0,
+                        CONVERGENCE_DISTANCE_MIN_MAX.1,
+                    )),
+                    Err(_) => elem.1.set_value_as_number(CONVERGENCE_DISTANCE_DEFAULT),
+                },
+                "animation_time" => match elem.1.value().parse::<f64>() {
+                    Ok(v) => elem.1.set_value_as_number(v.clamp(0.0, 1.0)),
+                    Err(_) => elem.1.set_value_as_number(ANIMATION_TIME_DEFAULT),
+                },
                 _ => unreachable!(),
             }
         }
+        self.input_elements["orthographic_height"].set_disabled(
+            self.select_elements["projection"].value() != "orthographic",
+        );
+
+        let stereo_off = self.select_elements["stereo_mode"].value() == "off";
+        self.input_elements["interocular_distance"].set_disabled(stereo_off);
+        self.input_elements["convergence_distance"].set_disabled(stereo_off);
     }
 
     pub fn visibility(&self) -> &bool {
@@ -143,6 +188,56 @@ impl Settings {
         }
     }
 
+    pub fn projection(&self) -> Projection {
+        match self.select_elements["projection"].value().as_str() {
+            "orthographic" => Projection::Orthographic {
+                view_height: match self.input_elements["orthographic_height"]
+                    .value()
+                    .parse::<f32>()
+                {
+                    Ok(v) => v,
+                    Err(_) => ORTHOGRAPHIC_HEIGHT_DEFAULT as f32,
+                },
+            },
+            "fisheye" => Projection::Fisheye,
+            "equirectangular" => Projection::Equirectangular,
+            _ => Projection::Perspective,
+        }
+    }
+
+    pub fn stereo(&self) -> Stereo {
+        Stereo::new(
+            match self.select_elements["stereo_mode"].value().as_str() {
+                "side_by_side" => StereoMode::SideBySide,
+                "top_bottom" => StereoMode::TopBottom,
+                "anaglyph" => StereoMode::Anaglyph,
+                _ => StereoMode::Off,
+            },
+            match self.input_elements["interocular_distance"]
+                .value()
+                .parse::<f32>()
+            {
+                Ok(v) => v,
+                Err(_) => INTEROCULAR_DISTANCE_DEFAULT as f32,
+            },
+            match self.input_elements["convergence_distance"]
+                .value()
+                .parse::<f32>()
+            {
+                Ok(v) => v,
+                Err(_) => CONVERGENCE_DISTANCE_DEFAULT as f32,
+            },
+        )
+    }
+
+    // Fraction of the animation duration shown in the preview
+    pub fn animation_time(&self) -> f32 {
+        match self.input_elements["animation_time"].value().parse::<f32>() {
+            Ok(v) => v,
+            Err(_) => ANIMATION_TIME_DEFAULT as f32,
+        }
+    }
+
     pub fn auto_resize(&self) -> bool {
         self.input_elements["auto_resize"].checked()
     }
@@ -218,6 +313,44 @@ impl Default for Settings {
                     .dyn_into::<HtmlInputElement>()
                     .unwrap(),
             ),
+            (
+                "orthographic_height".to_string(),
+                get_element_by_id("orthographic_height")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "interocular_distance".to_string(),
+                get_element_by_id("interocular_distance")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "convergence_distance".to_string(),
+                get_element_by_id("convergence_distance")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+            (
+                "animation_time".to_string(),
+                get_element_by_id("animation_time")
+                    .dyn_into::<HtmlInputElement>()
+                    .unwrap(),
+            ),
+        ]);
+        let select_elements = HashMap::from([
+            (
+                "projection".to_string(),
+                get_element_by_id("projection")
+                    .dyn_into::<HtmlSelectElement>()
+                    .unwrap(),
+            ),
+            (
+                "stereo_mode".to_string(),
+                get_element_by_id("stereo_mode")
+                    .dyn_into::<HtmlSelectElement>()
+                    .unwrap(),
+            ),
         ]);
         let labels = HashMap::from([("scene_label".to_string(), get_element_by_id("scene_label"))]);
 
@@ -268,6 +401,19 @@ impl Default for Settings {
                     elem.1.set_value_as_number(RESOLUTION_Y_DEFAULT);
                     elem.1.set_disabled(true);
                 }
+                "orthographic_height" => {
+                    elem.1.set_value_as_number(ORTHOGRAPHIC_HEIGHT_DEFAULT);
+                    elem.1.set_disabled(true);
+                }
+                "interocular_distance" => {
+                    elem.1.set_value_as_number(INTEROCULAR_DISTANCE_DEFAULT);
+                    elem.1.set_disabled(true);
+                }
+                "convergence_distance" => {
+                    elem.1.set_value_as_number(CONVERGENCE_DISTANCE_DEFAULT);
+                    elem.1.set_disabled(true);
+                }
+                "animation_time" => elem.1.set_value_as_number(ANIMATION_TIME_DEFAULT),
                 _ => {
                     unreachable!()
                 }
@@ -287,6 +433,7 @@ impl Default for Settings {
             show: true,
             panel_element: setting_panel_element,
             input_elements,
+            select_elements,
             button_elements,
             file_input_element,
             labels,
@@ -0,0 +1,286 @@
+use std::ops::Mul;
+
+use super::vec_3d::Vec3D;
+
+// --------------------------------------------------
+
+// Row major 4x4 matrix for affine transforms, points are treated as column vectors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4(pub [[f32; 4]; 4]);
+
+impl Mat4 {
+    pub const fn identity() -> Mat4 {
+        Mat4([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Column major, as stored by OpenGL and glTF
+    pub fn from_cols_array(cols: &[f32; 16]) -> Mat4 {
+        let mut out = [[0.0; 4]; 4];
+        for (i, value) in cols.iter().enumerate() {
+            out[i % 4][i / 4] = *value;
+        }
+        Mat4(out)
+    }
+
+    // Scale first, then rotate by the unit quaternion (x, y, z, w), then translate
+    pub fn from_translation_rotation_scale(
+        translation: Vec3D<f32>,
+        rotation: [f32; 4],
+        scale: Vec3D<f32>,
+    ) -> Mat4 {
+        let [x, y, z, w] = rotation;
+        let rotation = [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ];
+        let scale = [scale.x, scale.y, scale.z];
+        let translation = [translation.x, translation.y, translation.z];
+
+        let mut out = Mat4::identity();
+        for row in 0..3 {
+            for col in 0..3 {
+                out.0[row][col] = rotation[row][col] * scale[col];
+            }
+            out.0[row][3] = translation[row];
+        }
+        out
+    }
+
+    pub fn transform_point(&self, p: Vec3D<f32>) -> Vec3D<f32> {
+        let m = &self.0;
+        Vec3D::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+
+    // Ignores the translation
+    pub fn transform_vector(&self, v: Vec3D<f32>) -> Vec3D<f32> {
+        let m = &self.0;
+        Vec3D::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    pub fn transposed(&self) -> Mat4 {
+        let mut out = [[0.0; 4]; 4];
+        for (row, values) in self.0.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                out[col][row] = *value;
+            }
+        }
+        Mat4(out)
+    }
+
+    // Cofactor expansion, None for singular matrices
+    pub fn inverse(&self) -> Option<Mat4> {
+        let m: Vec<f32> = self.0.iter().flatten().copied().collect();
+        let mut inv = [0.0; 16];
+
+        inv[0] = m[5] * m[10] * m[15] - m[5] * m[11] * m[14] - m[9] * m[6] * m[15]
+            + m[9] * m[7] * m[14]
+            + m[13] * m[6] * m[11]
+            - m[13] * m[7] * m[10];
+        inv[4] = -m[4] * m[10] * m[15] + m[4] * m[11] * m[14] + m[8] * m[6] * m[15]
+            - m[8] * m[7] * m[14]
+            - m[12] * m[6] * m[11]
+            + m[12] * m[7] * m[10];
+        inv[8] = m[4] * m[9] * m[15] - m[4] * m[11] * m[13] - m[8] * m[5] * m[15]
+            + m[8] * m[7] * m[13]
+            + m[12] * m[5] * m[11]
+            - m[12] * m[7] * m[9];
+        inv[12] = -m[4] * m[9] * m[14] + m[4] * m[10] * m[13] + m[8] * m[5] * m[14]
+            - m[8] * m[6] * m[13]
+            - m[12] * m[5] * m[10]
+            + m[12] * m[6] * m[9];
+        inv[1] = -m[1] * m[10] * m[15] + m[1] * m[11] * m[14] + m[9] * m[2] * m[15]
+            - m[9] * m[3] * m[14]
+            - m[13] * m[2] * m[11]
+            + m[13] * m[3] * m[10];
+        inv[5] = m[0] * m[10] * m[15] - m[0] * m[11] * m[14] - m[8] * m[2] * m[15]
+            + m[8] * m[3] * m[14]
+            + m[12] * m[2] * m[11]
+            - m[12] * m[3] * m[10];
+        inv[9] = -m[0] * m[9] * m[15] + m[0] * m[11] * m[13] + m[8] * m[1] * m[15]
+            - m[8] * m[3] * m[13]
+            - m[12] * m[1] * m[11]
+            + m[12] * m[3] * m[9];
+        inv[13] = m[0] * m[9] * m[14] - m[0] * m[10] * m[13] - m[8] * m[1] * m[14]
+            + m[8] * m[2] * m[13]
+            + m[12] * m[1] * m[10]
+            - m[12] * m[2] * m[9];
+        inv[2] = m[1] * m[6] * m[15] - m[1] * m[7] * m[14] - m[5] * m[2] * m[15]
+            + m[5] * m[3] * m[14]
+            + m[13] * m[2] * m[7]
+            - m[13] * m[3] * m[6];
+        inv[6] = -m[0] * m[6] * m[15] + m[0] * m[7] * m[14] + m[4] * m[2] * m[15]
+            - m[4] * m[3] * m[14]
+            - m[12] * m[2] * m[7]
+            + m[12] * m[3] * m[6];
+        inv[10] = m[0] * m[5] * m[15] - m[0] * m[7] * m[13] - m[4] * m[1] * m[15]
+            + m[4] * m[3] * m[13]
+            + m[12] * m[1] * m[7]
+            - m[12] * m[3] * m[5];
+        inv[14] = -m[0] * m[5] * m[14] + m[0] * m[6] * m[13] + m[4] * m[1] * m[14]
+            - m[4] * m[2] * m[13]
+            - m[12] * m[1] * m[6]
+            + m[12] * m[2] * m[5];
+        inv[3] = -m[1] * m[6] * m[11] + m[1] * m[7] * m[10] + m[5] * m[2] * m[11]
+            - m[5] * m[3] * m[10]
+            - m[9] * m[2] * m[7]
+            + m[9] * m[3] * m[6];
+        inv[7] = m[0] * m[6] * m[11] - m[0] * m[7] * m[10] - m[4] * m[2] * m[11]
+            + m[4] * m[3] * m[10]
+            + m[8] * m[2] * m[7]
+            - m[8] * m[3] * m[6];
+        inv[11] = -m[0] * m[5] * m[11] + m[0] * m[7] * m[9] + m[4] * m[1] * m[11]
+            - m[4] * m[3] * m[9]
+            - m[8] * m[1] * m[7]
+            + m[8] * m[3] * m[5];
+        inv[15] = m[0] * m[5] * m[10] - m[0] * m[6] * m[9] - m[4] * m[1] * m[10]
+            + m[4] * m[2] * m[9]
+            + m[8] * m[1] * m[6]
+            - m[8] * m[2] * m[5];
+
+        let det = m[0] * inv[0] + m[1] * inv[4] + m[2] * inv[8] + m[3] * inv[12];
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+
+        let mut out = [[0.0; 4]; 4];
+        for (i, value) in inv.iter().enumerate() {
+            out[i / 4][i % 4] = value / det;
+        }
+        Some(Mat4(out))
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::identity()
+    }
+}
+
+// Mul
+impl Mul<Mat4> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut out = [[0.0; 4]; 4];
+        for (row, values) in out.iter_mut().enumerate() {
+            for (col, value) in values.iter_mut().enumerate() {
+                *value = (0..4).map(|i| self.0[row][i] * other.0[i][col]).sum();
+            }
+        }
+        Mat4(out)
+    }
+}
+
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::VectorOperation;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn assert_identity(m: Mat4) {
+        for (row, values) in m.0.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                let expected = if row == col { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < 1e-4, "{m:?} is not the identity");
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_times_matrix_is_identity() {
+        let mut rng = StdRng::seed_from_u64(35);
+
+        for _ in 0..100 {
+            let mut random = || rng.gen_range(-2.0..2.0);
+            let m = Mat4([
+                [random(), random(), random(), random()],
+                [random(), random(), random(), random()],
+                [random(), random(), random(), random()],
+                [0.0, 0.0, 0.0, 1.0],
+            ]);
+
+            // Random matrices close to singular lose too much precision to compare
+            let Some(inverse) = m.inverse() else { continue };
+            if inverse.0.iter().flatten().any(|value| value.abs() > 100.0) {
+                continue;
+            }
+            assert_identity(inverse * m);
+            assert_identity(m * inverse);
+        }
+    }
+
+    #[test]
+    fn affine_inverse_undoes_the_transform() {
+        let m = Mat4::from_translation_rotation_scale(
+            Vec3D::new(1.0, -2.0, 3.0),
+            // Half a turn around the y axis
+            [0.0, 1.0, 0.0, 0.0],
+            Vec3D::new(2.0, 0.5, 4.0),
+        );
+        let inverse = m.inverse().unwrap();
+        assert_identity(inverse * m);
+
+        let p = Vec3D::new(0.5, 1.0, -1.0);
+        assert_eq!(m.transform_point(p), Vec3D::new(0.0, -1.5, 7.0));
+        assert!((inverse.transform_point(m.transform_point(p)) - p).length() < 1e-5);
+        // Vectors skip the translation
+        assert_eq!(m.transform_vector(p), Vec3D::new(-1.0, 0.5, 4.0));
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        let flat = Mat4::from_translation_rotation_scale(
+            Vec3D::new(1.0, 2.0, 3.0),
+            [0.0, 0.0, 0.0, 1.0],
+            Vec3D::new(1.0, 0.0, 1.0),
+        );
+        assert_eq!(flat.inverse(), None);
+
+        let repeated_row = Mat4([
+            [1.0, 2.0, 3.0, 4.0],
+            [1.0, 2.0, 3.0, 4.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert_eq!(repeated_row.inverse(), None);
+        assert_eq!(Mat4([[f32::NAN; 4]; 4]).inverse(), None);
+    }
+
+    #[test]
+    fn column_major_arrays_are_transposed() {
+        let cols: [f32; 16] = std::array::from_fn(|i| i as f32);
+        let m = Mat4::from_cols_array(&cols);
+
+        assert_eq!(m.0[0], [0.0, 4.0, 8.0, 12.0]);
+        assert_eq!(m.transposed().0[0], [0.0, 1.0, 2.0, 3.0]);
+    }
+}
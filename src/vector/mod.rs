@@ -1,7 +1,9 @@
+mod mat_4;
 mod traits;
 mod vec_2d;
 mod vec_3d;
 
+pub use mat_4::Mat4;
 pub use traits::VectorOperation;
 pub use vec_2d::Vec2D;
 pub use vec_3d::Vec3D;
@@ -73,6 +73,13 @@ impl Vec3D<f32> {
         )
     }
 
+    // Rodrigues' rotation around an arbitrary unit axis
+    pub fn rotate_around(&self, axis: Vec3D<f32>, theta: f32) -> Vec3D<f32> {
+        *self * theta.cos()
+            + axis.cross(self) * theta.sin()
+            + axis * (axis.dot(self) * (1.0 - theta.cos()))
+    }
+
     pub fn rotate_around_z(&self, theta: f32) -> Vec3D<f32> {
         let rot_old = self;
         Vec3D::new(